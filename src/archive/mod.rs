@@ -0,0 +1,117 @@
+use std::fs::{self, OpenOptions};
+use std::io::{stdout, Write};
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::arena::Termination;
+use crate::chess::color::Color;
+use crate::chess::game::Game;
+use crate::chess::pgn::{parse_pgn, time_now, to_san, today, PgnGame};
+use crate::eco;
+
+// Every game played at the terminal is added to a PGN file as it ends, whether it was finished or
+// abandoned, with the date and time it ended, the players, how it ended and how long each move
+// took. The archive screen lists the games newest first and narrows them down by words that have
+// to appear in the players' names, the date, the result or the way the game ended.
+
+pub const ARCHIVE_FILE: &str = "archive.pgn";
+
+// Games listed on one page of the archive screen.
+pub const PAGE: usize = 15;
+
+// The game, which started from the initial position, as PGN. The result is "*" for a game that
+// was not decided.
+pub fn to_pgn(game: &Game, event: &str, round: &str, result: &str, termination: Termination, times: &[Duration]) -> PgnGame {
+    let mut replay = Game::new();
+    replay.default_board();
+    let mut pgn = PgnGame::new();
+    for m in game.history() {
+        pgn.moves.push(to_san(&replay, m));
+        replay.do_move(*m);
+        replay.next_turn();
+    }
+    if times.len() == pgn.moves.len() {
+        pgn.times = times.to_vec();
+    }
+    pgn.tags = vec![
+        (String::from("Event"), event.to_string()),
+        (String::from("Site"), String::from("jchess")),
+        (String::from("Date"), today()),
+        (String::from("Round"), round.to_string()),
+        (String::from("White"), game.player(Color::White).id_string()),
+        (String::from("Black"), game.player(Color::Black).id_string()),
+        (String::from("Result"), result.to_string()),
+        (String::from("UTCDate"), today()),
+        (String::from("UTCTime"), time_now()),
+        (String::from("Termination"), termination.to_string()),
+    ];
+    if let Some(o) = eco::classify(game.history()) {
+        pgn.tags.push((String::from("ECO"), o.code.to_string()));
+        pgn.tags.push((String::from("Opening"), o.name.to_string()));
+    }
+    pgn.result = result.to_string();
+    return pgn;
+}
+
+pub fn append(path: &Path, pgn: &PgnGame) -> Result<(), String> {
+    let written = OpenOptions::new().create(true).append(true).open(path)
+        .and_then(|mut f| f.write_all(pgn.to_string().as_bytes()));
+    return match written {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("Could not write {}: {}", path.display(), e)),
+    };
+}
+
+// The archived games in the order they were played; none when there is no archive yet.
+pub fn load(path: &Path) -> Result<Vec<PgnGame>, String> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    return match fs::read_to_string(path) {
+        Ok(text) => Ok(parse_pgn(&text)),
+        Err(e) => Err(format!("Could not read {}: {}", path.display(), e)),
+    };
+}
+
+// Whether every word of the filter appears in one of the tags it looks at, ignoring case.
+pub fn matches(pgn: &PgnGame, filter: &str) -> bool {
+    let fields: Vec<String> = ["White", "Black", "Date", "Result", "Termination", "Event"].iter()
+        .map(|t| pgn.tag(t).unwrap_or_default().to_lowercase())
+        .collect();
+    return filter.split_whitespace().all(|w| {
+        let w = w.to_lowercase();
+        fields.iter().any(|f| f.contains(&w))
+    });
+}
+
+// One page of the games in `shown`, which are indices into `games`, newest first.
+pub fn print_list(games: &[PgnGame], shown: &[usize], page: usize, filter: &str, message: &str) {
+    let _ = Command::new("clear").status();
+    let cut = |s: String| s.chars().take(16).collect::<String>();
+    println!();
+    if filter.is_empty() {
+        println!("  Game Archive, {} games", games.len());
+    } else {
+        println!("  Game Archive, {} of {} games matching \"{}\"", shown.len(), games.len(), filter);
+    }
+    println!();
+    if shown.len() == 0 {
+        println!("  No games");
+    } else {
+        println!("  {:>4}  {:<10}  {:<8}  {:<16}  {:<16}  {:<7}  {:>5}  Termination", "#", "Date", "Time", "White", "Black", "Result",
+            "Moves");
+        for i in shown.iter().skip(page * PAGE).take(PAGE) {
+            let g = &games[*i];
+            let tag = |name: &str| g.tag(name).unwrap_or(String::from("?"));
+            println!("  {:>4}  {:<10}  {:<8}  {:<16}  {:<16}  {:<7}  {:>5}  {}", i + 1, tag("Date"), tag("UTCTime"), cut(tag("White")),
+                cut(tag("Black")), g.result, g.moves.len().div_ceil(2), tag("Termination"));
+        }
+        println!();
+        println!("  Page {} of {}", page + 1, shown.len().div_ceil(PAGE));
+    }
+    println!();
+    println!("  {}", message);
+    print!("  > ");
+    let _ = stdout().flush();
+}
@@ -0,0 +1,147 @@
+// Match statistics from wins, draws and losses: the Elo difference they point to, with a 95% error
+// margin, and the sequential probability ratio test that stops a match once the result is clear.
+// Both treat the games as independent draws of a score of 1, 0.5 or 0 and go by its mean and
+// variance (the normal approximation, as the usual testing tools do).
+
+#[derive(Copy, Clone)]
+pub struct Score {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+// The Elo difference at which the expected score is `score`.
+fn elo_of(score: f64) -> f64 {
+    // Adding zero turns -0 into 0 for printing.
+    return -400.0 * (1.0 / score - 1.0).log10() + 0.0;
+}
+
+fn expected_score(elo: f64) -> f64 {
+    return 1.0 / (1.0 + 10f64.powf(-elo / 400.0));
+}
+
+impl Score {
+    pub fn new() -> Self {
+        return Score{wins: 0, draws: 0, losses: 0};
+    }
+
+    // Counts a game scored 1, 0.5 or 0.
+    pub fn add(&mut self, points: f64) {
+        if points > 0.75 {
+            self.wins += 1;
+        } else if points < 0.25 {
+            self.losses += 1;
+        } else {
+            self.draws += 1;
+        }
+    }
+
+    pub fn games(&self) -> u32 {
+        return self.wins + self.draws + self.losses;
+    }
+
+    pub fn points(&self) -> f64 {
+        return self.wins as f64 + self.draws as f64 / 2.0;
+    }
+
+    // The mean score of a game and its variance.
+    fn mean_variance(&self) -> (f64, f64) {
+        let n = self.games() as f64;
+        let mean = self.points() / n;
+        let variance = (self.wins as f64 * (1.0 - mean).powi(2) + self.draws as f64 * (0.5 - mean).powi(2)
+            + self.losses as f64 * mean.powi(2)) / n;
+        return (mean, variance);
+    }
+
+    // The Elo difference and the half width of its 95% interval. None without games, or when every
+    // game was won or every game lost, which puts no bound on the difference.
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        if self.games() == 0 || self.wins == self.games() || self.losses == self.games() {
+            return None;
+        }
+        let (mean, variance) = self.mean_variance();
+        let deviation = (variance / self.games() as f64).sqrt();
+        let low = mean - 1.96 * deviation;
+        let high = mean + 1.96 * deviation;
+        let margin = if low <= 0.0 || high >= 1.0 { f64::INFINITY } else { (elo_of(high) - elo_of(low)) / 2.0 };
+        return Some((elo_of(mean), margin));
+    }
+
+    // "+35.2 +/- 40.1", or "-" when there is nothing to go by.
+    pub fn elo_string(&self) -> String {
+        return match self.elo() {
+            Some((elo, margin)) => format!("{:+.1} +/- {:.1}", elo, margin),
+            None if self.games() == 0 => String::from("-"),
+            None => String::from(if self.wins > 0 { "+inf" } else { "-inf" }),
+        };
+    }
+
+    pub fn to_string(self) -> String {
+        return format!("+{} ={} -{}", self.wins, self.draws, self.losses);
+    }
+}
+
+// Tests the hypothesis that the Elo difference is elo1 against that it is elo0, with false
+// positive and false negative rates alpha and beta.
+#[derive(Copy, Clone)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Sprt {
+    // "ELO0,ELO1" or "ELO0,ELO1,ALPHA,BETA"; alpha and beta default to 0.05.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let values: Vec<f64> = match text.split(',').map(|v| v.trim().parse::<f64>()).collect() {
+            Ok(v) => v,
+            Err(_) => return Err(format!("Bad SPRT bounds: {}", text)),
+        };
+        let sprt = match values.len() {
+            2 => Sprt{elo0: values[0], elo1: values[1], alpha: 0.05, beta: 0.05},
+            4 => Sprt{elo0: values[0], elo1: values[1], alpha: values[2], beta: values[3]},
+            _ => return Err(format!("Expected ELO0,ELO1[,ALPHA,BETA]: {}", text)),
+        };
+        if sprt.elo0 >= sprt.elo1 || !(0.0..0.5).contains(&sprt.alpha) || !(0.0..0.5).contains(&sprt.beta)
+            || sprt.alpha == 0.0 || sprt.beta == 0.0 {
+            return Err(format!("Bad SPRT bounds: {}", text));
+        }
+        return Ok(sprt);
+    }
+
+    // The log likelihood ratio bounds: elo0 is accepted below the first, elo1 above the second.
+    pub fn bounds(&self) -> (f64, f64) {
+        return ((self.beta / (1.0 - self.alpha)).ln(), ((1.0 - self.beta) / self.alpha).ln());
+    }
+
+    // The log likelihood ratio of elo1 against elo0 for the score so far.
+    pub fn llr(&self, score: &Score) -> f64 {
+        if score.games() == 0 {
+            return 0.0;
+        }
+        let (mean, variance) = score.mean_variance();
+        if variance == 0.0 {
+            return 0.0;
+        }
+        let (s0, s1) = (expected_score(self.elo0), expected_score(self.elo1));
+        return score.games() as f64 * (s1 - s0) * (2.0 * mean - s0 - s1) / (2.0 * variance);
+    }
+
+    // Some(true) once elo1 is accepted, Some(false) once elo0 is, None while undecided.
+    pub fn verdict(&self, score: &Score) -> Option<bool> {
+        let llr = self.llr(score);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            return Some(true);
+        }
+        if llr <= lower {
+            return Some(false);
+        }
+        return None;
+    }
+
+    pub fn to_string(self) -> String {
+        return format!("elo0 {} elo1 {} alpha {} beta {}", self.elo0, self.elo1, self.alpha, self.beta);
+    }
+}
@@ -0,0 +1,414 @@
+pub mod elo;
+pub mod selfplay;
+pub mod tournament;
+
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+use crate::bitbase::{material, Material};
+use crate::chess::color::Color;
+use crate::chess::fen::parse_fen;
+use crate::chess::game::Game;
+use crate::chess::piece::PieceKind;
+use crate::chess::pgn::{to_san, today, PgnGame};
+use crate::chess::player::{Limits, Player, Thinking};
+use crate::chess::r#move::Move;
+use crate::chess::zobrist;
+use crate::eco;
+use crate::protocol::time_for_move;
+
+// Games between two Players without the interactive UI, for the subcommands that play many games.
+// Game itself knows checkmate and stalemate only, so the usual draws are adjudicated here: threefold
+// repetition, the fifty move rule, and too little material for either side to mate. A game that
+// runs past the ply limit is called a draw too. Games can also be played on a clock, and decided
+// early on the scores the players report, as Rules says.
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Termination {
+    Checkmate,
+    Stalemate,
+    Repetition,
+    FiftyMoves,
+    Material,
+    MaxPlies,
+    Forfeit, // a player had no move to give or gave an illegal one
+    Time,
+    Adjudication, // on the players' scores
+    Resignation,
+    Abandoned, // stopped before it was decided
+}
+
+impl Termination {
+    pub fn to_string(self) -> String {
+        return match self {
+            Termination::Checkmate => String::from("checkmate"),
+            Termination::Stalemate => String::from("stalemate"),
+            Termination::Repetition => String::from("threefold repetition"),
+            Termination::FiftyMoves => String::from("fifty move rule"),
+            Termination::Material => String::from("insufficient material"),
+            Termination::MaxPlies => String::from("move limit"),
+            Termination::Forfeit => String::from("forfeit"),
+            Termination::Time => String::from("time forfeit"),
+            Termination::Adjudication => String::from("adjudication"),
+            Termination::Resignation => String::from("resignation"),
+            Termination::Abandoned => String::from("abandoned"),
+        };
+    }
+}
+
+pub struct GameRecord {
+    pub white: String,
+    pub black: String,
+    pub start: Option<String>, // the FEN the game started from, when not the initial position
+    pub moves: Vec<Move>, // from the start position, the opening included
+    pub sans: Vec<String>,
+    pub result: String, // "1-0", "0-1" or "1/2-1/2"
+    pub termination: Termination,
+}
+
+impl GameRecord {
+    // The result for white: 1, 0.5 or 0.
+    pub fn white_score(&self) -> f64 {
+        return match self.result.as_str() {
+            "1-0" => 1.0,
+            "0-1" => 0.0,
+            _ => 0.5,
+        };
+    }
+
+    // Every position of the game with the move played from it, in order.
+    pub fn positions(&self) -> Vec<(Game, Move)> {
+        let mut game = start_position(self.start.as_deref());
+        let mut positions = Vec::with_capacity(self.moves.len());
+        for m in &self.moves {
+            positions.push((game.clone(), *m));
+            game.do_move(*m);
+            game.next_turn();
+        }
+        return positions;
+    }
+
+    pub fn to_pgn(&self, event: &str, round: usize) -> PgnGame {
+        let mut pgn = PgnGame::new();
+        pgn.tags = vec![
+            (String::from("Event"), event.to_string()),
+            (String::from("Site"), String::from("jchess")),
+            (String::from("Date"), today()),
+            (String::from("Round"), round.to_string()),
+            (String::from("White"), self.white.clone()),
+            (String::from("Black"), self.black.clone()),
+            (String::from("Result"), self.result.clone()),
+            (String::from("Termination"), self.termination.to_string()),
+        ];
+        if let Some(fen) = &self.start {
+            pgn.tags.push((String::from("SetUp"), String::from("1")));
+            pgn.tags.push((String::from("FEN"), fen.clone()));
+        } else if let Some(o) = eco::classify(&self.moves) {
+            pgn.tags.push((String::from("ECO"), o.code.to_string()));
+            pgn.tags.push((String::from("Opening"), o.name.to_string()));
+        }
+        pgn.moves = self.sans.clone();
+        pgn.result = self.result.clone();
+        return pgn;
+    }
+}
+
+// Random legal moves from the initial position. An opening that ends the game is thrown away and
+// another one is tried.
+pub fn random_opening<R: Rng>(rng: &mut R, plies: usize) -> Vec<Move> {
+    loop {
+        let mut game = Game::new();
+        game.default_board();
+        let mut moves = Vec::new();
+        for _ in 0..plies {
+            let legal = game.list_valid_moves();
+            if legal.len() == 0 {
+                break;
+            }
+            let m = legal[rng.random_range(0..legal.len())];
+            moves.push(m);
+            game.do_move(m);
+            game.next_turn();
+        }
+        if moves.len() == plies && game.any_valid_moves() {
+            return moves;
+        }
+    }
+}
+
+// The draws Game does not know about, tracked move by move: threefold repetition, the fifty move
+// rule, and too little material to mate. Shared with the interactive spectator mode and xboard.
+#[derive(Clone)]
+pub struct DrawRules {
+    seen: Vec<u64>, // hash of every position so far
+    quiet_plies: u32,
+}
+
+impl DrawRules {
+    pub fn new(game: &Game) -> Self {
+        return DrawRules{seen: vec![zobrist::hash(game)], quiet_plies: 0};
+    }
+
+    // Call with the position after the move.
+    pub fn record(&mut self, game: &Game, m: &Move) {
+        let zeroing = m.takes || m.en_passant.is_some() || m.piece.kind == PieceKind::Pawn;
+        self.quiet_plies = if zeroing { 0 } else { self.quiet_plies + 1 };
+        self.seen.push(zobrist::hash(game));
+    }
+
+    pub fn check(&self, game: &Game) -> Option<Termination> {
+        let key = self.seen[self.seen.len() - 1];
+        if self.seen.iter().filter(|k| **k == key).count() >= 3 {
+            return Some(Termination::Repetition);
+        }
+        if self.quiet_plies >= 100 {
+            return Some(Termination::FiftyMoves);
+        }
+        if let Material::Draw = material(game) {
+            return Some(Termination::Material);
+        }
+        return None;
+    }
+}
+
+// Castles are told apart by their flags alone, since generated ones carry no squares.
+fn same_move(a: &Move, b: &Move) -> bool {
+    if a.castle || a.long_castle || b.castle || b.long_castle {
+        return a.castle == b.castle && a.long_castle == b.long_castle;
+    }
+    return a.origin == b.origin && a.dest == b.dest && a.promotion == b.promotion;
+}
+
+pub fn start_position(fen: Option<&str>) -> Game {
+    if let Some(Ok(game)) = fen.map(parse_fen) {
+        return game;
+    }
+    let mut game = Game::new();
+    game.default_board();
+    return game;
+}
+
+// Where a game starts: a position, the initial one unless a FEN is given, and moves played from
+// it for both players before they take over.
+#[derive(Clone)]
+pub struct Opening {
+    pub fen: Option<String>,
+    pub moves: Vec<Move>,
+}
+
+#[derive(Copy, Clone)]
+pub enum TimeControl {
+    Unlimited, // the players search as they normally would
+    PerMove(Duration),
+    // `moves` moves in `base`, then the same again; with moves at 0 the base is for the whole game.
+    // The increment is added after every move.
+    Clock{moves: u32, base: Duration, inc: Duration},
+}
+
+impl TimeControl {
+    // "40/60+0.5", "10+0.1" or "300", in seconds.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let bad = || format!("Bad time control: {}", text);
+        let (moves, rest) = match text.split_once('/') {
+            Some((m, r)) => (m.parse::<u32>().map_err(|_| bad())?, r),
+            None => (0, text),
+        };
+        let (base, inc) = match rest.split_once('+') {
+            Some((b, i)) => (b, i),
+            None => (rest, "0"),
+        };
+        let seconds = |t: &str| t.parse::<f64>().ok().filter(|s| *s >= 0.0 && s.is_finite()).map(Duration::from_secs_f64);
+        let base = seconds(base).filter(|b| !b.is_zero()).ok_or_else(bad)?;
+        let inc = seconds(inc).ok_or_else(bad)?;
+        return Ok(TimeControl::Clock{moves: moves, base: base, inc: inc});
+    }
+
+    pub fn to_string(self) -> String {
+        return match self {
+            TimeControl::Unlimited => String::from("-"),
+            TimeControl::PerMove(t) => format!("{}/move", t.as_secs_f64()),
+            TimeControl::Clock{moves: 0, base, inc} => format!("{}+{}", base.as_secs_f64(), inc.as_secs_f64()),
+            TimeControl::Clock{moves, base, inc} => format!("{}/{}+{}", moves, base.as_secs_f64(), inc.as_secs_f64()),
+        };
+    }
+}
+
+// A game is resigned for a player once, for `moves` moves in a row by each side, both players'
+// scores have put it at least `score` centipawns behind.
+#[derive(Copy, Clone)]
+pub struct ResignAdjudication {
+    pub moves: usize,
+    pub score: i32,
+}
+
+// A game is drawn from move number `after` on once, for `moves` moves in a row by each side, both
+// players' scores have been within `score` centipawns of equal.
+#[derive(Copy, Clone)]
+pub struct DrawAdjudication {
+    pub after: usize,
+    pub moves: usize,
+    pub score: i32,
+}
+
+#[derive(Copy, Clone)]
+pub struct Rules {
+    pub max_plies: usize,
+    pub time: TimeControl,
+    pub resign: Option<ResignAdjudication>,
+    pub draw: Option<DrawAdjudication>,
+}
+
+impl Rules {
+    pub fn new(max_plies: usize) -> Self {
+        return Rules{max_plies: max_plies, time: TimeControl::Unlimited, resign: None, draw: None};
+    }
+
+    // Goes over the last scores, one per ply from white's side, None where the player reported none.
+    fn adjudicate(&self, scores: &[Option<i32>], plies: usize, start_move: usize) -> Option<&'static str> {
+        let last = |n: usize| -> Option<Vec<i32>> {
+            if n == 0 || scores.len() < 2 * n {
+                return None;
+            }
+            return scores[scores.len() - 2 * n..].iter().copied().collect();
+        };
+        if let Some(r) = self.resign {
+            if let Some(s) = last(r.moves) {
+                if s.iter().all(|x| *x >= r.score) {
+                    return Some("1-0");
+                }
+                if s.iter().all(|x| *x <= -r.score) {
+                    return Some("0-1");
+                }
+            }
+        }
+        if let Some(d) = self.draw {
+            if start_move + plies / 2 >= d.after && last(d.moves).is_some_and(|s| s.iter().all(|x| x.abs() <= d.score)) {
+                return Some("1/2-1/2");
+            }
+        }
+        return None;
+    }
+}
+
+// Plays one game from the initial position, the opening moves first. Both players are reset
+// before the game starts.
+pub fn play_game(white: &mut dyn Player, black: &mut dyn Player, opening: &[Move], max_plies: usize) -> GameRecord {
+    return play_match(white, black, &Opening{fen: None, moves: opening.to_vec()}, &Rules::new(max_plies));
+}
+
+// Plays one game under the rules. Time is only kept from the end of the opening.
+pub fn play_match(white: &mut dyn Player, black: &mut dyn Player, opening: &Opening, rules: &Rules) -> GameRecord {
+    white.reset();
+    black.reset();
+    let mut game = start_position(opening.fen.as_deref());
+    let start_move = match &opening.fen {
+        Some(fen) => fen.split_whitespace().nth(5).and_then(|n| n.parse::<usize>().ok()).unwrap_or(1),
+        None => 1,
+    };
+    let mut record = GameRecord{
+        white: white.id_string(),
+        black: black.id_string(),
+        start: opening.fen.clone(),
+        moves: Vec::new(),
+        sans: Vec::new(),
+        result: String::from("1/2-1/2"),
+        termination: Termination::MaxPlies,
+    };
+    let mut draws = DrawRules::new(&game);
+    let mut scores: Vec<Option<i32>> = Vec::new(); // from white's side, one per ply out of the opening
+    let (mut clock, mut moves_made) = match rules.time {
+        TimeControl::Clock{base, ..} => ([base, base], [0, 0]),
+        _ => ([Duration::ZERO; 2], [0, 0]),
+    };
+    let lose = |to_move: Color| String::from(if to_move == Color::White { "0-1" } else { "1-0" });
+
+    loop {
+        let to_move = game.current_color();
+        let side = if to_move == Color::White { 0 } else { 1 };
+        let legal = game.list_valid_moves_with_underpromotions();
+        if legal.len() == 0 {
+            if game.is_check() {
+                record.result = lose(to_move);
+                record.termination = Termination::Checkmate;
+            } else {
+                record.termination = Termination::Stalemate;
+            }
+            return record;
+        }
+        if let Some(t) = draws.check(&game) {
+            record.termination = t;
+            return record;
+        }
+        if let Some(result) = rules.adjudicate(&scores, record.moves.len(), start_move) {
+            record.result = String::from(result);
+            record.termination = Termination::Adjudication;
+            return record;
+        }
+        if record.moves.len() >= rules.max_plies {
+            return record;
+        }
+
+        let ply = record.moves.len();
+        let chosen = if ply < opening.moves.len() {
+            Ok(opening.moves[ply])
+        } else {
+            let mut limits = Limits::new();
+            match rules.time {
+                TimeControl::Unlimited => {},
+                TimeControl::PerMove(t) => limits.movetime = Some(t),
+                TimeControl::Clock{moves, inc, ..} => {
+                    let to_go = if moves == 0 { None } else { Some(moves - moves_made[side] % moves) };
+                    limits.movetime = Some(time_for_move(clock[side], inc, to_go));
+                },
+            }
+            let score = Arc::new(Mutex::new(None));
+            let reported = score.clone();
+            let report = Box::new(move |t: &Thinking| *reported.lock().unwrap() = Some(t.score));
+            let stop = Arc::new(AtomicBool::new(false));
+            let started = Instant::now();
+            let chosen = if to_move == Color::White {
+                white.think(&game, limits, stop, report)
+            } else {
+                black.think(&game, limits, stop, report)
+            };
+            if let TimeControl::Clock{moves, base, inc} = rules.time {
+                let used = started.elapsed();
+                if used > clock[side] {
+                    record.result = lose(to_move);
+                    record.termination = Termination::Time;
+                    return record;
+                }
+                moves_made[side] += 1;
+                clock[side] = clock[side] - used + inc;
+                if moves > 0 && moves_made[side] % moves == 0 {
+                    clock[side] += base;
+                }
+            }
+            let score = *score.lock().unwrap();
+            scores.push(score.map(|s| if to_move == Color::White { s } else { -s }));
+            chosen
+        };
+        let m = match chosen.ok().and_then(|c| legal.iter().find(|l| same_move(l, &c)).copied()) {
+            Some(m) => m,
+            None => {
+                record.result = lose(to_move);
+                record.termination = Termination::Forfeit;
+                return record;
+            },
+        };
+
+        record.sans.push(to_san(&game, &m));
+        record.moves.push(m);
+        game.do_move(m);
+        game.next_turn();
+        draws.record(&game, &m);
+        if to_move == Color::White {
+            black.opponent_moved(&game);
+        } else {
+            white.opponent_moved(&game);
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use crate::bots::{from_spec, SPEC_HELP};
+use crate::chess::color::Color;
+use crate::chess::fen::to_fen;
+use crate::eval::evaluate;
+use crate::eval::weights::{Weights, WEIGHTS_FILE};
+use crate::input::args::Args;
+use crate::storage::data_file;
+
+use super::*;
+
+// Plays bots against each other to produce training data:
+//   jchess selfplay [--white BOT] [--black BOT] [--games N] [--threads N] [--opening-plies N]
+//                   [--max-plies N] [--sample RATE] [--seed N] [--pgn FILE] [--positions FILE]
+// Every pair of games starts from the same random opening with the bots' colors swapped, unless
+// both colors play the same bot: then each game has an opening of its own, as a deterministic bot
+// would only play the same game twice. The games are written as PGN, and a sample of their positions as `FEN | eval | result` lines: the static
+// evaluation in centipawns and the game's result (1.0, 0.5 or 0.0), both from white's side. The
+// first positions of the random opening and positions in check are never sampled.
+
+const USAGE: &str = "Usage: jchess selfplay [--white BOT] [--black BOT] [--games N] [--threads N] [--opening-plies N] [--max-plies N] [--sample RATE] [--seed N] [--pgn FILE] [--positions FILE]\nEach opening is played with colors swapped when --black differs from --white (which it defaults to), and once otherwise.";
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let options = ["white", "black", "games", "threads", "opening-plies", "max-plies", "sample", "seed", "pgn", "positions"];
+    let args = Args::parse(args, &options, &[], &format!("{}\n{}", USAGE, SPEC_HELP))?;
+    if args.positional.len() > 0 {
+        return Err(format!("{}\n{}", USAGE, SPEC_HELP));
+    }
+    let first = args.get("white").unwrap_or(String::from("search:depth=3"));
+    let second = args.get("black").unwrap_or(first.clone());
+    let games = args.get_or("games", 100usize)?;
+    let threads = args.get_or("threads", thread::available_parallelism().map(|n| n.get()).unwrap_or(1))?.max(1);
+    let opening_plies = args.get_or("opening-plies", 8usize)?;
+    let max_plies = args.get_or("max-plies", 400usize)?;
+    let sample = args.get_or("sample", 0.1f64)?;
+    let seed = args.get_or("seed", rand::rng().random::<u64>())?;
+    let pgn_path = args.get("pgn").map(PathBuf::from).unwrap_or(data_file("selfplay.pgn"));
+    let positions_path = args.get("positions").map(PathBuf::from).unwrap_or(data_file("selfplay.positions"));
+    // Checked up front so that a typo does not only show up in every thread.
+    from_spec(&first)?;
+    from_spec(&second)?;
+
+    let create = |path: &Path| match File::create(path) {
+        Ok(f) => Ok(BufWriter::new(f)),
+        Err(e) => Err(format!("Could not create {}: {}", path.display(), e)),
+    };
+    let mut pgn_out = create(&pgn_path)?;
+    let mut positions_out = create(&positions_path)?;
+    let weights = Weights::load_or_default(&data_file(WEIGHTS_FILE))?;
+    let per_opening = if first == second { 1 } else { 2 };
+
+    println!("{} games of {} vs {} on {} threads, seed {}", games, first, second, threads, seed);
+    let start = Instant::now();
+    let next = Arc::new(AtomicUsize::new(0));
+    let (send, receive) = mpsc::channel();
+    for _ in 0..threads {
+        let (next, send) = (next.clone(), send.clone());
+        let (first, second) = (first.clone(), second.clone());
+        thread::spawn(move || {
+            // Each thread has bots of its own, so nothing they keep between moves is shared.
+            let mut a = from_spec(&first).unwrap();
+            let mut b = from_spec(&second).unwrap();
+            loop {
+                let job = next.fetch_add(1, Ordering::Relaxed);
+                if per_opening * job >= games {
+                    return;
+                }
+                let opening = random_opening(&mut StdRng::seed_from_u64(seed ^ job as u64), opening_plies);
+                for round in per_opening * job..per_opening * (job + 1) {
+                    if round >= games {
+                        break;
+                    }
+                    let first_white = round % 2 == 0 || per_opening == 1;
+                    let record = if first_white {
+                        play_game(a.as_mut(), b.as_mut(), &opening, max_plies)
+                    } else {
+                        play_game(b.as_mut(), a.as_mut(), &opening, max_plies)
+                    };
+                    if send.send((round, first_white, record)).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+    }
+    drop(send);
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut scores = [0.0; 3]; // first bot's wins, draws, losses
+    let mut sampled = 0;
+    for (done, (round, first_white, record)) in receive.iter().enumerate() {
+        let write = pgn_out.write_all(record.to_pgn("jchess self-play", round + 1).to_string().as_bytes());
+        if let Err(e) = write {
+            return Err(format!("Could not write {}: {}", pgn_path.display(), e));
+        }
+
+        let result = record.white_score();
+        for (i, (game, _)) in record.positions().iter().enumerate() {
+            if i < opening_plies || game.is_check() || rng.random::<f64>() >= sample {
+                continue;
+            }
+            let eval = evaluate(game, &weights) * if game.current_color() == Color::White { 1 } else { -1 };
+            if let Err(e) = writeln!(positions_out, "{} | {} | {:.1}", to_fen(game), eval, result) {
+                return Err(format!("Could not write {}: {}", positions_path.display(), e));
+            }
+            sampled += 1;
+        }
+
+        let first_score = if first_white { result } else { 1.0 - result };
+        scores[if first_score == 1.0 { 0 } else if first_score == 0.5 { 1 } else { 2 }] += 1.0;
+        println!("Game {:>4}: {} {} - {} ({}, {} plies)   [{}/{}]", round + 1, record.result, record.white, record.black,
+            record.termination.to_string(), record.moves.len(), done + 1, games);
+    }
+    let _ = pgn_out.flush();
+    let _ = positions_out.flush();
+
+    println!("{} vs {}: +{} ={} -{}", first, second, scores[0], scores[1], scores[2]);
+    println!("Wrote {} games to {} and {} positions to {} in {:.1}s", games, pgn_path.display(), sampled,
+        positions_path.display(), start.elapsed().as_secs_f64());
+    return Ok(());
+}
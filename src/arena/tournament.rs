@@ -0,0 +1,361 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use crate::bots::{from_spec, SPEC_HELP};
+use crate::chess::fen::parse_fen;
+use crate::chess::game::Game;
+use crate::chess::pgn::{parse_pgn, play_san};
+use crate::chess::player::Player;
+use crate::input::args::Args;
+use crate::storage::data_file;
+
+use super::elo::{Score, Sprt};
+use super::*;
+
+// Matches and tournaments between bots, to measure a change to one of them:
+//   jchess match [--gauntlet] [--games N] [--threads N] [--tc TC | --movetime MS] [--openings FILE]
+//                [--opening-plies N] [--max-plies N] [--resign MOVES/SCORE] [--draw MOVE/MOVES/SCORE]
+//                [--sprt ELO0,ELO1[,ALPHA,BETA]] [--seed N] [--pgn FILE] BOT BOT...
+// Every bot plays every other (a round robin), or with --gauntlet the first bot plays each of the
+// others. Bots are given as for selfplay, and external engines as uci:PATH. Each pairing plays N
+// games in pairs on the same opening with colors swapped. Openings come from a file in turn, EPD
+// or FEN lines or the games of a PGN file, or are random moves otherwise.
+//
+// The time control is TC seconds, as 40/60+0.5 (moves/base+increment), 10+0.1 or 60, or a fixed
+// time per move; without either the bots search as their specs say. A game can be adjudicated a
+// loss once both players' scores agree on it for some moves, and a draw once both scores stay
+// near zero. With two bots, --sprt stops the match as soon as the result is clear either way.
+//
+// At the end come the crosstable with each bot's Elo against the field, and all games are written
+// as PGN.
+
+const USAGE: &str = "Usage: jchess match [--gauntlet] [--games N] [--threads N] [--tc TC | --movetime MS] [--openings FILE] [--opening-plies N] [--max-plies N] [--resign MOVES/SCORE] [--draw MOVE/MOVES/SCORE] [--sprt ELO0,ELO1[,ALPHA,BETA]] [--seed N] [--pgn FILE] BOT BOT...";
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let options = ["games", "threads", "tc", "movetime", "openings", "opening-plies", "max-plies", "resign", "draw", "sprt",
+        "seed", "pgn"];
+    let args = Args::parse(args, &options, &["gauntlet"], &format!("{}\n{}", USAGE, SPEC_HELP))?;
+    let specs = args.positional.clone();
+    if specs.len() < 2 {
+        return Err(format!("{}\n{}", USAGE, SPEC_HELP));
+    }
+    let gauntlet = args.get("gauntlet").is_some();
+    let games = args.get_or("games", 20usize)?.max(1);
+    let threads = args.get_or("threads", thread::available_parallelism().map(|n| n.get()).unwrap_or(1))?.max(1);
+    let opening_plies = match args.get("opening-plies") {
+        Some(_) => Some(args.get_or("opening-plies", 0usize)?),
+        None => None,
+    };
+    let seed = args.get_or("seed", rand::rng().random::<u64>())?;
+    let pgn_path = args.get("pgn").map(PathBuf::from).unwrap_or(data_file("tournament.pgn"));
+
+    let mut rules = Rules::new(args.get_or("max-plies", 400usize)?);
+    rules.time = match (args.get("tc"), args.get("movetime")) {
+        (Some(_), Some(_)) => return Err(String::from("Give either --tc or --movetime")),
+        (Some(tc), None) => TimeControl::parse(&tc)?,
+        (None, Some(_)) => TimeControl::PerMove(Duration::from_millis(args.get_or("movetime", 0u64)?.max(1))),
+        (None, None) => TimeControl::Unlimited,
+    };
+    if let Some(text) = args.get("resign") {
+        let n = numbers(&text, 2, "--resign MOVES/SCORE")?;
+        rules.resign = Some(ResignAdjudication{moves: n[0] as usize, score: n[1] as i32});
+    }
+    if let Some(text) = args.get("draw") {
+        let n = numbers(&text, 3, "--draw MOVE/MOVES/SCORE")?;
+        rules.draw = Some(DrawAdjudication{after: n[0] as usize, moves: n[1] as usize, score: n[2] as i32});
+    }
+    let sprt = match args.get("sprt") {
+        Some(text) if specs.len() == 2 => Some(Sprt::parse(&text)?),
+        Some(_) => return Err(String::from("--sprt needs exactly two bots")),
+        None => None,
+    };
+    let openings = match args.get("openings") {
+        Some(path) => load_openings(&path, opening_plies)?,
+        None => Vec::new(),
+    };
+    // Checked up front so that a typo does not only show up in every thread.
+    for spec in &specs {
+        from_spec(spec)?;
+    }
+    let names = names(&specs);
+    let mut pgn_out = match File::create(&pgn_path) {
+        Ok(f) => BufWriter::new(f),
+        Err(e) => return Err(format!("Could not create {}: {}", pgn_path.display(), e)),
+    };
+
+    let mut pairings = Vec::new();
+    for a in 0..specs.len() {
+        for b in a + 1..specs.len() {
+            if !gauntlet || a == 0 {
+                pairings.push((a, b));
+            }
+        }
+    }
+    // One pair of games of every pairing at a time, so that a match cut short by the SPRT (or by
+    // hand) has every pairing played about as often.
+    let mut jobs = Vec::new();
+    for pair in 0..games.div_ceil(2) {
+        for p in 0..pairings.len() {
+            jobs.push((p, pair));
+        }
+    }
+    let total = pairings.len() * games;
+
+    println!("{} games: {} of {} pairings, {} on {} threads, seed {}", total, games, pairings.len(),
+        rules.time.to_string(), threads, seed);
+    if let Some(s) = sprt {
+        println!("SPRT {}", s.to_string());
+    }
+    let start = Instant::now();
+    let next = Arc::new(AtomicUsize::new(0));
+    let stop = Arc::new(AtomicBool::new(false));
+    let (send, receive) = mpsc::channel();
+    for _ in 0..threads {
+        let (next, stop, send) = (next.clone(), stop.clone(), send.clone());
+        let (specs, names, jobs, pairings, openings) = (specs.clone(), names.clone(), jobs.clone(), pairings.clone(), openings.clone());
+        let opening_plies = opening_plies.unwrap_or(8);
+        thread::spawn(move || {
+            // Each thread starts its own bots as it needs them, external engines included.
+            let mut bots: Vec<Option<Box<dyn Player>>> = specs.iter().map(|_| None).collect();
+            loop {
+                let job = next.fetch_add(1, Ordering::Relaxed);
+                if job >= jobs.len() {
+                    return;
+                }
+                let (p, pair) = jobs[job];
+                let (a, b) = pairings[p];
+                let opening = if openings.len() > 0 {
+                    openings[pair % openings.len()].clone()
+                } else {
+                    Opening{fen: None, moves: random_opening(&mut StdRng::seed_from_u64(seed ^ pair as u64), opening_plies)}
+                };
+                for g in [2 * pair, 2 * pair + 1] {
+                    if g >= games || stop.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    // A bot that fails to start (an engine that has gone missing, say) ends the run.
+                    let (mut first, mut second) = match (start_bot(&mut bots, &specs, a), start_bot(&mut bots, &specs, b)) {
+                        (Ok(first), Ok(second)) => (first, second),
+                        (Err(e), _) | (_, Err(e)) => {
+                            stop.store(true, Ordering::Relaxed);
+                            let _ = send.send(Err(e));
+                            return;
+                        },
+                    };
+                    let first_white = g % 2 == 0;
+                    let mut record = if first_white {
+                        play_match(first.as_mut(), second.as_mut(), &opening, &rules)
+                    } else {
+                        play_match(second.as_mut(), first.as_mut(), &opening, &rules)
+                    };
+                    bots[a] = Some(first);
+                    bots[b] = Some(second);
+                    (record.white, record.black) = if first_white {
+                        (names[a].clone(), names[b].clone())
+                    } else {
+                        (names[b].clone(), names[a].clone())
+                    };
+                    // The pairing's games are numbered in order, two to a pair.
+                    let round = g + 1;
+                    if send.send(Ok((p, round, first_white, record))).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+    }
+    drop(send);
+
+    // table[a][b] is a's score against b.
+    let mut table = vec![vec![Score::new(); specs.len()]; specs.len()];
+    let mut played = 0;
+    let mut failed = None;
+    for message in receive.iter() {
+        let (p, round, first_white, record) = match message {
+            Ok(game) => game,
+            Err(e) => {
+                // The other threads finish the games they are playing, which are still written.
+                failed = failed.or(Some(e));
+                continue;
+            },
+        };
+        let mut pgn = record.to_pgn("jchess tournament", round);
+        if let TimeControl::Clock{..} = rules.time {
+            pgn.tags.push((String::from("TimeControl"), rules.time.to_string()));
+        }
+        if let Err(e) = pgn_out.write_all(pgn.to_string().as_bytes()) {
+            return Err(format!("Could not write {}: {}", pgn_path.display(), e));
+        }
+        let (a, b) = pairings[p];
+        let first_score = if first_white { record.white_score() } else { 1.0 - record.white_score() };
+        table[a][b].add(first_score);
+        table[b][a].add(1.0 - first_score);
+        played += 1;
+        println!("Round {:>3}: {} {} - {} ({}, {} plies)   [{}/{}]", round, record.result, record.white, record.black,
+            record.termination.to_string(), record.moves.len(), played, total);
+
+        if let Some(s) = sprt {
+            if !stop.load(Ordering::Relaxed) {
+                if let Some(accepted) = s.verdict(&table[0][1]) {
+                    stop.store(true, Ordering::Relaxed);
+                    println!("SPRT: H{} accepted, LLR {:.2} after {} games; finishing the games in progress",
+                        if accepted { 1 } else { 0 }, s.llr(&table[0][1]), played);
+                }
+            }
+        }
+    }
+    let _ = pgn_out.flush();
+    if let Some(e) = failed {
+        return Err(format!("{}; stopped after {} games, written to {}", e, played, pgn_path.display()));
+    }
+
+    println!();
+    print_crosstable(&names, &table, gauntlet);
+    if specs.len() == 2 {
+        let score = &table[0][1];
+        println!();
+        println!("{} vs {}: {}, score {:.1}/{}", names[0], names[1], score.to_string(), score.points(), score.games());
+        println!("Elo difference: {} (95%)", score.elo_string());
+        if let Some(s) = sprt {
+            let (lower, upper) = s.bounds();
+            let verdict = match s.verdict(score) {
+                Some(true) => "H1 accepted",
+                Some(false) => "H0 accepted",
+                None => "undecided",
+            };
+            println!("SPRT: LLR {:.2} ({:.2}, {:.2}), {}", s.llr(score), lower, upper, verdict);
+        }
+    }
+    println!("Wrote {} games to {} in {:.1}s", played, pgn_path.display(), start.elapsed().as_secs_f64());
+    return Ok(());
+}
+
+// The thread's bot for the spec, started the first time it is needed.
+fn start_bot(bots: &mut [Option<Box<dyn Player>>], specs: &[String], i: usize) -> Result<Box<dyn Player>, String> {
+    return match bots[i].take() {
+        Some(bot) => Ok(bot),
+        None => from_spec(&specs[i]).map_err(|e| format!("Could not start {}: {}", specs[i], e)),
+    };
+}
+
+// The bots' specs as names for the table and the PGN, numbered where a spec comes more than once.
+fn names(specs: &[String]) -> Vec<String> {
+    let mut names = Vec::new();
+    for (i, spec) in specs.iter().enumerate() {
+        let copies = specs[..i].iter().filter(|s| *s == spec).count();
+        names.push(if copies > 0 { format!("{} #{}", spec, copies + 1) } else { spec.clone() });
+    }
+    return names;
+}
+
+// "4/800" and the like, with exactly `count` numbers.
+fn numbers(text: &str, count: usize, usage: &str) -> Result<Vec<i64>, String> {
+    let values: Vec<Option<i64>> = text.split('/').map(|v| v.trim().parse::<i64>().ok().filter(|n| *n >= 0)).collect();
+    if values.len() != count || values.iter().any(|v| v.is_none()) {
+        return Err(format!("Expected {}, not {}", usage, text));
+    }
+    return Ok(values.into_iter().flatten().collect());
+}
+
+// Openings from a PGN file, a game each, up to `plies` moves when given; or from EPD or FEN lines,
+// a position each. Openings that already end the game are skipped.
+fn load_openings(path: &str, plies: Option<usize>) -> Result<Vec<Opening>, String> {
+    let text = match fs::read_to_string(path) {
+        Ok(t) => t,
+        Err(e) => return Err(format!("Could not read {}: {}", path, e)),
+    };
+    let mut openings = Vec::new();
+    if path.to_lowercase().ends_with(".pgn") {
+        for (n, pgn) in parse_pgn(&text).iter().enumerate() {
+            let fen = pgn.tag("FEN");
+            let mut game = match &fen {
+                Some(f) => parse_fen(f).map_err(|e| format!("{} game {}: {}", path, n + 1, e))?,
+                None => {
+                    let mut g = Game::new();
+                    g.default_board();
+                    g
+                },
+            };
+            let mut moves = Vec::new();
+            for san in pgn.moves.iter().take(plies.unwrap_or(usize::MAX)) {
+                match play_san(&mut game, san) {
+                    Ok(m) => moves.push(m),
+                    Err(e) => return Err(format!("{} game {}: {}", path, n + 1, e)),
+                }
+            }
+            if game.any_valid_moves() {
+                openings.push(Opening{fen: fen, moves: moves});
+            }
+        }
+    } else {
+        for (n, line) in text.lines().enumerate() {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            if tokens.len() == 0 || tokens[0].starts_with('#') {
+                continue;
+            }
+            // The four EPD fields, and the move counters of a FEN; EPD operations are skipped.
+            let mut fen_len = 4.min(tokens.len());
+            while fen_len < 6 && fen_len < tokens.len() && tokens[fen_len].parse::<u32>().is_ok() {
+                fen_len += 1;
+            }
+            let mut fen = tokens[..fen_len].join(" ");
+            if fen_len == 4 {
+                fen.push_str(" 0 1");
+            }
+            match parse_fen(&fen) {
+                Ok(g) if g.any_valid_moves() => openings.push(Opening{fen: Some(fen), moves: Vec::new()}),
+                Ok(_) => {},
+                Err(e) => return Err(format!("{} line {}: {}", path, n + 1, e)),
+            }
+        }
+    }
+    if openings.len() == 0 {
+        return Err(format!("No openings in {}", path));
+    }
+    return Ok(openings);
+}
+
+// The bots by score, each with its games, points and Elo against the field, and its points against
+// each of the others in the order of the table.
+fn print_crosstable(names: &[String], table: &[Vec<Score>], gauntlet: bool) {
+    let totals: Vec<Score> = table.iter().map(|row| {
+        let mut total = Score::new();
+        for s in row {
+            total.wins += s.wins;
+            total.draws += s.draws;
+            total.losses += s.losses;
+        }
+        total
+    }).collect();
+    let mut order: Vec<usize> = (0..names.len()).collect();
+    order.sort_by(|a, b| totals[*b].points().total_cmp(&totals[*a].points()));
+
+    let width = names.iter().map(|n| n.len()).max().unwrap_or(0).max(4);
+    let mut header = format!("{:>4}  {:<width$}  {:>5}  {:>6}  {:<20}", "Rank", "Name", "Games", "Score", "Elo", width = width);
+    for i in 1..=names.len() {
+        header.push_str(&format!("  {:>5}", i));
+    }
+    println!("{}", header);
+    for (rank, i) in order.iter().enumerate() {
+        let mut line = format!("{:>4}  {:<width$}  {:>5}  {:>6.1}  {:<20}", rank + 1, names[*i], totals[*i].games(),
+            totals[*i].points(), totals[*i].elo_string(), width = width);
+        for j in &order {
+            let cell = if i == j || (gauntlet && *i != 0 && *j != 0) {
+                String::from("-")
+            } else {
+                format!("{:.1}", table[*i][*j].points())
+            };
+            line.push_str(&format!("  {:>5}", cell));
+        }
+        println!("{}", line);
+    }
+}
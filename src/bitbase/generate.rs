@@ -0,0 +1,283 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::chess::color::Color;
+use crate::chess::game::Game;
+use crate::chess::piece::{Piece, PieceKind};
+use crate::input::args::Args;
+use crate::storage::data_file;
+
+use super::*;
+
+// Generates bitbases by retrograde analysis:
+//   jchess bitbase [--out DIR] [NAME...]
+// The legal moves of every position come from Game, so the longest mates found are compared with
+// the known values as a check on the move generator.
+//
+// Every position is first set up on a board to find its legal moves, which are kept as one byte
+// each: the moving piece's slot in the signature's square list and its destination. Then the
+// results are built up from the mates, one move at a time: after sweep k every loss in 2k plies
+// and every win in 2k+1 plies is known. Whatever is left at the end is a draw.
+
+const USAGE: &str = "Usage: jchess bitbase [--out DIR] [KQK|KRK|KPK|KBNK...]";
+
+// A pawn promoting to a rook is stored in this slot, which KPK has no piece for.
+const ROOK_PROMOTION: u8 = 3;
+
+// Longest mates with the strong side to move, in moves.
+fn known_longest_mate(sig: Signature) -> Option<u32> {
+    return match sig {
+        Signature::Kqk => Some(10),
+        Signature::Krk => Some(16),
+        Signature::Kbnk => Some(33),
+        Signature::Kpk => None,
+    };
+}
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &["out"], &[], USAGE)?;
+    let out = match args.get("out") {
+        Some(p) => PathBuf::from(p),
+        None => data_file(BITBASE_DIR),
+    };
+    let mut wanted = Vec::new();
+    for name in &args.positional {
+        match Signature::from_name(name) {
+            Some(s) => wanted.push(s),
+            None => return Err(format!("Unknown signature: {}\n{}", name, USAGE)),
+        }
+    }
+    if wanted.len() == 0 {
+        wanted = Signature::ALL.to_vec();
+    }
+    if let Err(e) = fs::create_dir_all(&out) {
+        return Err(format!("Could not create {}: {}", out.display(), e));
+    }
+
+    // KPK needs the tables its promotions lead to, which are generated too unless they are there.
+    let mut bitbases = Bitbases::new();
+    if wanted.contains(&Signature::Kpk) {
+        for dep in [Signature::Kqk, Signature::Krk] {
+            if wanted.contains(&dep) {
+                continue;
+            }
+            match Table::load(&file_path(&out, dep)) {
+                Ok((_, t)) => bitbases.insert(dep, t),
+                Err(_) => wanted.push(dep),
+            }
+        }
+    }
+
+    let mut mismatch = Vec::new();
+    for sig in Signature::ALL {
+        if !wanted.contains(&sig) {
+            continue;
+        }
+        let table = generate(sig, &bitbases);
+        let path = file_path(&out, sig);
+        if let Err(e) = fs::write(&path, table.to_bytes(sig)) {
+            return Err(format!("Could not write {}: {}", path.display(), e));
+        }
+        if !report(sig, &table) {
+            mismatch.push(sig.name());
+        }
+        println!("  wrote {}", path.display());
+        bitbases.insert(sig, table);
+    }
+    if mismatch.len() > 0 {
+        return Err(format!("Longest mate differs from the known value for {}", mismatch.join(", ")));
+    }
+    return Ok(());
+}
+
+fn file_path(dir: &Path, sig: Signature) -> PathBuf {
+    return dir.join(format!("{}.{}", sig.name(), EXTENSION));
+}
+
+// Prints the statistics of a table and returns whether its longest mate is the known one.
+fn report(sig: Signature, table: &Table) -> bool {
+    let count = |values: &Vec<u8>, f: &dyn Fn(u8) -> bool| values.iter().filter(|v| f(**v)).count();
+    let legal = count(&table.white, &|v| v != ILLEGAL);
+    let wins = count(&table.white, &|v| v != 0 && v != ILLEGAL);
+    let longest = table.white.iter().filter(|v| **v != ILLEGAL).map(|v| *v as u32).max().unwrap_or(0);
+    let moves = longest / 2;
+    println!("{}: {} positions with white to move, {} legal, {} won, {} drawn", sig.name(), sig.size(), legal, wins, legal - wins);
+    println!("  black to move: {} legal, {} lost", count(&table.black, &|v| v != ILLEGAL),
+        count(&table.black, &|v| v != 0 && v != ILLEGAL));
+    return match known_longest_mate(sig) {
+        Some(known) if known == moves => {
+            println!("  longest mate: {} moves, matches the known value", moves);
+            true
+        },
+        Some(known) => {
+            println!("  longest mate: {} moves, MISMATCH: expected {}", moves, known);
+            false
+        },
+        None => {
+            println!("  longest mate: {} moves", moves);
+            true
+        },
+    };
+}
+
+// Legal moves of every position of one side, as (slot << 6 | destination) bytes.
+struct Moves {
+    offsets: Vec<u32>,
+    codes: Vec<u8>,
+}
+
+impl Moves {
+    fn of(&self, idx: usize) -> &[u8] {
+        return &self.codes[self.offsets[idx] as usize..self.offsets[idx + 1] as usize];
+    }
+}
+
+fn is_adjacent(a: usize, b: usize) -> bool {
+    return (a % 8).abs_diff(b % 8) <= 1 && (a / 8).abs_diff(b / 8) <= 1;
+}
+
+fn set_up(game: &mut Game, sig: Signature, sq: &[usize; 4], to_move: Color) {
+    let mut pieces = vec![
+        (sq[0] % 8, sq[0] / 8, Piece::make(PieceKind::King, Color::White, true, 0)),
+        (sq[1] % 8, sq[1] / 8, Piece::make(PieceKind::King, Color::Black, true, 0)),
+    ];
+    for (i, kind) in sig.pieces().iter().enumerate() {
+        let s = sq[i + 2];
+        // A pawn that has not moved may still make a double step.
+        let moved = !(*kind == PieceKind::Pawn && s / 8 == 1);
+        pieces.push((s % 8, s / 8, Piece::make(*kind, Color::White, moved, 0)));
+    }
+    game.set_position(&pieces, to_move);
+}
+
+// Sets up every position of one side and records its moves. Illegal positions and mates are marked
+// in `values`. A black capture draws every signature here, so those positions get no moves at all
+// and stay drawn.
+fn list_moves(sig: Signature, to_move: Color, values: &mut [u8]) -> Moves {
+    let n = sig.pieces().len() + 2;
+    let mut game = Game::new();
+    let mut moves = Moves{offsets: Vec::with_capacity(sig.size() + 1), codes: Vec::new()};
+    for idx in 0..sig.size() {
+        moves.offsets.push(moves.codes.len() as u32);
+        let sq = sig.squares(idx);
+        let overlap = (0..n).any(|i| (0..i).any(|j| sq[i] == sq[j]));
+        if overlap || is_adjacent(sq[0], sq[1]) {
+            values[idx] = ILLEGAL;
+            continue;
+        }
+        set_up(&mut game, sig, &sq, to_move);
+        if to_move == Color::White && game.is_check_color(Color::Black) {
+            values[idx] = ILLEGAL;
+            continue;
+        }
+
+        let start = moves.codes.len();
+        let mut captures = false;
+        for m in game.list_valid_moves_with_underpromotions() {
+            let origin = m.origin.1.index().unwrap() * 8 + m.origin.0.index().unwrap();
+            let dest = m.dest.1.index().unwrap() * 8 + m.dest.0.index().unwrap();
+            if to_move == Color::Black && sq[2..n].contains(&dest) {
+                moves.codes.truncate(start);
+                captures = true;
+                break;
+            }
+            let slot = match m.promotion {
+                PieceKind::None | PieceKind::Queen => sq[..n].iter().position(|s| *s == origin).unwrap() as u8,
+                PieceKind::Rook => ROOK_PROMOTION,
+                _ => continue, // a lone minor piece cannot win
+            };
+            moves.codes.push(slot << 6 | dest as u8);
+        }
+        if to_move == Color::Black && moves.codes.len() == start && !captures && game.is_check() {
+            values[idx] = 1;
+        }
+    }
+    moves.offsets.push(moves.codes.len() as u32);
+    return moves;
+}
+
+// The value of the position after a move from `sq`, from the table of the other side to move.
+fn child_value(sig: Signature, sq: &[usize; 4], code: u8, other: &[u8], bitbases: &Bitbases) -> u8 {
+    let slot = (code >> 6) as usize;
+    let dest = (code & 63) as usize;
+    let mut next = *sq;
+    if sig == Signature::Kpk && (slot == ROOK_PROMOTION as usize || (slot == 2 && dest / 8 == 7)) {
+        let promoted = if slot == 2 { Signature::Kqk } else { Signature::Krk };
+        return match bitbases.get(promoted) {
+            Some(t) => t.black[promoted.index(&[sq[0], sq[1], dest, 0])],
+            None => 0,
+        };
+    }
+    next[slot] = dest;
+    return other[sig.index(&next)];
+}
+
+fn generate(sig: Signature, bitbases: &Bitbases) -> Table {
+    let size = sig.size();
+    let mut white = vec![0u8; size];
+    let mut black = vec![0u8; size];
+    let white_moves = list_moves(sig, Color::White, &mut white);
+    let black_moves = list_moves(sig, Color::Black, &mut black);
+
+    // Promotions can reach mates further away than anything in this table.
+    let mut external = 0;
+    if sig == Signature::Kpk {
+        for dep in [Signature::Kqk, Signature::Krk] {
+            if let Some(t) = bitbases.get(dep) {
+                external = external.max(t.black.iter().filter(|v| **v != ILLEGAL).map(|v| *v as u32).max().unwrap_or(0));
+            }
+        }
+    }
+
+    let mut k: u32 = 0;
+    loop {
+        let mut changed = false;
+        // Black loses if every move reaches a win for white known by now.
+        if k > 0 {
+            for idx in 0..size {
+                let codes = black_moves.of(idx);
+                if black[idx] != 0 || codes.len() == 0 {
+                    continue;
+                }
+                let sq = sig.squares(idx);
+                let mut worst = 0;
+                for code in codes {
+                    let v = child_value(sig, &sq, *code, &white, bitbases);
+                    if v == 0 || v == ILLEGAL || v as u32 > 2 * k {
+                        worst = 0;
+                        break;
+                    }
+                    worst = worst.max(v);
+                }
+                if worst > 0 {
+                    black[idx] = worst + 1;
+                    changed = true;
+                }
+            }
+        }
+        // White wins if any move reaches a loss for black.
+        for idx in 0..size {
+            let codes = white_moves.of(idx);
+            if white[idx] != 0 {
+                continue;
+            }
+            let sq = sig.squares(idx);
+            let mut best = 0;
+            for code in codes {
+                let v = child_value(sig, &sq, *code, &black, bitbases);
+                if v != 0 && v != ILLEGAL && v as u32 <= 2 * k + 1 && (best == 0 || v < best) {
+                    best = v;
+                }
+            }
+            if best > 0 {
+                white[idx] = best + 1;
+                changed = true;
+            }
+        }
+        if !changed && 2 * k + 1 >= external {
+            break;
+        }
+        k += 1;
+    }
+    return Table{white: white, black: black};
+}
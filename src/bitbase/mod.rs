@@ -0,0 +1,313 @@
+pub mod generate;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::chess::color::Color;
+use crate::chess::game::Game;
+use crate::chess::piece::PieceKind;
+use crate::chess::r#move::Move;
+
+// Bitbases for a few small endgames, computed by `jchess bitbase` with retrograde analysis. Unlike
+// the Syzygy tables these hold the distance to mate, so a bot can play the fastest mate outright.
+//
+// A bitbase file is the magic "JBB1", the signature name (one length byte and the name), the
+// number of positions as a little-endian u32, and then one byte per position with white to move
+// followed by one byte per position with black to move. The strong side is always white in the
+// file; a byte is 0 for a draw, ILLEGAL for a position that cannot occur, or otherwise the
+// distance to mate in plies plus one.
+
+pub const BITBASE_DIR: &str = "bitbases";
+pub const EXTENSION: &str = "jbb";
+const MAGIC: &[u8; 4] = b"JBB1";
+pub const ILLEGAL: u8 = 255;
+
+// Squares are numbered rank * 8 + file. The strong king of a pawnless position is moved into this
+// triangle by mirroring the board, which leaves 10 of its 64 squares.
+const TRIANGLE: [usize; 10] = [0, 1, 2, 3, 9, 10, 11, 18, 19, 27];
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Signature {
+    Kqk,
+    Krk,
+    Kpk,
+    Kbnk,
+}
+
+impl Signature {
+    // In the order they have to be generated: KPK promotes into KQK and KRK.
+    pub const ALL: [Signature; 4] = [Signature::Kqk, Signature::Krk, Signature::Kpk, Signature::Kbnk];
+
+    pub fn name(self) -> &'static str {
+        return match self {
+            Signature::Kqk => "KQK",
+            Signature::Krk => "KRK",
+            Signature::Kpk => "KPK",
+            Signature::Kbnk => "KBNK",
+        };
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        for s in Signature::ALL {
+            if s.name().eq_ignore_ascii_case(name) {
+                return Some(s);
+            }
+        }
+        return None;
+    }
+
+    // The strong side's pieces besides the king.
+    pub fn pieces(self) -> &'static [PieceKind] {
+        return match self {
+            Signature::Kqk => &[PieceKind::Queen],
+            Signature::Krk => &[PieceKind::Rook],
+            Signature::Kpk => &[PieceKind::Pawn],
+            Signature::Kbnk => &[PieceKind::Bishop, PieceKind::Knight],
+        };
+    }
+
+    fn piece_count(self) -> usize {
+        return self.pieces().len() + 2;
+    }
+
+    pub fn size(self) -> usize {
+        if self == Signature::Kpk {
+            return 24 * 64 * 64;
+        }
+        return TRIANGLE.len() * 64usize.pow(self.piece_count() as u32 - 1);
+    }
+
+    // The index of a position given as [strong king, weak king, pieces in signature order], with
+    // the strong side playing up the board.
+    pub fn index(self, squares: &[usize; 4]) -> usize {
+        let n = self.piece_count();
+        let mut sq = *squares;
+        if self == Signature::Kpk {
+            // Only the files can be mirrored with a pawn on the board.
+            if sq[2] % 8 > 3 {
+                for s in &mut sq[..n] {
+                    *s ^= 7;
+                }
+            }
+            let pawn = (sq[2] / 8 - 1) * 4 + sq[2] % 8;
+            return (pawn * 64 + sq[0]) * 64 + sq[1];
+        }
+
+        if sq[0] % 8 > 3 {
+            for s in &mut sq[..n] {
+                *s ^= 7;
+            }
+        }
+        if sq[0] / 8 > 3 {
+            for s in &mut sq[..n] {
+                *s ^= 56;
+            }
+        }
+        if sq[0] / 8 > sq[0] % 8 {
+            for s in &mut sq[..n] {
+                *s = (*s % 8) * 8 + *s / 8;
+            }
+        }
+        let mut idx = TRIANGLE.iter().position(|t| *t == sq[0]).unwrap();
+        for s in &sq[1..n] {
+            idx = idx * 64 + s;
+        }
+        return idx;
+    }
+
+    // The inverse of index.
+    pub fn squares(self, mut idx: usize) -> [usize; 4] {
+        let n = self.piece_count();
+        let mut sq = [0; 4];
+        if self == Signature::Kpk {
+            sq[1] = idx % 64;
+            sq[0] = idx / 64 % 64;
+            let pawn = idx / 4096;
+            sq[2] = (pawn / 4 + 1) * 8 + pawn % 4;
+            return sq;
+        }
+        for i in (1..n).rev() {
+            sq[i] = idx % 64;
+            idx /= 64;
+        }
+        sq[0] = TRIANGLE[idx];
+        return sq;
+    }
+}
+
+// What the material on the board means for the bitbases.
+pub enum Material {
+    Draw, // no side can mate
+    Table(Signature, [usize; 4], Color), // the squares as in Signature::index, and the strong side
+    Unknown,
+}
+
+pub fn material(game: &Game) -> Material {
+    let mut kings = [0; 2];
+    let mut pieces: [Vec<(PieceKind, usize)>; 2] = [Vec::new(), Vec::new()];
+    for f in 0..8 {
+        for r in 0..8 {
+            let p = game.piece_at(f, r);
+            let side = if p.color == Color::White { 0 } else { 1 };
+            match p.kind {
+                PieceKind::None => {},
+                PieceKind::King => kings[side] = r * 8 + f,
+                kind => pieces[side].push((kind, r * 8 + f)),
+            }
+        }
+    }
+
+    let strong = if pieces[1].len() == 0 { 0 } else if pieces[0].len() == 0 { 1 } else { return Material::Unknown };
+    let own = &pieces[strong];
+    if own.len() == 0 || (own.len() == 1 && (own[0].0 == PieceKind::Bishop || own[0].0 == PieceKind::Knight)) {
+        return Material::Draw;
+    }
+    for sig in Signature::ALL {
+        let kinds = sig.pieces();
+        if kinds.len() != own.len() {
+            continue;
+        }
+        let mut squares = [kings[strong], kings[1 - strong], 0, 0];
+        let mut found = true;
+        for (i, kind) in kinds.iter().enumerate() {
+            match own.iter().find(|p| p.0 == *kind) {
+                Some(p) => squares[i + 2] = p.1,
+                None => found = false,
+            }
+        }
+        if !found {
+            continue;
+        }
+        // Tables are stored with the strong side as white.
+        if strong == 1 {
+            for s in &mut squares {
+                *s ^= 56;
+            }
+        }
+        return Material::Table(sig, squares, if strong == 0 { Color::White } else { Color::Black });
+    }
+    return Material::Unknown;
+}
+
+pub struct Table {
+    pub white: Vec<u8>, // strong side to move
+    pub black: Vec<u8>,
+}
+
+impl Table {
+    pub fn load(path: &Path) -> Result<(Signature, Self), String> {
+        let bytes = match fs::read(path) {
+            Ok(b) => b,
+            Err(e) => return Err(format!("Could not read {}: {}", path.display(), e)),
+        };
+        let bad = format!("{} is not a bitbase", path.display());
+        if bytes.len() < 5 || &bytes[0..4] != MAGIC {
+            return Err(bad);
+        }
+        let name_end = 5 + bytes[4] as usize;
+        let sig = match bytes.get(5..name_end).and_then(|n| std::str::from_utf8(n).ok()).and_then(Signature::from_name) {
+            Some(s) => s,
+            None => return Err(bad),
+        };
+        let count = match bytes.get(name_end..name_end + 4) {
+            Some(c) => u32::from_le_bytes([c[0], c[1], c[2], c[3]]) as usize,
+            None => return Err(bad),
+        };
+        let data = &bytes[name_end + 4..];
+        if count != sig.size() || data.len() != 2 * count {
+            return Err(bad);
+        }
+        return Ok((sig, Table{white: data[..count].to_vec(), black: data[count..].to_vec()}));
+    }
+
+    pub fn to_bytes(&self, sig: Signature) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(9 + sig.name().len() + 2 * self.white.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(sig.name().len() as u8);
+        bytes.extend_from_slice(sig.name().as_bytes());
+        bytes.extend_from_slice(&(self.white.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.white);
+        bytes.extend_from_slice(&self.black);
+        return bytes;
+    }
+}
+
+pub struct Bitbases {
+    tables: HashMap<Signature, Table>,
+}
+
+impl Bitbases {
+    pub fn new() -> Self {
+        return Bitbases{tables: HashMap::new()};
+    }
+
+    pub fn load_dir(dir: &Path) -> Result<Self, String> {
+        let listing = match fs::read_dir(dir) {
+            Ok(l) => l,
+            Err(e) => return Err(format!("Could not read {}: {}", dir.display(), e)),
+        };
+        let mut bb = Bitbases::new();
+        for file in listing.flatten() {
+            let path = file.path();
+            if path.extension().and_then(|e| e.to_str()) == Some(EXTENSION) {
+                let (sig, table) = Table::load(&path)?;
+                bb.tables.insert(sig, table);
+            }
+        }
+        if bb.tables.len() == 0 {
+            return Err(format!("No bitbases found in {}", dir.display()));
+        }
+        return Ok(bb);
+    }
+
+    pub fn insert(&mut self, sig: Signature, table: Table) {
+        self.tables.insert(sig, table);
+    }
+
+    pub fn get(&self, sig: Signature) -> Option<&Table> {
+        return self.tables.get(&sig);
+    }
+
+    // The result for the side to move (1 win, 0 draw, -1 loss) and the distance to mate in plies,
+    // or None if the position is not covered.
+    pub fn probe(&self, game: &Game) -> Option<(i32, u32)> {
+        if game.castle_rights().contains(&true) {
+            return None;
+        }
+        let (sig, squares, strong) = match material(game) {
+            Material::Draw => return Some((0, 0)),
+            Material::Table(sig, squares, strong) => (sig, squares, strong),
+            Material::Unknown => return None,
+        };
+        let table = self.tables.get(&sig)?;
+        let strong_to_move = game.current_color() == strong;
+        let values = if strong_to_move { &table.white } else { &table.black };
+        return match values[sig.index(&squares)] {
+            0 => Some((0, 0)),
+            ILLEGAL => None,
+            v => Some((if strong_to_move { 1 } else { -1 }, v as u32 - 1)),
+        };
+    }
+
+    // The quickest mate when winning, the longest defence when losing, or any drawing move. None
+    // if the position or one of the replies is not covered.
+    pub fn best_move(&self, game: &Game) -> Option<Move> {
+        self.probe(game)?;
+        let mut best: Option<(i32, Move)> = None;
+        for m in game.list_valid_moves_with_underpromotions() {
+            let mut next = game.clone();
+            next.do_move(m);
+            next.next_turn();
+            let (wdl, dtm) = self.probe(&next)?;
+            let rank = -wdl * 1000 + if wdl < 0 { -(dtm as i32) } else { dtm as i32 };
+            if best.is_none() || rank > best.unwrap().0 {
+                best = Some((rank, m));
+            }
+        }
+        return match best {
+            Some((_, m)) => Some(m),
+            None => None,
+        };
+    }
+}
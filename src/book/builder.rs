@@ -0,0 +1,129 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::chess::color::Color;
+use crate::chess::game::Game;
+use crate::chess::pgn::*;
+use crate::chess::zobrist;
+use crate::input::args::Args;
+use crate::storage::data_file;
+
+use super::BOOK_FILE;
+use super::polyglot::*;
+
+// Builds a Polyglot book from PGN files:
+//   jchess book [--out FILE] [--min-games N] [--min-score PCT] [--max-ply N] FILE...
+// Every game is replayed up to the maximum ply, counting how often each move was played from each
+// position and how it scored for the side that played it. Moves below the minimum number of games
+// or score are dropped; the rest are weighted 2 per win and 1 per draw.
+
+const USAGE: &str = "Usage: jchess book [--out FILE] [--min-games N] [--min-score PCT] [--max-ply N] FILE...";
+
+#[derive(Copy, Clone)]
+struct Stats {
+    games: u32,
+    wins: u32,
+    draws: u32,
+}
+
+impl Stats {
+    fn score(&self) -> f64 {
+        return (self.wins as f64 + self.draws as f64 / 2.0) * 100.0 / self.games as f64;
+    }
+
+    fn weight(&self) -> u32 {
+        return 2 * self.wins + self.draws;
+    }
+}
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &["out", "min-games", "min-score", "max-ply"], &[], USAGE)?;
+    let min_games = args.get_or("min-games", 1u32)?;
+    let min_score = args.get_or("min-score", 0.0f64)?;
+    let max_ply = args.get_or("max-ply", 30usize)?;
+    let out = match args.get("out") {
+        Some(p) => PathBuf::from(p),
+        None => data_file(BOOK_FILE),
+    };
+    if args.positional.len() == 0 {
+        return Err(String::from(USAGE));
+    }
+
+    let mut stats: HashMap<(u64, u16), Stats> = HashMap::new();
+    let mut read = 0;
+    let mut skipped = 0;
+    for path in &args.positional {
+        let text = match fs::read_to_string(path) {
+            Ok(t) => t,
+            Err(e) => return Err(format!("Could not read {}: {}", path, e)),
+        };
+        for pgn in parse_pgn(&text) {
+            if add_game(&pgn, max_ply, &mut stats) {
+                read += 1;
+            } else {
+                skipped += 1;
+            }
+        }
+    }
+
+    let mut entries: Vec<(u64, u16, u32)> = Vec::new();
+    for ((key, mv), s) in &stats {
+        if s.games >= min_games && s.score() >= min_score && s.weight() > 0 {
+            entries.push((*key, *mv, s.weight()));
+        }
+    }
+    // Weights have to fit in 16 bits, so a big collection is scaled down as a whole.
+    let max = entries.iter().map(|e| e.2).max().unwrap_or(0);
+    let scale = if max > u16::MAX as u32 { u16::MAX as f64 / max as f64 } else { 1.0 };
+    entries.sort_by(|a, b| a.0.cmp(&b.0).then(b.2.cmp(&a.2)));
+
+    let mut bytes: Vec<u8> = Vec::with_capacity(entries.len() * ENTRY_SIZE);
+    for (key, mv, weight) in &entries {
+        let weight = ((*weight as f64 * scale) as u16).max(1);
+        bytes.extend_from_slice(&Entry{key: *key, mv: *mv, weight, learn: 0}.to_bytes());
+    }
+    if let Err(e) = fs::write(&out, bytes) {
+        return Err(format!("Could not write {}: {}", out.display(), e));
+    }
+
+    println!("Read {} games ({} skipped)", read, skipped);
+    println!("Wrote {} entries for {} positions to {}", entries.len(),
+        entries.iter().map(|e| e.0).collect::<HashSet<u64>>().len(), out.display());
+    return Ok(());
+}
+
+// Replays one game into the statistics. Games that do not start from the initial position are
+// skipped; a game with an unreadable move counts up to that move.
+fn add_game(pgn: &PgnGame, max_ply: usize, stats: &mut HashMap<(u64, u16), Stats>) -> bool {
+    if pgn.tag("FEN").is_some() || pgn.moves.len() == 0 {
+        return false;
+    }
+    // Results as (white, black) points out of 2
+    let points = match pgn.result.as_str() {
+        "1-0" => (2, 0),
+        "0-1" => (0, 2),
+        "1/2-1/2" => (1, 1),
+        _ => return false,
+    };
+
+    let mut game = Game::new();
+    game.default_board();
+    for san in pgn.moves.iter().take(max_ply) {
+        let color = game.current_color();
+        let key = zobrist::hash(&game);
+        let m = match play_san(&mut game, san) {
+            Ok(m) => m,
+            Err(_) => break,
+        };
+        let entry = stats.entry((key, encode_move(&m, color))).or_insert(Stats{games: 0, wins: 0, draws: 0});
+        let p = if color == Color::White { points.0 } else { points.1 };
+        entry.games += 1;
+        match p {
+            2 => entry.wins += 1,
+            1 => entry.draws += 1,
+            _ => {},
+        }
+    }
+    return true;
+}
@@ -0,0 +1,112 @@
+pub mod builder;
+pub mod polyglot;
+
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use rand::Rng;
+
+use crate::chess::game::Game;
+use crate::chess::r#move::Move;
+use crate::chess::zobrist;
+
+use polyglot::*;
+
+// Opening books in the Polyglot format.
+
+pub const BOOK_FILE: &str = "book.bin";
+
+pub struct Book {
+    entries: Vec<Entry>,
+}
+
+impl Book {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let bytes = match fs::read(path) {
+            Ok(b) => b,
+            Err(e) => return Err(format!("Could not read {}: {}", path.display(), e)),
+        };
+        if bytes.len() % ENTRY_SIZE != 0 {
+            return Err(format!("{} is not a Polyglot book", path.display()));
+        }
+
+        let mut entries: Vec<Entry> = bytes.chunks(ENTRY_SIZE).map(Entry::from_bytes).collect();
+        // Books are supposed to be sorted already, but a lookup on an unsorted one would quietly miss.
+        entries.sort_by_key(|e| e.key);
+        return Ok(Book{entries: entries});
+    }
+
+    pub fn len(&self) -> usize {
+        return self.entries.len();
+    }
+
+    fn lookup(&self, key: u64) -> &[Entry] {
+        let start = self.entries.partition_point(|e| e.key < key);
+        let end = self.entries.partition_point(|e| e.key <= key);
+        return &self.entries[start..end];
+    }
+
+    // The book moves for the current position with their weights, best first.
+    pub fn moves(&self, game: &Game) -> Vec<(Move, u16)> {
+        let mut moves = Vec::new();
+        for e in self.lookup(zobrist::hash(game)) {
+            if let Some(m) = decode_move(game, e.mv) {
+                moves.push((m, e.weight));
+            }
+        }
+        moves.sort_by(|a, b| b.1.cmp(&a.1));
+        return moves;
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Selection {
+    WeightedRandom,
+    Best,
+}
+
+impl Selection {
+    pub fn to_string(self) -> String {
+        match self {
+            Selection::WeightedRandom => "Weighted Random".to_string(),
+            Selection::Best => "Best Move".to_string(),
+        }
+    }
+}
+
+// How a bot uses a book: only for its first `depth` moves, choosing moves by `selection`.
+#[derive(Clone)]
+pub struct BookConfig {
+    pub book: Arc<Book>,
+    pub depth: u32,
+    pub selection: Selection,
+}
+
+impl BookConfig {
+    pub fn probe(&self, game: &Game) -> Option<Move> {
+        if game.history().len() as u32 >= 2 * self.depth {
+            return None;
+        }
+        let moves = self.book.moves(game);
+        if moves.len() == 0 {
+            return None;
+        }
+        if self.selection == Selection::Best {
+            return Some(moves[0].0);
+        }
+
+        let total: u32 = moves.iter().map(|(_, w)| *w as u32).sum();
+        if total == 0 {
+            return Some(moves[0].0);
+        }
+        let mut pick = rand::rng().random_range(0..total);
+        for (m, w) in &moves {
+            if pick < *w as u32 {
+                return Some(*m);
+            }
+            pick -= *w as u32;
+        }
+        return Some(moves[0].0);
+    }
+}
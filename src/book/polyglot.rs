@@ -0,0 +1,112 @@
+use crate::chess::color::Color;
+use crate::chess::game::Game;
+use crate::chess::piece::PieceKind;
+use crate::chess::r#move::Move;
+use crate::chess::rankfile::*;
+
+// Polyglot .bin books are a list of 16 byte big-endian entries sorted by position key:
+//   key: u64, move: u16, weight: u16, learn: u32
+// The move packs the destination file and rank (bits 0-5), the origin file and rank (bits 6-11)
+// and the promotion piece (bits 12-14: none, knight, bishop, rook, queen). Castling is written as
+// the king capturing its own rook, e.g. e1h1 for white short castles.
+
+pub const ENTRY_SIZE: usize = 16;
+
+#[derive(Copy, Clone)]
+pub struct Entry {
+    pub key: u64,
+    pub mv: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+impl Entry {
+    pub fn from_bytes(b: &[u8]) -> Self {
+        let mut key: u64 = 0;
+        for byte in &b[0..8] {
+            key = (key << 8) | *byte as u64;
+        }
+        return Entry {
+            key: key,
+            mv: u16::from_be_bytes([b[8], b[9]]),
+            weight: u16::from_be_bytes([b[10], b[11]]),
+            learn: u32::from_be_bytes([b[12], b[13], b[14], b[15]]),
+        };
+    }
+
+    pub fn to_bytes(&self) -> [u8; ENTRY_SIZE] {
+        let mut b = [0u8; ENTRY_SIZE];
+        b[0..8].copy_from_slice(&self.key.to_be_bytes());
+        b[8..10].copy_from_slice(&self.mv.to_be_bytes());
+        b[10..12].copy_from_slice(&self.weight.to_be_bytes());
+        b[12..16].copy_from_slice(&self.learn.to_be_bytes());
+        return b;
+    }
+}
+
+fn promotion_kind(code: u16) -> PieceKind {
+    return match code {
+        1 => PieceKind::Knight,
+        2 => PieceKind::Bishop,
+        3 => PieceKind::Rook,
+        4 => PieceKind::Queen,
+        _ => PieceKind::None,
+    };
+}
+
+fn promotion_code(kind: PieceKind) -> u16 {
+    return match kind {
+        PieceKind::Knight => 1,
+        PieceKind::Bishop => 2,
+        PieceKind::Rook => 3,
+        PieceKind::Queen => 4,
+        _ => 0,
+    };
+}
+
+// Packs a move played by `color` into the Polyglot move format.
+pub fn encode_move(m: &Move, color: Color) -> u16 {
+    let (from, to) = if m.castle || m.long_castle {
+        let back = back_rank_index(color);
+        ((4, back), (rook_castle_file(m.long_castle), back))
+    } else {
+        (
+            (m.origin.0.index().unwrap_or(0), m.origin.1.index().unwrap_or(0)),
+            (m.dest.0.index().unwrap_or(0), m.dest.1.index().unwrap_or(0)),
+        )
+    };
+    return (to.0 | to.1 << 3 | from.0 << 6 | from.1 << 9) as u16 | promotion_code(m.promotion) << 12;
+}
+
+// Finds the legal move in the game matching a Polyglot move, if there is one.
+pub fn decode_move(game: &Game, code: u16) -> Option<Move> {
+    let to = ((code & 7) as usize, ((code >> 3) & 7) as usize);
+    let from = (((code >> 6) & 7) as usize, ((code >> 9) & 7) as usize);
+    let promotion = promotion_kind((code >> 12) & 7);
+
+    for mut m in game.list_valid_moves() {
+        if m.castle || m.long_castle {
+            let back = back_rank_index(game.current_color());
+            if from == (4, back) && to == (rook_castle_file(m.long_castle), back)
+                && game.piece_at(4, back).kind == PieceKind::King
+            {
+                return Some(m);
+            }
+            continue;
+        }
+        if m.origin.0.index() != Some(from.0) || m.origin.1.index() != Some(from.1)
+            || m.dest.0.index() != Some(to.0) || m.dest.1.index() != Some(to.1)
+        {
+            continue;
+        }
+        // Only queen promotions are generated, the others are the same move with a different piece.
+        if m.promotion != PieceKind::None {
+            if promotion == PieceKind::None {
+                continue;
+            }
+            m.promotion = promotion;
+        }
+        return Some(m);
+    }
+    return None;
+}
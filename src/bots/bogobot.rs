@@ -15,17 +15,24 @@ impl Bogobot {
     }
 }
 
+// Any one of the legal moves, all equally likely. The MCTS bot plays its playouts with this too.
+pub fn random_move<R: Rng>(game: &Game, rng: &mut R) -> Option<Move> {
+    let all_moves = game.list_valid_moves();
+    if all_moves.len() == 0 {
+        return None;
+    }
+    let choice = rng.random_range(0..all_moves.len());
+    return Some(all_moves[choice]);
+}
+
 impl Player for Bogobot {
     fn reset(&mut self)  {}
 
     fn get_move(&self, game: &Game) -> Result<Move, String> {
-        let all_moves = game.list_valid_moves();
-        if all_moves.len() == 0 {
-            return Err(String::from("No valid moves for BogoBot!"));
-        }
-        let mut rng = rand::rng();
-        let choice = rng.random_range(0..all_moves.len());
-        return Ok(all_moves[choice]);
+        return match random_move(game, &mut rand::rng()) {
+            Some(m) => Ok(m),
+            None => Err(String::from("No valid moves for BogoBot!")),
+        };
     }
 
     fn is_bot(&self) -> bool {
@@ -35,4 +42,9 @@ impl Player for Bogobot {
     fn id_string(&self) -> String {
         return String::from("BogoBot");
     }
+
+    // Random moves lose to almost anyone.
+    fn anchor_rating(&self) -> Option<f64> {
+        return Some(400.0);
+    }
 }
@@ -0,0 +1,452 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::chess::coordinate::{resolve_coordinate, to_coordinate};
+use crate::chess::fen::to_fen;
+use crate::chess::game::Game;
+use crate::chess::player::*;
+use crate::chess::r#move::Move;
+use crate::chess::zobrist;
+
+use super::searchbot::MATE;
+
+// An external UCI engine as a player. The engine runs as a child process for as long as any clone
+// of the player is around. For each move it is sent the game's moves from the initial position (or
+// the position's FEN, for a game that was set up) and asked to think for a fixed time, or within
+// the limits it is given; its `bestmove` is read back as coordinate notation and checked through
+// Game::disambiguate, the same way the xboard protocol reads moves.
+
+// How long the engine may take to start up or to answer beyond its thinking time.
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone)]
+pub struct EngineConfig {
+    pub path: String,
+    pub options: Vec<(String, String)>, // sent with setoption before the first game
+    pub movetime: Duration,
+}
+
+impl EngineConfig {
+    pub fn new() -> Self {
+        return EngineConfig{path: String::new(), options: Vec::new(), movetime: Duration::from_secs(1)};
+    }
+
+    // "Hash=64, Threads=2"
+    pub fn parse_options(text: &str) -> Result<Vec<(String, String)>, String> {
+        let mut options = Vec::new();
+        for part in text.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+            match part.split_once('=') {
+                Some((name, value)) if !name.trim().is_empty() => options.push((name.trim().to_string(), value.trim().to_string())),
+                _ => return Err(format!("Expected Name=Value, not {}", part)),
+            }
+        }
+        return Ok(options);
+    }
+
+    pub fn options_string(&self) -> String {
+        return self.options.iter().map(|(n, v)| format!("{}={}", n, v)).collect::<Vec<String>>().join(", ");
+    }
+}
+
+struct Process {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+}
+
+impl Process {
+    fn send(&mut self, line: &str) -> Result<(), String> {
+        return match writeln!(self.stdin, "{}", line).and_then(|_| self.stdin.flush()) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Engine is gone: {}", e)),
+        };
+    }
+
+    // The first line starting with `token`, skipping everything before it.
+    fn wait_for(&mut self, token: &str, timeout: Duration) -> Result<String, String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match self.lines.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(line) if line.split_whitespace().next() == Some(token) => return Ok(line),
+                Ok(_) => continue,
+                Err(RecvTimeoutError::Timeout) => return Err(format!("Engine did not answer with {}", token)),
+                Err(RecvTimeoutError::Disconnected) => return Err(String::from("Engine has quit")),
+            }
+        }
+    }
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let deadline = Instant::now() + Duration::from_millis(500);
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[derive(Clone)]
+pub struct UciEngine {
+    name: String,
+    movetime: Duration,
+    process: Arc<Mutex<Process>>,
+}
+
+impl UciEngine {
+    // Starts the engine and goes through the handshake, so that a bad path or an engine that does
+    // not speak UCI shows up straight away.
+    pub fn start(config: &EngineConfig) -> Result<Self, String> {
+        if config.path.is_empty() {
+            return Err(String::from("No engine path set"));
+        }
+        let mut child = match Command::new(&config.path).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::null()).spawn() {
+            Ok(c) => c,
+            Err(e) => return Err(format!("Could not start {}: {}", config.path, e)),
+        };
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (send, receive) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(l) if send.send(l.trim().to_string()).is_ok() => continue,
+                    _ => return,
+                }
+            }
+        });
+
+        let mut process = Process{child: child, stdin: stdin, lines: receive};
+        process.send("uci")?;
+        let mut name = config.path.clone();
+        let deadline = Instant::now() + ANSWER_TIMEOUT;
+        loop {
+            let line = match process.lines.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(l) => l,
+                Err(_) => return Err(format!("{} does not speak UCI", config.path)),
+            };
+            if let Some(n) = line.strip_prefix("id name ") {
+                name = n.trim().to_string();
+            } else if line == "uciok" {
+                break;
+            }
+        }
+        for (option, value) in &config.options {
+            process.send(&format!("setoption name {} value {}", option, value))?;
+        }
+        process.send("isready")?;
+        process.wait_for("readyok", ANSWER_TIMEOUT)?;
+        return Ok(UciEngine{name: name, movetime: config.movetime, process: Arc::new(Mutex::new(process))});
+    }
+}
+
+// The moves from the initial position, when the game's history leads from there to the position;
+// otherwise the position itself as a FEN.
+fn position_command(game: &Game) -> String {
+    let mut replay = Game::new();
+    replay.default_board();
+    let mut moves = Vec::new();
+    for m in game.history() {
+        moves.push(to_coordinate(&replay, m));
+        replay.do_move(*m);
+        replay.next_turn();
+    }
+    if replay.current_color() != game.current_color() || zobrist::hash(&replay) != zobrist::hash(game) {
+        return format!("position fen {}", to_fen(game));
+    }
+    if moves.len() == 0 {
+        return String::from("position startpos");
+    }
+    return format!("position startpos moves {}", moves.join(" "));
+}
+
+// What an `info` line says about the search, when it has a score. The principal variation is read
+// up to its first move that does not parse.
+fn parse_info(game: &Game, line: &str) -> Option<Thinking> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let mut thinking = Thinking{depth: 0, score: 0, mate: None, nodes: 0, pv: Vec::new()};
+    let mut scored = false;
+    let number = |i: usize| tokens.get(i + 1).and_then(|t| t.parse::<i64>().ok());
+    for (i, token) in tokens.iter().enumerate() {
+        match *token {
+            "depth" => thinking.depth = number(i).unwrap_or(0) as u32,
+            "nodes" => thinking.nodes = number(i).unwrap_or(0) as u64,
+            "score" => {
+                let value = tokens.get(i + 2).and_then(|t| t.parse::<i32>().ok());
+                match (tokens.get(i + 1), value) {
+                    (Some(&"cp"), Some(v)) => thinking.score = v,
+                    (Some(&"mate"), Some(v)) => {
+                        thinking.mate = Some(v);
+                        thinking.score = if v > 0 { MATE - 2 * v + 1 } else { -MATE - 2 * v };
+                    },
+                    _ => continue,
+                }
+                scored = true;
+            },
+            "pv" => {
+                let mut replay = game.clone();
+                for text in &tokens[i + 1..] {
+                    match resolve_coordinate(&replay, text) {
+                        Ok(m) => {
+                            thinking.pv.push(m);
+                            replay.do_move(m);
+                            replay.next_turn();
+                        },
+                        Err(_) => break,
+                    }
+                }
+                break;
+            },
+            _ => {},
+        }
+    }
+    return if scored { Some(thinking) } else { None };
+}
+
+impl Player for UciEngine {
+    fn reset(&mut self) {
+        let mut process = self.process.lock().unwrap();
+        if process.send("ucinewgame").is_ok() && process.send("isready").is_ok() {
+            let _ = process.wait_for("readyok", ANSWER_TIMEOUT);
+        }
+    }
+
+    fn get_move(&self, game: &Game) -> Result<Move, String> {
+        let mut limits = Limits::new();
+        limits.movetime = Some(self.movetime);
+        return self.think(game, limits, Arc::new(AtomicBool::new(false)), Box::new(|_| {}));
+    }
+
+    // Without a time or a depth the engine thinks for its configured time. Its info lines are
+    // passed on as reports; `stop` is forwarded to it.
+    fn think(&self, game: &Game, limits: Limits, stop: Arc<AtomicBool>, mut report: Report) -> Result<Move, String> {
+        let mut process = self.process.lock().unwrap();
+        process.send(&position_command(game))?;
+        let go = if limits.infinite {
+            String::from("go infinite")
+        } else if let (Some(depth), None) = (limits.depth, limits.movetime) {
+            format!("go depth {}", depth)
+        } else {
+            format!("go movetime {}", limits.movetime.unwrap_or(self.movetime).as_millis())
+        };
+        process.send(&go)?;
+
+        let mut deadline = match limits.movetime {
+            _ if limits.infinite || (limits.depth.is_some() && limits.movetime.is_none()) => None,
+            Some(t) => Some(Instant::now() + t + ANSWER_TIMEOUT),
+            None => Some(Instant::now() + self.movetime + ANSWER_TIMEOUT),
+        };
+        let mut stopped = false;
+        let line = loop {
+            if !stopped && stop.load(Ordering::Relaxed) {
+                process.send("stop")?;
+                stopped = true;
+                deadline = Some(Instant::now() + ANSWER_TIMEOUT);
+            }
+            match process.lines.recv_timeout(Duration::from_millis(10)) {
+                Ok(line) if line.starts_with("bestmove") => break line,
+                Ok(line) if line.starts_with("info") => {
+                    if let Some(t) = parse_info(game, &line) {
+                        report(&t);
+                    }
+                },
+                Ok(_) => {},
+                Err(RecvTimeoutError::Timeout) if deadline.is_some_and(|d| Instant::now() >= d) => {
+                    return Err(String::from("Engine did not answer with bestmove"));
+                },
+                Err(RecvTimeoutError::Timeout) => {},
+                Err(RecvTimeoutError::Disconnected) => return Err(String::from("Engine has quit")),
+            }
+        };
+        let text = match line.split_whitespace().nth(1) {
+            Some(t) if t != "(none)" && t != "0000" => t.to_string(),
+            _ => return Err(format!("No move from {}", self.name)),
+        };
+        return match resolve_coordinate(game, &text) {
+            Ok(m) => Ok(m),
+            Err(e) => Err(format!("{}: {}", self.name, e)),
+        };
+    }
+
+    fn is_bot(&self) -> bool {
+        return true;
+    }
+
+    fn id_string(&self) -> String {
+        return self.name.clone();
+    }
+}
+
+// The player against a scripted stand-in engine: a shell script that answers the handshake,
+// answers `go` with a fixed bestmove (or by quitting) and writes every line it is sent to a log.
+#[cfg(all(test, unix))]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicBool;
+
+    use crate::chess::fen::parse_fen;
+    use crate::chess::piece::PieceKind;
+
+    use super::*;
+
+    struct Script {
+        dir: PathBuf,
+    }
+
+    impl Script {
+        // `on_go` is the shell run for a `go` command.
+        fn new(name: &str, on_go: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("jchess-engine-{}-{}", std::process::id(), name));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            let script = format!(
+                "#!/bin/sh\n\
+                 while read -r line; do\n\
+                 echo \"$line\" >> '{log}'\n\
+                 case \"$line\" in\n\
+                 uci) echo 'id name Scripted'; echo 'option name Hash type spin default 1 min 1 max 64'; echo uciok;;\n\
+                 isready) echo readyok;;\n\
+                 go*) {go};;\n\
+                 quit) exit 0;;\n\
+                 esac\n\
+                 done\n",
+                log = dir.join("log").display(), go = on_go);
+            let path = dir.join("engine.sh");
+            fs::write(&path, script).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+            return Script{dir: dir};
+        }
+
+        fn config(&self) -> EngineConfig {
+            let mut config = EngineConfig::new();
+            config.path = self.dir.join("engine.sh").display().to_string();
+            config.movetime = Duration::from_millis(50);
+            return config;
+        }
+
+        fn log(&self) -> Vec<String> {
+            return fs::read_to_string(self.dir.join("log")).unwrap_or_default().lines().map(|l| l.to_string()).collect();
+        }
+    }
+
+    impl Drop for Script {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn start_position() -> Game {
+        let mut game = Game::new();
+        game.default_board();
+        return game;
+    }
+
+    #[test]
+    fn handshake_sends_options_before_isready() {
+        let script = Script::new("handshake", "echo 'bestmove e2e4'");
+        let mut config = script.config();
+        config.options = EngineConfig::parse_options("Hash=16, Move Overhead=30").unwrap();
+        let engine = UciEngine::start(&config).unwrap();
+        assert_eq!(engine.id_string(), "Scripted");
+        drop(engine);
+        let log = script.log();
+        assert_eq!(log[0], "uci");
+        assert_eq!(log[1], "setoption name Hash value 16");
+        assert_eq!(log[2], "setoption name Move Overhead value 30");
+        assert_eq!(log[3], "isready");
+    }
+
+    #[test]
+    fn reads_bestmove_and_info() {
+        let script = Script::new("bestmove", "echo 'info depth 3 score cp 25 nodes 100 pv e2e4 e7e5 zz'; echo 'bestmove e2e4 ponder e7e5'");
+        let engine = UciEngine::start(&script.config()).unwrap();
+        let game = start_position();
+        let reports: Arc<Mutex<Vec<Thinking>>> = Arc::new(Mutex::new(Vec::new()));
+        let seen = reports.clone();
+        let mut limits = Limits::new();
+        limits.movetime = Some(Duration::from_millis(50));
+        let m = engine.think(&game, limits, Arc::new(AtomicBool::new(false)), Box::new(move |t: &Thinking| {
+            seen.lock().unwrap().push(t.clone());
+        })).unwrap();
+        assert_eq!(to_coordinate(&game, &m), "e2e4");
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!((reports[0].depth, reports[0].score, reports[0].nodes), (3, 25, 100));
+        // The principal variation stops at the move that does not parse.
+        assert_eq!(reports[0].pv.len(), 2);
+        assert!(script.log().contains(&String::from("position startpos")));
+        assert!(script.log().contains(&String::from("go movetime 50")));
+    }
+
+    #[test]
+    fn illegal_move_is_an_error() {
+        let script = Script::new("illegal", "echo 'bestmove e2e5'");
+        let engine = UciEngine::start(&script.config()).unwrap();
+        let e = engine.get_move(&start_position()).err().unwrap();
+        assert!(e.contains("Illegal move"), "{}", e);
+    }
+
+    #[test]
+    fn garbage_move_is_an_error() {
+        let script = Script::new("garbage", "echo 'bestmove banana'");
+        let engine = UciEngine::start(&script.config()).unwrap();
+        let e = engine.get_move(&start_position()).err().unwrap();
+        assert!(e.contains("Not a move"), "{}", e);
+    }
+
+    #[test]
+    fn promotion_letter_on_a_plain_move_is_an_error() {
+        let script = Script::new("letter", "echo 'bestmove e2e4q'");
+        let engine = UciEngine::start(&script.config()).unwrap();
+        let e = engine.get_move(&start_position()).err().unwrap();
+        assert!(e.contains("Not a promotion"), "{}", e);
+    }
+
+    #[test]
+    fn promotion_needs_a_letter() {
+        let game = parse_fen("k7/4P3/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let script = Script::new("no-letter", "echo 'bestmove e7e8'");
+        let engine = UciEngine::start(&script.config()).unwrap();
+        let e = engine.get_move(&game).err().unwrap();
+        assert!(e.contains("Missing promotion"), "{}", e);
+
+        let script = Script::new("knight", "echo 'bestmove e7e8n'");
+        let engine = UciEngine::start(&script.config()).unwrap();
+        let m = engine.get_move(&game).unwrap();
+        assert!(m.promotion == PieceKind::Knight);
+    }
+
+    #[test]
+    fn engine_dying_mid_game_is_an_error() {
+        let script = Script::new("dies", "exit 1");
+        let engine = UciEngine::start(&script.config()).unwrap();
+        let e = engine.get_move(&start_position()).err().unwrap();
+        assert_eq!(e, "Engine has quit");
+        assert!(engine.get_move(&start_position()).is_err());
+    }
+
+    #[test]
+    fn drop_sends_quit() {
+        let script = Script::new("quit", "echo 'bestmove e2e4'");
+        let engine = UciEngine::start(&script.config()).unwrap();
+        let copy = engine.clone();
+        drop(engine);
+        // The process lives on while a clone of the player does.
+        assert!(!script.log().contains(&String::from("quit")));
+        drop(copy);
+        assert_eq!(script.log().last().map(|l| l.as_str()), Some("quit"));
+    }
+}
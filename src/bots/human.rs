@@ -3,14 +3,20 @@ use crate::chess::player::*;
 use crate::chess::r#move::*;
 use crate::input::*;
 
-// Here for human input
+// Here for human input. A human with a name has their games rated under it.
 
 #[derive(Clone)]
-pub struct Human();
+pub struct Human {
+    name: Option<String>,
+}
 
 impl Human {
     pub fn new() -> Self {
-        return Human();
+        return Human{name: None};
+    }
+
+    pub fn named(name: &str) -> Self {
+        return Human{name: Some(name.to_string())};
     }
 }
 
@@ -18,7 +24,11 @@ impl Player for Human {
     fn reset(&mut self) {}
 
     fn get_move(&self, game: &Game) -> Result<Move, String> {
-        let input = read_line();
+        // With no more input the game can only be left.
+        let input = match next_line() {
+            Some(l) => l,
+            None => return Ok(Move::new_meta(MetaMove::Quit)),
+        };
 
         return match parse_notation(input, game.current_color()) {
             Ok(m) => Ok(m),
@@ -31,6 +41,9 @@ impl Player for Human {
     }
 
     fn id_string(&self) -> String {
-        return String::from("Human");
+        return match &self.name {
+            Some(n) => n.clone(),
+            None => String::from("Human"),
+        };
     }
 }
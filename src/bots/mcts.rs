@@ -0,0 +1,256 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use crate::chess::game::Game;
+use crate::chess::player::*;
+use crate::chess::r#move::Move;
+use crate::eval::evaluate;
+use crate::eval::weights::Weights;
+
+use super::bogobot::random_move;
+
+// A Monte Carlo Tree Search bot. Each iteration walks down the tree choosing children by UCT,
+// adds one new position, plays random moves from there (BogoBot style) and feeds the result back
+// up the path. The most visited move at the root is played.
+//
+// A playout either runs to the end of the game, or with a playout depth stops after that many
+// plies and scores the position with the evaluation instead, which is much quicker and less noisy.
+//
+// Given limits, a time replaces the budget and a depth means nothing to it.
+
+const MAX_PLAYOUT: u32 = 200; // plies before an unfinished random game counts as a draw
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Budget {
+    Iterations(u32),
+    Time(Duration),
+}
+
+impl Budget {
+    // "2000" for iterations, "5s" or "500ms" for time.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if let Some(ms) = text.strip_suffix("ms") {
+            return match ms.trim().parse::<u64>() {
+                Ok(n) if n > 0 => Some(Budget::Time(Duration::from_millis(n))),
+                _ => None,
+            };
+        }
+        if let Some(s) = text.strip_suffix('s') {
+            // Negative, infinite or out of range times are not budgets.
+            return match s.trim().parse::<f64>() {
+                Ok(secs) if secs.is_finite() && secs > 0.0 => Duration::try_from_secs_f64(secs).ok().map(Budget::Time),
+                _ => None,
+            };
+        }
+        return match text.parse::<u32>() {
+            Ok(n) if n > 0 => Some(Budget::Iterations(n)),
+            _ => None,
+        };
+    }
+
+    pub fn to_string(self) -> String {
+        return match self {
+            Budget::Iterations(n) => format!("{} iterations", n),
+            Budget::Time(d) => format!("{}ms", d.as_millis()),
+        };
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub struct MctsOptions {
+    pub budget: Budget,
+    pub exploration: f64,
+    pub playout_depth: u32, // 0 plays out to the end of the game
+    pub seed: Option<u64>,  // None seeds from the OS
+}
+
+impl MctsOptions {
+    pub fn new() -> Self {
+        return MctsOptions {
+            budget: Budget::Iterations(2000),
+            exploration: 1.4,
+            playout_depth: 8,
+            seed: None,
+        };
+    }
+}
+
+struct Node {
+    game: Game,
+    mv: Option<Move>,    // the move that led here
+    parent: Option<usize>,
+    children: Vec<usize>,
+    untried: Vec<Move>,
+    visits: u32,
+    score: f64,          // summed results for the side that made `mv`
+}
+
+impl Node {
+    fn new(game: Game, mv: Option<Move>, parent: Option<usize>) -> Self {
+        let untried = game.list_valid_moves();
+        return Node{game: game, mv: mv, parent: parent, children: Vec::new(), untried: untried, visits: 0, score: 0.0};
+    }
+}
+
+#[derive(Clone)]
+pub struct MctsBot {
+    options: MctsOptions,
+    weights: Arc<Weights>,
+}
+
+impl MctsBot {
+    pub fn new(options: MctsOptions, weights: Arc<Weights>) -> Self {
+        return MctsBot{options: options, weights: weights};
+    }
+
+    // With a seed the same position always gets the same move.
+    fn rng(&self, game: &Game) -> StdRng {
+        return match self.options.seed {
+            Some(seed) => StdRng::seed_from_u64(seed ^ game.history().len() as u64),
+            None => StdRng::from_rng(&mut rand::rng()),
+        };
+    }
+
+    fn search(&self, game: &Game, budget: Budget, stop: &AtomicBool) -> Option<Move> {
+        let mut rng = self.rng(game);
+        let mut tree = vec![Node::new(game.clone(), None, None)];
+        if tree[0].untried.len() == 0 {
+            return None;
+        }
+
+        let start = Instant::now();
+        let mut iterations = 0;
+        loop {
+            let done = stop.load(Ordering::Relaxed) || match budget {
+                Budget::Iterations(n) => iterations >= n,
+                Budget::Time(d) => start.elapsed() >= d,
+            };
+            if done && iterations > 0 {
+                break;
+            }
+            iterations += 1;
+
+            let mut node = self.select(&tree, 0);
+            if tree[node].untried.len() > 0 {
+                node = expand(&mut tree, node, &mut rng);
+            }
+            // The result for the side to move at the new node, so for the side that moved into it
+            // it is the other way round.
+            let result = self.playout(&tree[node].game, &mut rng);
+            backpropagate(&mut tree, node, 1.0 - result);
+        }
+
+        let best = tree[0].children.iter().max_by_key(|c| tree[**c].visits)?;
+        return tree[*best].mv;
+    }
+
+    // Follows the UCT choice down to a node with moves left to try, or to the end of the game.
+    fn select(&self, tree: &Vec<Node>, mut node: usize) -> usize {
+        while tree[node].untried.len() == 0 && tree[node].children.len() > 0 {
+            let parent_log = (tree[node].visits.max(1) as f64).ln();
+            let mut best = tree[node].children[0];
+            let mut best_uct = f64::MIN;
+            for c in &tree[node].children {
+                let child = &tree[*c];
+                let visits = child.visits.max(1) as f64;
+                let uct = child.score / visits + self.options.exploration * (parent_log / visits).sqrt();
+                if uct > best_uct {
+                    best_uct = uct;
+                    best = *c;
+                }
+            }
+            node = best;
+        }
+        return node;
+    }
+
+    // Plays random moves and returns the result for the side to move at the start: 1 for a win,
+    // 0 for a loss, and in between for draws and evaluated positions.
+    fn playout<R: Rng>(&self, start: &Game, rng: &mut R) -> f64 {
+        let mut game = start.clone();
+        let limit = if self.options.playout_depth == 0 { MAX_PLAYOUT } else { self.options.playout_depth };
+        let mut plies = 0;
+        loop {
+            let m = match random_move(&game, rng) {
+                Some(m) => m,
+                None => {
+                    let result = if game.is_check() { 0.0 } else { 0.5 };
+                    return if plies % 2 == 0 { result } else { 1.0 - result };
+                },
+            };
+            if plies >= limit {
+                if self.options.playout_depth == 0 {
+                    return 0.5;
+                }
+                // A logistic curve turns centipawns into an expected score.
+                let cp = evaluate(&game, &self.weights) as f64;
+                let result = 1.0 / (1.0 + 10f64.powf(-cp / 400.0));
+                return if plies % 2 == 0 { result } else { 1.0 - result };
+            }
+            game.do_move(m);
+            game.next_turn();
+            plies += 1;
+        }
+    }
+}
+
+fn expand<R: Rng>(tree: &mut Vec<Node>, node: usize, rng: &mut R) -> usize {
+    let i = rng.random_range(0..tree[node].untried.len());
+    let m = tree[node].untried.swap_remove(i);
+    let mut next = tree[node].game.clone();
+    next.do_move(m);
+    next.next_turn();
+    tree.push(Node::new(next, Some(m), Some(node)));
+    let child = tree.len() - 1;
+    tree[node].children.push(child);
+    return child;
+}
+
+// `result` is for the side that moved into `node`; it flips at every step up.
+fn backpropagate(tree: &mut Vec<Node>, mut node: usize, mut result: f64) {
+    loop {
+        tree[node].visits += 1;
+        tree[node].score += result;
+        match tree[node].parent {
+            Some(p) => node = p,
+            None => return,
+        }
+        result = 1.0 - result;
+    }
+}
+
+impl Player for MctsBot {
+    fn reset(&mut self) {}
+
+    fn get_move(&self, game: &Game) -> Result<Move, String> {
+        return match self.search(game, self.options.budget, &AtomicBool::new(false)) {
+            Some(m) => Ok(m),
+            None => Err(String::from("No valid moves for MctsBot!")),
+        };
+    }
+
+    fn think(&self, game: &Game, limits: Limits, stop: Arc<AtomicBool>, _report: Report) -> Result<Move, String> {
+        let budget = match limits.movetime {
+            Some(t) => Budget::Time(t),
+            None if limits.infinite => Budget::Time(Duration::MAX),
+            None => self.options.budget,
+        };
+        return match self.search(game, budget, &stop) {
+            Some(m) => Ok(m),
+            None => Err(String::from("No valid moves for MctsBot!")),
+        };
+    }
+
+    fn is_bot(&self) -> bool {
+        return true;
+    }
+
+    fn id_string(&self) -> String {
+        return String::from("MctsBot");
+    }
+}
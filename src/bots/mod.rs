@@ -1,2 +1,85 @@
 pub mod bogobot;
+pub mod engine;
 pub mod human;
+pub mod mcts;
+pub mod searchbot;
+pub mod transposition;
+
+use std::sync::Arc;
+
+use crate::chess::player::Player;
+use crate::eval::nnue::{Network, NETWORK_FILE};
+use crate::eval::weights::{Weights, WEIGHTS_FILE};
+use crate::storage::data_file;
+
+use bogobot::Bogobot;
+use engine::{EngineConfig, UciEngine};
+use mcts::{Budget, MctsBot, MctsOptions};
+use searchbot::{SearchBot, SearchOptions, MAX_LEVEL};
+
+pub const SPEC_HELP: &str = "Bots: bogo, search[:depth=N,level=N,threads=N,nnue], mcts[:budget=N or 5s,seed=N,playout=N], uci:PATH";
+
+// Builds a bot from a short description for the headless subcommands, such as "search:depth=5" or
+// "mcts:budget=2s". Weights and the network come from the data directory as in the interactive
+// game. Pondering is left off since these bots usually play each other on the same machine.
+// "uci:PATH" starts an external UCI engine instead; everything after the colon is the path.
+pub fn from_spec(spec: &str) -> Result<Box<dyn Player>, String> {
+    if let Some(path) = spec.strip_prefix("uci:") {
+        let mut config = EngineConfig::new();
+        config.path = path.to_string();
+        return Ok(Box::new(UciEngine::start(&config)?));
+    }
+    let (name, params) = match spec.split_once(':') {
+        Some((n, p)) => (n, p),
+        None => (spec, ""),
+    };
+    let mut options: Vec<(&str, &str)> = Vec::new();
+    for param in params.split(',').filter(|p| !p.is_empty()) {
+        options.push(match param.split_once('=') {
+            Some((k, v)) => (k, v),
+            None => (param, ""),
+        });
+    }
+    let bad = |key: &str, value: &str| format!("Bad value for {} in {}: {}", key, spec, value);
+    let weights = Arc::new(Weights::load_or_default(&data_file(WEIGHTS_FILE))?);
+
+    match name {
+        "bogo" => {
+            if let Some((key, _)) = options.first() {
+                return Err(format!("Unknown option for bogo: {}", key));
+            }
+            return Ok(Box::new(Bogobot::new()));
+        },
+        "search" => {
+            let mut o = SearchOptions::new();
+            o.ponder = false;
+            for (key, value) in options {
+                match key {
+                    "depth" => o.depth = value.parse().ok().filter(|d| *d >= 1).ok_or(bad(key, value))?,
+                    "level" => o.level = value.parse().ok().filter(|l| (1..=MAX_LEVEL).contains(l)).ok_or(bad(key, value))?,
+                    "threads" => o.threads = value.parse().ok().filter(|t| *t >= 1).ok_or(bad(key, value))?,
+                    "nnue" => o.nnue = true,
+                    _ => return Err(format!("Unknown option for search: {}", key)),
+                }
+            }
+            let mut bot = SearchBot::new(o, weights);
+            if o.nnue {
+                bot.set_network(Some(Arc::new(Network::load(&data_file(NETWORK_FILE))?)));
+            }
+            return Ok(Box::new(bot));
+        },
+        "mcts" => {
+            let mut o = MctsOptions::new();
+            for (key, value) in options {
+                match key {
+                    "budget" => o.budget = Budget::parse(value).ok_or(bad(key, value))?,
+                    "seed" => o.seed = Some(value.parse().map_err(|_| bad(key, value))?),
+                    "playout" => o.playout_depth = value.parse().map_err(|_| bad(key, value))?,
+                    _ => return Err(format!("Unknown option for mcts: {}", key)),
+                }
+            }
+            return Ok(Box::new(MctsBot::new(o, weights)));
+        },
+        _ => return Err(format!("Unknown bot: {}\n{}", name, SPEC_HELP)),
+    }
+}
@@ -0,0 +1,808 @@
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+use rand::Rng;
+
+use crate::bitbase::Bitbases;
+use crate::book::BookConfig;
+use crate::book::polyglot::{decode_move, encode_move};
+use crate::chess::game::Game;
+use crate::chess::piece::PieceKind;
+use crate::chess::player::*;
+use crate::chess::r#move::Move;
+use crate::chess::zobrist;
+use crate::eval::evaluate;
+use crate::eval::nnue::{Accumulator, Network};
+use crate::eval::weights::Weights;
+use crate::tablebase::Tablebase;
+
+use super::transposition::*;
+
+// An alpha-beta searching bot. The pruning techniques are individually toggleable through
+// SearchOptions so that each one can be measured on its own.
+//
+// With more than one thread the search is a Lazy SMP: helper threads search the same root at the
+// same time, some of them one ply deeper, and share what they find through the transposition
+// table. Only the main thread's result is played; the helpers stop when it is done.
+//
+// Pondering: after its move the bot guesses the reply from the transposition table (the next move
+// of the principal variation) and searches the position after it in the background while the
+// opponent thinks. If the guess was right it waits for that search and plays its move, otherwise
+// the search is stopped and thrown away.
+//
+// Strength levels below MAX_LEVEL handicap the bot: a shallower search with a node budget, noise
+// added to the evaluation, and now and then the second or third best move when it is not much
+// worse than the best.
+//
+// Searches can also be limited by time, as the engine protocols ask. Each depth is started only
+// while no more than half of the time has gone, and an iteration cut short by the clock or a stop
+// is thrown away in favour of the last one that finished.
+//
+// With a neural network loaded and enabled it replaces the handcrafted evaluation. The search keeps
+// one accumulator per ply: making a move updates the next ply's from the current one, and going
+// back up a ply simply leaves it to be overwritten by the next sibling.
+
+pub const MATE: i32 = 100000;
+pub const INFINITY: i32 = 1000000;
+// Tablebase results score below any mate found by the search itself.
+pub const TB_WIN: i32 = MATE / 2;
+
+const NULL_MOVE_REDUCTION: u32 = 2;
+const LMR_MIN_DEPTH: u32 = 3;
+const LMR_MIN_MOVES: usize = 3;
+const ASPIRATION_WINDOW: i32 = 50;
+const MAX_PLY: u32 = 64;
+// How deep a search with a time limit, or none at all, may go.
+const MAX_DEPTH: u32 = MAX_PLY - 4;
+pub const MAX_LEVEL: u32 = 20;
+
+#[derive(Copy, Clone, PartialEq)]
+pub struct SearchOptions {
+    pub depth: u32,
+    pub null_move: bool,
+    pub late_move_reductions: bool,
+    pub pvs: bool,
+    pub check_extensions: bool,
+    pub aspiration_windows: bool,
+    pub threads: u32,
+    pub ponder: bool,
+    pub level: u32, // 1 to MAX_LEVEL
+    pub nnue: bool,  // evaluate with the network, when one is loaded
+}
+
+impl SearchOptions {
+    pub fn new() -> Self {
+        SearchOptions {
+            depth: 4,
+            null_move: true,
+            late_move_reductions: true,
+            pvs: true,
+            check_extensions: true,
+            aspiration_windows: true,
+            threads: 1,
+            ponder: true,
+            level: MAX_LEVEL,
+            nnue: false,
+        }
+    }
+}
+
+// What a strength level takes away from the search.
+#[derive(Copy, Clone)]
+struct Handicap {
+    depth: u32,
+    nodes: u64,
+    noise: i32,         // the evaluation is off by up to this many centipawns either way
+    second_best: f64,   // chance of playing the second best move, half that for the third
+    max_loss: i32,      // how much worse than the best move a weaker pick may be
+}
+
+impl Handicap {
+    fn for_level(level: u32) -> Option<Self> {
+        if level >= MAX_LEVEL {
+            return None;
+        }
+        let level = level.max(1);
+        let missing = (MAX_LEVEL - level) as i32;
+        return Some(Handicap {
+            depth: 1 + level / 5,
+            nodes: 500 * (level * level) as u64,
+            noise: 15 * missing,
+            second_best: 0.025 * missing as f64,
+            max_loss: 20 * missing,
+        });
+    }
+}
+
+pub fn level_name(level: u32) -> String {
+    let name = match level {
+        0..=5 => "Beginner",
+        6..=10 => "Casual",
+        11..=15 => "Club",
+        16..=19 => "Strong",
+        _ => "Full Strength",
+    };
+    return format!("{} ({})", name, level);
+}
+
+// A rough rating for each level, from about 850 at level 1 to 2200 at full strength, to rate
+// games against the bot by.
+pub fn level_rating(level: u32) -> f64 {
+    return 800.0 + 70.0 * level.min(MAX_LEVEL) as f64;
+}
+
+#[derive(Clone)]
+pub struct SearchBot {
+    options: SearchOptions,
+    weights: Arc<Weights>,
+    network: Option<Arc<Network>>,
+    book: Option<BookConfig>,
+    tablebase: Option<Arc<Tablebase>>,
+    bitbases: Option<Arc<Bitbases>>,
+    tt: Arc<TranspositionTable>, // kept from move to move, shared by clones of the bot
+    ponder: Arc<Mutex<Option<Ponder>>>,
+}
+
+struct Ponder {
+    key: u64, // the position expected after the opponent's reply
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<Option<Move>>,
+}
+
+impl SearchBot {
+    pub fn new(options: SearchOptions, weights: Arc<Weights>) -> Self {
+        return SearchBot{
+            options: options,
+            weights: weights,
+            network: None,
+            book: None,
+            tablebase: None,
+            bitbases: None,
+            tt: Arc::new(TranspositionTable::new(DEFAULT_SIZE)),
+            ponder: Arc::new(Mutex::new(None)),
+        };
+    }
+
+    pub fn set_network(&mut self, network: Option<Arc<Network>>) {
+        self.network = network;
+    }
+
+    fn search(&self, game: &Game, stop: Arc<AtomicBool>) -> Option<Move> {
+        return self.search_within(game, Limits::new(), stop, None);
+    }
+
+    fn search_within(&self, game: &Game, limits: Limits, stop: Arc<AtomicBool>, report: Option<Report>) -> Option<Move> {
+        return thread::scope(|s| {
+            for id in 1..self.options.threads.max(1) {
+                let mut helper = Search::new(self, limits, stop.clone());
+                helper.depth += id % 2;
+                let game = game.clone();
+                s.spawn(move || helper.iterate(&game));
+            }
+            let mut main = Search::new(self, limits, stop.clone());
+            main.report = report;
+            let best = main.iterate(game);
+            stop.store(true, Ordering::Relaxed);
+            return best;
+        });
+    }
+
+    fn choose_move(&self, game: &Game) -> Option<Move> {
+        if let Some(m) = self.ponder_result(game) {
+            return Some(m);
+        }
+        if let Some(book) = &self.book {
+            if let Some(m) = book.probe(game) {
+                return Some(m);
+            }
+        }
+        // With DTZ tables the best move is known outright; with only WDL tables the search uses
+        // them at its leaves instead.
+        if let Some(tb) = &self.tablebase {
+            if let Some(m) = tb.best_move(game) {
+                return Some(m);
+            }
+        }
+        if let Some(bb) = &self.bitbases {
+            if let Some(m) = bb.best_move(game) {
+                return Some(m);
+            }
+        }
+        return self.search(game, Arc::new(AtomicBool::new(false)));
+    }
+
+    // Starts searching the position after `m` and the reply the table expects.
+    fn start_ponder(&self, game: &Game, m: Move) {
+        if !self.options.ponder {
+            return;
+        }
+        let after = child(game, m);
+        let reply = match self.tt.probe(zobrist::hash(&after)).and_then(|e| decode_move(&after, e.mv)) {
+            Some(r) => r,
+            None => return,
+        };
+        let target = child(&after, reply);
+        if !target.any_valid_moves() {
+            return;
+        }
+
+        let bot = self.clone();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let key = zobrist::hash(&target);
+        let handle = thread::spawn(move || bot.search(&target, thread_stop));
+        *self.ponder.lock().unwrap() = Some(Ponder{key: key, stop: stop, handle: handle});
+    }
+
+    // Stops a ponder search that was for some other position than this one.
+    fn stop_ponder(&self, game: Option<&Game>) {
+        let mut ponder = self.ponder.lock().unwrap();
+        let hit = match (ponder.as_ref(), game) {
+            (Some(p), Some(g)) => p.key == zobrist::hash(g),
+            _ => false,
+        };
+        if !hit {
+            if let Some(p) = ponder.take() {
+                // Not joined: it stops at its next node, and what it stored in the table is still right.
+                p.stop.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    // The move found by pondering, when the opponent played the expected reply.
+    fn ponder_result(&self, game: &Game) -> Option<Move> {
+        self.stop_ponder(Some(game));
+        let p = self.ponder.lock().unwrap().take()?;
+        return p.handle.join().ok()?;
+    }
+}
+
+impl Player for SearchBot {
+    fn reset(&mut self) {
+        self.stop_ponder(None);
+        self.tt.clear();
+    }
+
+    fn get_move(&self, game: &Game) -> Result<Move, String> {
+        return match self.choose_move(game) {
+            Some(m) => {
+                self.start_ponder(game, m);
+                Ok(m)
+            },
+            None => Err(String::from("No valid moves for SearchBot!")),
+        };
+    }
+
+    fn opponent_moved(&mut self, game: &Game) {
+        self.stop_ponder(Some(game));
+    }
+
+    // The book and endgame tables are left to whoever set the limits.
+    fn think(&self, game: &Game, limits: Limits, stop: Arc<AtomicBool>, report: Report) -> Result<Move, String> {
+        self.stop_ponder(None);
+        return match self.search_within(game, limits, stop, Some(report)) {
+            Some(m) => Ok(m),
+            None => Err(String::from("No valid moves for SearchBot!")),
+        };
+    }
+
+    fn is_bot(&self) -> bool {
+        return true;
+    }
+
+    // The level is always part of the name, since games are rated against each level as a
+    // separate opponent with its own anchor rating.
+    fn id_string(&self) -> String {
+        return format!("SearchBot, {}", level_name(self.options.level));
+    }
+
+    fn anchor_rating(&self) -> Option<f64> {
+        return Some(level_rating(self.options.level));
+    }
+
+    fn set_book(&mut self, book: Option<BookConfig>) {
+        self.book = book;
+    }
+
+    fn set_tablebase(&mut self, tablebase: Option<Arc<Tablebase>>) {
+        self.tablebase = tablebase;
+    }
+
+    fn set_bitbases(&mut self, bitbases: Option<Arc<Bitbases>>) {
+        self.bitbases = bitbases;
+    }
+}
+
+pub fn piece_value(kind: PieceKind) -> i32 {
+    return match kind {
+        PieceKind::None => 0,
+        PieceKind::Pawn => 100,
+        PieceKind::Knight => 320,
+        PieceKind::Bishop => 330,
+        PieceKind::Rook => 500,
+        PieceKind::Queen => 900,
+        PieceKind::King => 0,
+    };
+}
+
+fn is_capture(m: &Move) -> bool {
+    return m.takes || m.en_passant.is_some();
+}
+
+fn is_promotion(m: &Move) -> bool {
+    return m.promotion != PieceKind::None;
+}
+
+// Makes a move on a copy of the game and hands the turn over.
+fn child(game: &Game, m: Move) -> Game {
+    let mut next = game.clone();
+    next.do_move(m);
+    next.next_turn();
+    return next;
+}
+
+// Mate and tablebase scores count from the root, but the table keeps them counted from the position
+// itself so that they stay right when it is reached at another ply.
+fn is_mate_score(score: i32) -> bool {
+    return score.abs() >= TB_WIN - 2 * MAX_PLY as i32;
+}
+
+fn score_to_tt(score: i32, ply: u32) -> i32 {
+    if !is_mate_score(score) {
+        return score;
+    }
+    return if score > 0 { score + ply as i32 } else { score - ply as i32 };
+}
+
+fn score_from_tt(score: i32, ply: u32) -> i32 {
+    if !is_mate_score(score) {
+        return score;
+    }
+    return if score > 0 { score - ply as i32 } else { score + ply as i32 };
+}
+
+pub struct Search {
+    options: SearchOptions,
+    depth: u32,
+    weights: Arc<Weights>,
+    network: Option<Arc<Network>>,
+    accumulators: Vec<Accumulator>, // indexed by ply, only used with a network
+    tablebase: Option<Arc<Tablebase>>,
+    bitbases: Option<Arc<Bitbases>>,
+    tt: Arc<TranspositionTable>,
+    stop: Arc<AtomicBool>,
+    best: Option<Move>,
+    handicap: Option<Handicap>,
+    nodes: u64,
+    noise_seed: u64,
+    root_scores: Vec<(Move, i32)>, // every root move's score, only kept with a handicap
+    started: Instant,
+    deadline: Option<Instant>,
+    report: Option<Report>, // told about every finished depth
+}
+
+impl Search {
+    pub fn new(bot: &SearchBot, limits: Limits, stop: Arc<AtomicBool>) -> Self {
+        let handicap = Handicap::for_level(bot.options.level);
+        let depth = match limits.depth {
+            Some(d) => d.clamp(1, MAX_DEPTH),
+            None if limits.movetime.is_some() || limits.infinite => MAX_DEPTH,
+            None => bot.options.depth,
+        };
+        let started = Instant::now();
+        return Search{
+            options: bot.options,
+            depth: match handicap { Some(h) => h.depth.min(depth), None => depth },
+            weights: bot.weights.clone(),
+            network: if bot.options.nnue { bot.network.clone() } else { None },
+            accumulators: Vec::new(),
+            tablebase: bot.tablebase.clone(),
+            bitbases: bot.bitbases.clone(),
+            tt: bot.tt.clone(),
+            stop: stop,
+            best: None,
+            handicap: handicap,
+            nodes: 0,
+            noise_seed: rand::rng().random(),
+            root_scores: Vec::new(),
+            started: started,
+            deadline: limits.movetime.map(|t| started + t),
+            report: None,
+        };
+    }
+
+    // Running out of time stops the helpers too.
+    fn stopped(&self) -> bool {
+        if self.stop.load(Ordering::Relaxed) {
+            return true;
+        }
+        if self.deadline.is_some_and(|d| Instant::now() >= d) {
+            self.stop.store(true, Ordering::Relaxed);
+            return true;
+        }
+        return false;
+    }
+
+    // Only worth starting another depth while at most half of the time has gone.
+    fn time_for_another_depth(&self) -> bool {
+        return match self.deadline {
+            Some(d) => Instant::now() < self.started + (d - self.started) / 2,
+            None => true,
+        };
+    }
+
+    // The node budget only counts once a first iteration has given a move to fall back on.
+    fn out_of_nodes(&self) -> bool {
+        if self.best.is_none() {
+            return false;
+        }
+        return match self.handicap {
+            Some(h) => self.nodes >= h.nodes,
+            None => false,
+        };
+    }
+
+    // The static evaluation, plus the handicap's noise. The noise is fixed per position within one
+    // search so that the search still sees a consistent picture.
+    fn evaluate(&self, game: &Game, ply: u32) -> i32 {
+        let score = match &self.network {
+            Some(net) => net.evaluate(&self.accumulators[ply as usize], game.current_color()),
+            None => evaluate(game, &self.weights),
+        };
+        return match self.handicap {
+            Some(h) if h.noise > 0 => {
+                let mixed = (zobrist::hash(game) ^ self.noise_seed).wrapping_mul(0x9E3779B97F4A7C15) >> 32;
+                score + (mixed % (2 * h.noise as u64 + 1)) as i32 - h.noise
+            },
+            _ => score,
+        };
+    }
+
+    // Makes a move and brings the next ply's accumulator up to date.
+    fn make(&mut self, game: &Game, m: Move, ply: u32) -> Game {
+        let next = child(game, m);
+        if let Some(net) = &self.network {
+            let acc = next_accumulator(&mut self.accumulators, ply as usize);
+            net.update(acc, game, &next);
+        }
+        return next;
+    }
+
+    // A null move leaves the board, and so the accumulators, as they are.
+    fn make_null(&mut self, game: &Game, ply: u32) -> Game {
+        let mut next = game.clone();
+        next.do_null_move();
+        if self.network.is_some() {
+            next_accumulator(&mut self.accumulators, ply as usize);
+        }
+        return next;
+    }
+
+    // With a handicap, sometimes gives up the best move for the second or third best.
+    fn pick(&self, best: Move) -> Move {
+        let h = match self.handicap {
+            Some(h) => h,
+            None => return best,
+        };
+        let mut scores = self.root_scores.clone();
+        scores.sort_by(|a, b| b.1.cmp(&a.1));
+        let roll: f64 = rand::rng().random();
+        let choice = if roll < h.second_best / 2.0 { 2 } else if roll < h.second_best * 1.5 { 1 } else { 0 };
+        if choice == 0 || choice >= scores.len() || is_mate_score(scores[0].1) {
+            return best;
+        }
+        if scores[0].1 - scores[choice].1 > h.max_loss {
+            return best;
+        }
+        return scores[choice].0;
+    }
+
+    // Iterative deepening from depth 1 up to the configured depth. Returns the best move found.
+    pub fn iterate(&mut self, game: &Game) -> Option<Move> {
+        let moves = game.list_valid_moves();
+        if moves.len() == 0 {
+            return None;
+        }
+        if let Some(net) = &self.network {
+            self.accumulators = vec![net.refresh(game)];
+        }
+
+        let mut best = moves[0];
+        let mut score = 0;
+        let mut scores = Vec::new();
+        for depth in 1..=self.depth {
+            if self.stopped() || (depth > 1 && !self.time_for_another_depth()) {
+                break;
+            }
+            let (s, m) = if self.options.aspiration_windows && depth > 1 && self.handicap.is_none() {
+                self.aspiration(game, depth, score)
+            } else {
+                self.root(game, depth, -INFINITY, INFINITY)
+            };
+            // An iteration cut short by the node budget, the clock or a stop is not trusted.
+            if self.out_of_nodes() || (self.stopped() && self.best.is_some()) {
+                break;
+            }
+            score = s;
+            scores = self.root_scores.clone();
+            if let Some(mo) = m {
+                best = mo;
+                self.best = Some(mo);
+            }
+            if self.report.is_some() {
+                let thinking = Thinking{
+                    depth: depth,
+                    score: score,
+                    mate: if score.abs() >= MATE - 2 * MAX_PLY as i32 {
+                        Some(if score > 0 { (MATE - score + 1) / 2 } else { -(MATE + score) / 2 })
+                    } else {
+                        None
+                    },
+                    nodes: self.nodes,
+                    pv: self.principal_variation(game, best, depth),
+                };
+                if let Some(report) = &mut self.report {
+                    report(&thinking);
+                }
+            }
+        }
+        self.root_scores = scores;
+        return Some(self.pick(best));
+    }
+
+    // The best move followed by the best replies the table has stored, at most `depth` moves.
+    fn principal_variation(&self, game: &Game, best: Move, depth: u32) -> Vec<Move> {
+        let mut pv = vec![best];
+        let mut pos = child(game, best);
+        while pv.len() < depth as usize {
+            let m = match self.tt.probe(zobrist::hash(&pos)).and_then(|e| decode_move(&pos, e.mv)) {
+                Some(m) => m,
+                None => break,
+            };
+            pv.push(m);
+            pos = child(&pos, m);
+        }
+        return pv;
+    }
+
+    // Searches a narrow window around the previous iteration's score, widening on a fail.
+    fn aspiration(&mut self, game: &Game, depth: u32, guess: i32) -> (i32, Option<Move>) {
+        let mut delta = ASPIRATION_WINDOW;
+        let mut alpha = guess - delta;
+        let mut beta = guess + delta;
+        loop {
+            let (score, m) = self.root(game, depth, alpha, beta);
+            if score <= alpha {
+                alpha = (alpha - delta).max(-INFINITY);
+            } else if score >= beta {
+                beta = (beta + delta).min(INFINITY);
+            } else {
+                return (score, m);
+            }
+            delta *= 2;
+            if delta > 4 * ASPIRATION_WINDOW {
+                alpha = -INFINITY;
+                beta = INFINITY;
+            }
+        }
+    }
+
+    fn root(&mut self, game: &Game, depth: u32, mut alpha: i32, beta: i32) -> (i32, Option<Move>) {
+        let hint = self.best.map(|m| encode_move(&m, game.current_color()));
+        let moves = self.order_moves(game, game.list_valid_moves(), hint);
+        let in_check = game.is_check();
+        let mut best: Option<Move> = None;
+        let mut best_score = -INFINITY;
+        self.root_scores.clear();
+
+        for (i, m) in moves.iter().enumerate() {
+            let next = self.make(game, *m, 0);
+            // A handicapped search needs the real score of every move to choose a weaker one.
+            let score = if self.handicap.is_some() {
+                -self.negamax(&next, depth - 1, -INFINITY, INFINITY, 1, true)
+            } else {
+                let reduction = self.reduction(depth, i, in_check, &next, m);
+                self.search_child(&next, depth, reduction, i == 0, (alpha, beta), 1)
+            };
+            if self.handicap.is_some() {
+                self.root_scores.push((*m, score));
+            }
+            if score > best_score {
+                best_score = score;
+                best = Some(*m);
+            }
+            if score > alpha {
+                alpha = score;
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+        return (best_score, best);
+    }
+
+    // Late move reductions: quiet moves far down the ordered list are searched one ply shallower.
+    fn reduction(&self, depth: u32, index: usize, in_check: bool, next: &Game, m: &Move) -> u32 {
+        if !self.options.late_move_reductions || depth < LMR_MIN_DEPTH || index < LMR_MIN_MOVES {
+            return 0;
+        }
+        if in_check || is_capture(m) || is_promotion(m) || next.is_check() {
+            return 0;
+        }
+        return 1;
+    }
+
+    // Searches one child of a node. With PVS enabled, every move after the first is searched
+    // with a null window and only re-searched in full if it might improve on alpha.
+    fn search_child(&mut self, next: &Game, depth: u32, reduction: u32, first: bool, window: (i32, i32), ply: u32) -> i32 {
+        let (alpha, beta) = window;
+        if first || !self.options.pvs {
+            let mut score = -self.negamax(next, depth - 1 - reduction, -beta, -alpha, ply, true);
+            if reduction > 0 && score > alpha {
+                score = -self.negamax(next, depth - 1, -beta, -alpha, ply, true);
+            }
+            return score;
+        }
+
+        let mut score = -self.negamax(next, depth - 1 - reduction, -alpha - 1, -alpha, ply, true);
+        if score > alpha && reduction > 0 {
+            score = -self.negamax(next, depth - 1, -alpha - 1, -alpha, ply, true);
+        }
+        if score > alpha && score < beta {
+            score = -self.negamax(next, depth - 1, -beta, -alpha, ply, true);
+        }
+        return score;
+    }
+
+    fn negamax(&mut self, game: &Game, mut depth: u32, mut alpha: i32, beta: i32, ply: u32, allow_null: bool) -> i32 {
+        // A helper's result is thrown away once the main thread is done, so any score will do.
+        // Likewise for an iteration that runs out of nodes.
+        self.nodes += 1;
+        if self.stopped() || self.out_of_nodes() {
+            return 0;
+        }
+        if let Some(tb) = &self.tablebase {
+            if let Some(wdl) = tb.probe_wdl(game) {
+                return if wdl > 0 { TB_WIN - ply as i32 } else if wdl < 0 { -TB_WIN + ply as i32 } else { 0 };
+            }
+        }
+        // Bitbases know the distance to mate, so they score like a mate found by the search.
+        if let Some(bb) = &self.bitbases {
+            if let Some((wdl, dtm)) = bb.probe(game) {
+                let plies = (ply + dtm) as i32;
+                return if wdl > 0 { MATE - plies } else if wdl < 0 { -MATE + plies } else { 0 };
+            }
+        }
+        let in_check = game.is_check();
+        // Extensions are capped so that a string of checks cannot run the search away.
+        if in_check && self.options.check_extensions && ply < 2 * self.depth {
+            depth += 1;
+        }
+
+        if depth == 0 || ply >= MAX_PLY {
+            return self.quiesce(game, alpha, beta, ply);
+        }
+
+        let key = zobrist::hash(game);
+        let mut hint = None;
+        if let Some(entry) = self.tt.probe(key) {
+            hint = Some(entry.mv);
+            let score = score_from_tt(entry.score, ply);
+            if entry.depth >= depth && match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => score >= beta,
+                Bound::Upper => score <= alpha,
+            } {
+                return score;
+            }
+        }
+
+        // Null move pruning. Skipped when only pawns remain, where zugzwang is common.
+        if self.options.null_move && allow_null && !in_check && depth > NULL_MOVE_REDUCTION
+            && game.has_non_pawn_material(game.current_color()) && self.evaluate(game, ply) >= beta
+        {
+            let next = self.make_null(game, ply);
+            let score = -self.negamax(&next, depth - 1 - NULL_MOVE_REDUCTION, -beta, -beta + 1, ply + 1, false);
+            if score >= beta {
+                return beta;
+            }
+        }
+
+        let moves = self.order_moves(game, game.list_valid_moves(), hint);
+        if moves.len() == 0 {
+            return if in_check { -MATE + ply as i32 } else { 0 };
+        }
+
+        let original_alpha = alpha;
+        let mut best = -INFINITY;
+        let mut best_move = moves[0];
+        for (i, m) in moves.iter().enumerate() {
+            let next = self.make(game, *m, ply);
+            let reduction = self.reduction(depth, i, in_check, &next, m);
+            let score = self.search_child(&next, depth, reduction, i == 0, (alpha, beta), ply + 1);
+            if score > best {
+                best = score;
+                best_move = *m;
+            }
+            if score > alpha {
+                alpha = score;
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        if !self.stopped() && !self.out_of_nodes() {
+            let bound = if best >= beta { Bound::Lower } else if best <= original_alpha { Bound::Upper } else { Bound::Exact };
+            let mv = encode_move(&best_move, game.current_color());
+            self.tt.store(key, TtEntry{score: score_to_tt(best, ply), depth: depth, bound: bound, mv: mv});
+        }
+        return best;
+    }
+
+    // Only captures are searched so that the static evaluation is not taken mid-exchange.
+    fn quiesce(&mut self, game: &Game, mut alpha: i32, beta: i32, ply: u32) -> i32 {
+        self.nodes += 1;
+        let stand_pat = self.evaluate(game, ply);
+        if stand_pat >= beta {
+            return beta;
+        }
+        if stand_pat > alpha {
+            alpha = stand_pat;
+        }
+
+        let captures: Vec<Move> = game.list_valid_moves().into_iter().filter(is_capture).collect();
+        for m in self.order_moves(game, captures, None) {
+            let next = self.make(game, m, ply);
+            let score = -self.quiesce(&next, -beta, -alpha, ply + 1);
+            if score >= beta {
+                return beta;
+            }
+            if score > alpha {
+                alpha = score;
+            }
+        }
+        return alpha;
+    }
+
+    // The hint (the previous or stored best move, in the Polyglot move format) goes first, then
+    // captures by most valuable victim / least valuable attacker.
+    fn order_moves(&self, game: &Game, mut moves: Vec<Move>, hint: Option<u16>) -> Vec<Move> {
+        moves.sort_by_key(|m| {
+            if let Some(h) = hint {
+                if encode_move(m, game.current_color()) == h {
+                    return -INFINITY;
+                }
+            }
+            if is_capture(m) || is_promotion(m) {
+                return -(10 * piece_value(victim(game, m)) - piece_value(m.piece.kind) + piece_value(m.promotion));
+            }
+            return 0;
+        });
+        return moves;
+    }
+}
+
+// Copies the accumulator of a ply into the next one, which is returned.
+fn next_accumulator(accumulators: &mut Vec<Accumulator>, ply: usize) -> &mut Accumulator {
+    if accumulators.len() <= ply + 1 {
+        let acc = accumulators[ply].clone();
+        accumulators.push(acc);
+    } else {
+        let (done, rest) = accumulators.split_at_mut(ply + 1);
+        rest[0].copy_from(&done[ply]);
+    }
+    return &mut accumulators[ply + 1];
+}
+
+fn victim(game: &Game, m: &Move) -> PieceKind {
+    if m.en_passant.is_some() {
+        return PieceKind::Pawn;
+    }
+    if !m.takes {
+        return PieceKind::None;
+    }
+    return game.piece_at(m.dest.0.index().unwrap(), m.dest.1.index().unwrap()).kind;
+}
@@ -0,0 +1,103 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// A transposition table that search threads share without locking. Each slot holds the position
+// key XORed with the entry's data next to the data itself. When two threads write the same slot at
+// once the halves can come from different writes, but then the key no longer checks out and the
+// slot just reads as empty.
+//
+// Data layout: score (bits 0-31), depth (32-39), bound (40-41), best move in the Polyglot move
+// format (42-57).
+
+pub const DEFAULT_SIZE: usize = 1 << 20; // slots, 16 bytes each
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Bound {
+    Exact,
+    Lower, // the score is at least this, the search failed high
+    Upper, // the score is at most this, no move raised alpha
+}
+
+#[derive(Copy, Clone)]
+pub struct TtEntry {
+    pub score: i32,
+    pub depth: u32,
+    pub bound: Bound,
+    pub mv: u16,
+}
+
+impl TtEntry {
+    fn pack(&self) -> u64 {
+        let bound = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+        return self.score as u32 as u64 | (self.depth.min(255) as u64) << 32 | bound << 40 | (self.mv as u64) << 42;
+    }
+
+    fn unpack(data: u64) -> Self {
+        return TtEntry {
+            score: data as u32 as i32,
+            depth: ((data >> 32) & 0xFF) as u32,
+            bound: match (data >> 40) & 3 {
+                0 => Bound::Exact,
+                1 => Bound::Lower,
+                _ => Bound::Upper,
+            },
+            mv: (data >> 42) as u16,
+        };
+    }
+}
+
+struct Slot {
+    check: AtomicU64, // key ^ data
+    data: AtomicU64,
+}
+
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+}
+
+impl TranspositionTable {
+    // The size is rounded up to a power of two.
+    pub fn new(size: usize) -> Self {
+        let mut slots = Vec::with_capacity(size.next_power_of_two());
+        for _ in 0..size.next_power_of_two() {
+            slots.push(Slot{check: AtomicU64::new(0), data: AtomicU64::new(0)});
+        }
+        return TranspositionTable{slots: slots};
+    }
+
+    fn slot(&self, key: u64) -> &Slot {
+        return &self.slots[key as usize & (self.slots.len() - 1)];
+    }
+
+    pub fn probe(&self, key: u64) -> Option<TtEntry> {
+        let slot = self.slot(key);
+        let data = slot.data.load(Ordering::Relaxed);
+        if slot.check.load(Ordering::Relaxed) ^ data != key || data == 0 {
+            return None;
+        }
+        return Some(TtEntry::unpack(data));
+    }
+
+    // Always replaces, except that a deeper result for the same position is kept.
+    pub fn store(&self, key: u64, entry: TtEntry) {
+        if let Some(old) = self.probe(key) {
+            if old.depth > entry.depth && entry.bound != Bound::Exact {
+                return;
+            }
+        }
+        let slot = self.slot(key);
+        let data = entry.pack();
+        slot.data.store(data, Ordering::Relaxed);
+        slot.check.store(key ^ data, Ordering::Relaxed);
+    }
+
+    pub fn clear(&self) {
+        for slot in &self.slots {
+            slot.data.store(0, Ordering::Relaxed);
+            slot.check.store(0, Ordering::Relaxed);
+        }
+    }
+}
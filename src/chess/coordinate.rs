@@ -0,0 +1,62 @@
+use crate::chess::game::Game;
+use crate::chess::piece::PieceKind;
+use crate::chess::r#move::Move;
+use crate::chess::rankfile::{back_rank_index, tuple_to_square};
+
+// Coordinate notation, as the engine protocols use it: the origin and destination squares and a
+// promotion letter, such as e2e4 or e7e8q. Castling is the king's move, e1g1.
+
+pub fn to_coordinate(game: &Game, m: &Move) -> String {
+    if m.castle || m.long_castle {
+        let rank = back_rank_index(game.current_color()) + 1;
+        return format!("e{}{}{}", rank, if m.castle { "g" } else { "c" }, rank);
+    }
+    let promotion = if m.promotion != PieceKind::None { m.promotion.to_letter().to_lowercase() } else { String::new() };
+    return format!("{}{}{}", tuple_to_square(m.origin), tuple_to_square(m.dest), promotion);
+}
+
+// Finds the legal move written in coordinate notation. A pawn reaching the last rank needs a
+// promotion letter, and no other move may have one. The move is checked through
+// Game::disambiguate, the same as a typed move.
+pub fn resolve_coordinate(game: &Game, text: &str) -> Result<Move, String> {
+    if !text.is_ascii() {
+        return Err(format!("Not a move: {}", text));
+    }
+    let promotion = match text.len() {
+        4 => PieceKind::None,
+        5 => match &text[4..] {
+            "q" => PieceKind::Queen,
+            "r" => PieceKind::Rook,
+            "b" => PieceKind::Bishop,
+            "n" => PieceKind::Knight,
+            _ => return Err(format!("Bad promotion: {}", text)),
+        },
+        _ => return Err(format!("Not a move: {}", text)),
+    };
+    for mut m in game.list_valid_moves() {
+        if to_coordinate(game, &m)[..4] != text[..4] {
+            continue;
+        }
+        // Only queen promotions are generated, the others are the same move with a different piece.
+        if m.promotion != PieceKind::None && promotion == PieceKind::None {
+            return Err(format!("Missing promotion: {}", text));
+        }
+        if m.promotion == PieceKind::None && promotion != PieceKind::None {
+            return Err(format!("Not a promotion: {}", text));
+        }
+        m.promotion = promotion;
+        if let Some(e) = game.disambiguate(&mut m) {
+            return Err(format!("Illegal move: {} ({})", text, e));
+        }
+        return Ok(m);
+    }
+    return Err(format!("Illegal move: {}", text));
+}
+
+// Plays a move in coordinate notation on the game and hands the turn over.
+pub fn play_coordinate(game: &mut Game, text: &str) -> Result<Move, String> {
+    let m = resolve_coordinate(game, text)?;
+    game.do_move(m);
+    game.next_turn();
+    return Ok(m);
+}
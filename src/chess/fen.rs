@@ -0,0 +1,136 @@
+use crate::chess::color::Color;
+use crate::chess::game::Game;
+use crate::chess::piece::{Piece, PieceKind};
+
+// Forsyth-Edwards Notation. Game keeps castling rights as "has not moved" flags on the king and
+// rooks, and en passant as the last move of the history, so reading a FEN sets those up to match.
+// The halfmove clock and move number are accepted but Game has no use for them.
+
+fn kind_from_letter(c: char) -> Option<PieceKind> {
+    return match c.to_ascii_lowercase() {
+        'p' => Some(PieceKind::Pawn),
+        'n' => Some(PieceKind::Knight),
+        'b' => Some(PieceKind::Bishop),
+        'r' => Some(PieceKind::Rook),
+        'q' => Some(PieceKind::Queen),
+        'k' => Some(PieceKind::King),
+        _ => None,
+    };
+}
+
+// Reads the first four fields of a FEN (placement, side to move, castling, en passant); any
+// further fields are ignored.
+pub fn parse_fen(fen: &str) -> Result<Game, String> {
+    let fields: Vec<&str> = fen.split_whitespace().collect();
+    if fields.len() < 4 {
+        return Err(format!("Not a FEN: {}", fen));
+    }
+    let bad = |what: &str| format!("Bad {} in FEN: {}", what, fen);
+
+    let rows: Vec<&str> = fields[0].split('/').collect();
+    if rows.len() != 8 {
+        return Err(bad("placement"));
+    }
+    let castling = fields[2];
+    if castling != "-" && !castling.chars().all(|c| "KQkq".contains(c)) {
+        return Err(bad("castling"));
+    }
+    let mut pieces = Vec::new();
+    for (i, row) in rows.iter().enumerate() {
+        let r = 7 - i;
+        let mut f = 0;
+        for c in row.chars() {
+            if let Some(n) = c.to_digit(10) {
+                f += n as usize;
+                continue;
+            }
+            let kind = match kind_from_letter(c) {
+                Some(k) if f < 8 => k,
+                _ => return Err(bad("placement")),
+            };
+            let color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+            let back = if color == Color::White { 0 } else { 7 };
+            let (short, long) = if color == Color::White { ('K', 'Q') } else { ('k', 'q') };
+            let unmoved = match kind {
+                PieceKind::Pawn => r == if color == Color::White { 1 } else { 6 },
+                PieceKind::King => r == back && f == 4 && (castling.contains(short) || castling.contains(long)),
+                PieceKind::Rook => r == back && ((f == 7 && castling.contains(short)) || (f == 0 && castling.contains(long))),
+                _ => false,
+            };
+            pieces.push((f, r, Piece::make(kind, color, !unmoved, 0)));
+            f += 1;
+        }
+        if f != 8 {
+            return Err(bad("placement"));
+        }
+    }
+
+    let to_move = match fields[1] {
+        "w" => Color::White,
+        "b" => Color::Black,
+        _ => return Err(bad("side to move")),
+    };
+    let mut game = Game::new();
+    game.set_position(&pieces, to_move);
+
+    if fields[3] != "-" {
+        let square: Vec<char> = fields[3].chars().collect();
+        if square.len() != 2 || !('a'..='h').contains(&square[0]) || square[1] != if to_move == Color::White { '6' } else { '3' } {
+            return Err(bad("en passant square"));
+        }
+        game.set_en_passant(square[0] as usize - 'a' as usize);
+    }
+    return Ok(game);
+}
+
+// The FEN of a position. The halfmove clock and move number are counted from the game's history,
+// so they start from zero and one for a position that was set up.
+pub fn to_fen(game: &Game) -> String {
+    let mut placement = String::new();
+    for r in (0..8).rev() {
+        let mut empty = 0;
+        for f in 0..8 {
+            let p = game.piece_at(f, r);
+            if p.kind == PieceKind::None {
+                empty += 1;
+                continue;
+            }
+            if empty > 0 {
+                placement.push_str(&empty.to_string());
+                empty = 0;
+            }
+            let letter = match p.kind {
+                PieceKind::Pawn => 'p',
+                PieceKind::Knight => 'n',
+                PieceKind::Bishop => 'b',
+                PieceKind::Rook => 'r',
+                PieceKind::Queen => 'q',
+                _ => 'k',
+            };
+            placement.push(if p.color == Color::White { letter.to_ascii_uppercase() } else { letter });
+        }
+        if empty > 0 {
+            placement.push_str(&empty.to_string());
+        }
+        if r > 0 {
+            placement.push('/');
+        }
+    }
+
+    let mut castling: String = game.castle_rights().iter().zip("KQkq".chars()).filter(|(right, _)| **right).map(|(_, c)| c).collect();
+    if castling.is_empty() {
+        castling = String::from("-");
+    }
+    // Like Polyglot, the en passant square is only given when a capture there is possible.
+    let to_move = game.current_color();
+    let en_passant = match game.en_passant_file() {
+        Some(f) if game.list_valid_moves().iter().any(|m| m.en_passant.is_some()) => {
+            format!("{}{}", (b'a' + f as u8) as char, if to_move == Color::White { 6 } else { 3 })
+        },
+        _ => String::from("-"),
+    };
+    let history = game.history();
+    let halfmove = history.iter().rev().take_while(|m| !m.takes && m.en_passant.is_none() && m.piece.kind != PieceKind::Pawn).count();
+    let fullmove = history.len() / 2 + 1;
+    return format!("{} {} {} {} {} {}", placement, if to_move == Color::White { "w" } else { "b" }, castling, en_passant, halfmove, fullmove);
+}
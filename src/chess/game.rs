@@ -1,5 +1,7 @@
 use std::process::Command;
 use std::io::{Write, stdout};
+use std::sync::Arc;
+use std::time::Duration;
 use rand::Rng;
 
 use crate::chess::color::Color;
@@ -9,6 +11,11 @@ use crate::chess::player::*;
 use crate::chess::rankfile::*;
 use crate::chess::setup::Setup;
 use crate::chess::strings::*;
+use crate::book::BookConfig;
+use crate::database::explorer::Candidate;
+use crate::eco;
+use crate::eval::{Breakdown, TERMS};
+use crate::tablebase::Tablebase;
 
 use crate::bots::human::Human;
 
@@ -16,6 +23,7 @@ use crate::bots::human::Human;
 enum GameMode {
     AgainstHumanLocal,
     AgainstBotLocal,
+    BotVsBot, // the user only watches
 }
 
 #[derive(Copy, Clone, PartialEq)]
@@ -83,6 +91,9 @@ pub struct Game {
     white_cap: Vec<Piece>,
     black_cap: Vec<Piece>,
     error: String,
+    panel: Vec<String>,
+    annotation: String, // shown next to the side to move
+    opening: String, // the ECO code and name, shown over the history
     orientation: Color,
     do_flip: bool,
     game_mode: GameMode,
@@ -115,6 +126,9 @@ impl Game {
             white_cap: Vec::new(),
             black_cap: Vec::new(),
             error: String::new(),
+            panel: Vec::new(),
+            annotation: String::new(),
+            opening: String::new(),
             orientation: Color::White,
             do_flip: false,
             game_mode: GameMode::AgainstHumanLocal,
@@ -189,15 +203,27 @@ impl Game {
             self.orientation = self.player_one.color();
             // Flipping while playing against a bot is silly.
             self.do_flip = false;
+        } else if self.game_mode == GameMode::BotVsBot {
+            // Player one is the white bot, as picked in Setup.
+            self.orientation = Color::White;
+            self.do_flip = false;
         }
     }
 
+    // Shows the board as in a game without starting one, e.g. to step through a saved game.
+    pub fn show_position(&mut self) {
+        self.print_mode = PrintMode::Game;
+    }
+
     pub fn start_setup(&mut self) {
         self.print_mode = PrintMode::Setup;
     }
 
     pub fn clear(&mut self) {
         self.history.clear();
+        self.panel.clear();
+        self.annotation.clear();
+        self.opening.clear();
         self.white_cap.clear();
         self.black_cap.clear();
         self.board = Board::new();
@@ -210,6 +236,29 @@ impl Game {
         self.player_two.color = Color::Black;
     }
 
+    // Sets up an arbitrary position, e.g. for endgame analysis. Pieces are given as (file, rank, piece);
+    // whether a piece has moved decides castling and pawn double moves.
+    pub fn set_position(&mut self, pieces: &[(usize, usize, Piece)], to_move: Color) {
+        self.history.clear();
+        self.white_cap.clear();
+        self.black_cap.clear();
+        self.board = Board::new();
+        for (f, r, p) in pieces {
+            self.board.0[*f][*r] = *p;
+        }
+        self.to_move = to_move;
+    }
+
+    // Records that the pawn on the given file just made a double move, so that it can be taken en
+    // passant. Used after set_position, which starts without any history.
+    pub fn set_en_passant(&mut self, file: usize) {
+        let color = if self.to_move == Color::White { Color::Black } else { Color::White };
+        let (from, to) = if color == Color::White { (1, 3) } else { (6, 4) };
+        let mut m = Move::basic(self.board.0[file][to], (File::from_index(file), Rank::from_index(from)), (File::from_index(file), Rank::from_index(to)));
+        m.pawn_double = true;
+        self.history.push(m);
+    }
+
     pub fn toggle_flip(&mut self) -> bool {
         self.do_flip = !self.do_flip;
         return self.do_flip;
@@ -231,12 +280,27 @@ impl Game {
         return if color == self.player_one.color() { &self.player_one.player } else { &self.player_two.player };
     }
 
+    pub fn player_one(&self) -> &Box<dyn Player> {
+        return &self.player_one.player;
+    }
+
+    pub fn player_two(&self) -> &Box<dyn Player> {
+        return &self.player_two.player;
+    }
+
     pub fn current_color(&self) -> Color {
         return self.to_move;
     }
 
+    pub fn is_spectating(&self) -> bool {
+        return self.game_mode == GameMode::BotVsBot;
+    }
+
     fn update_game_mode(&mut self) {
-        if self.player_two.player.is_bot() && self.player_one.player.is_human() {
+        if self.player_two.player.is_bot() && self.player_one.player.is_bot() {
+            self.game_mode = GameMode::BotVsBot;
+        }
+        else if self.player_two.player.is_bot() && self.player_one.player.is_human() {
             self.game_mode = GameMode::AgainstBotLocal;
         }
         else if self.player_two.player.is_human() && self.player_one.player.is_human() {
@@ -254,6 +318,27 @@ impl Game {
         self.update_game_mode();
     }
 
+    pub fn set_book(&mut self, book: Option<BookConfig>) {
+        self.player_one.player.set_book(book.clone());
+        self.player_two.player.set_book(book);
+    }
+
+    pub fn set_tablebase(&mut self, tablebase: Option<Arc<Tablebase>>) {
+        self.player_one.player.set_tablebase(tablebase.clone());
+        self.player_two.player.set_tablebase(tablebase);
+    }
+
+    // Lets the player to move know what the opponent just did, e.g. so that a pondering bot can
+    // check its guess while a human is still typing.
+    pub fn notify_player_to_move(&mut self) {
+        let snapshot = self.clone();
+        if self.to_move == self.player_one.color() {
+            self.player_one.player.opponent_moved(&snapshot);
+        } else {
+            self.player_two.player.opponent_moved(&snapshot);
+        }
+    }
+
     pub fn set_player_one_color(&mut self, color: Color) {
         self.player_one.color = color;
     }
@@ -262,6 +347,55 @@ impl Game {
         self.player_two.color = color;
     }
 
+    // ================
+    // Board Queries
+    // ================
+
+    pub fn piece_at(&self, file: usize, rank: usize) -> Piece {
+        return self.board.0[file][rank];
+    }
+
+    pub fn history(&self) -> &Vec<Move> {
+        return &self.history;
+    }
+
+    // Castling rights in the order white short, white long, black short, black long. A right is
+    // kept as long as the king and that rook have not moved from their starting squares.
+    pub fn castle_rights(&self) -> [bool; 4] {
+        let mut rights = [false; 4];
+        for (i, (color, long)) in [(Color::White, false), (Color::White, true), (Color::Black, false), (Color::Black, true)].iter().enumerate() {
+            let back = back_rank_index(*color);
+            let king = self.board.0[4][back];
+            let rook = self.board.0[rook_castle_file(*long)][back];
+            rights[i] = king.kind == PieceKind::King && king.color == *color && !king.has_moved
+                && rook.kind == PieceKind::Rook && rook.color == *color && !rook.has_moved;
+        }
+        return rights;
+    }
+
+    // The file of a pawn that just made a double move, if any.
+    pub fn en_passant_file(&self) -> Option<usize> {
+        if let Some(m) = self.history.last() {
+            if m.piece.kind == PieceKind::Pawn && m.pawn_double {
+                return m.dest.0.index();
+            }
+        }
+        return None;
+    }
+
+    // True if the given color has anything besides its king and pawns left on the board.
+    pub fn has_non_pawn_material(&self, color: Color) -> bool {
+        for f in 0..8 {
+            for r in 0..8 {
+                let p = self.board.0[f][r];
+                if p.color == color && p.kind != PieceKind::None && p.kind != PieceKind::Pawn && p.kind != PieceKind::King {
+                    return true;
+                }
+            }
+        }
+        return false;
+    }
+
     // ================
     // UI Code
     // ================
@@ -311,13 +445,15 @@ impl Game {
         r = if self.orientation == Color::White { 3 } else { 4 };
         println!("{: >5} {}{: >2}{}", r + 1, self.print_rank(r), "", TITLE5);
         r = if self.orientation == Color::White { 2 } else { 5 };
-        println!("{: >5} {}{: >2}", r + 1, self.print_rank(r), "",);
-        r = if self.orientation == Color::White { 1 } else { 6 };
         println!("{: >5} {}{: >7}1. Play ({})", r + 1, self.print_rank(r), "", self.game_mode_string());
-        r = if self.orientation == Color::White { 0 } else { 7 };
+        r = if self.orientation == Color::White { 1 } else { 6 };
         println!("{: >5} {}{: >7}2. Setup", r + 1, self.print_rank(r), "");
-        println!("{: >5} {}{: >7}3. Exit", "", self.print_rank_label(), "");
-        println!("");
+        r = if self.orientation == Color::White { 0 } else { 7 };
+        println!("{: >5} {}{: >7}3. Tournament", r + 1, self.print_rank(r), "");
+        println!("{: >5} {}{: >7}4. Stats", "", self.print_rank_label(), "");
+        println!("{: >29}5. Archive", "");
+        println!("{: >29}6. Database", "");
+        println!("{: >29}7. Exit", "");
         print!("{: >29}> ", "");
         let _ = stdout().flush().unwrap();
     }
@@ -339,7 +475,11 @@ impl Game {
         println!("{: >5} {}{: >7}{}", r + 1, self.print_rank(r), "", config.current().print_entry(4));
         r = if self.orientation == Color::White { 0 } else { 7 };
         println!("{: >5} {}{: >7}{}", r + 1, self.print_rank(r), "", config.current().print_entry(5));
-        println!("{: >5} {}{: >7}{}", "", self.print_rank_label(), "", "");
+        println!("{: >5} {}{: >7}{}", "", self.print_rank_label(), "", config.current().print_entry(6));
+        // Longer menus continue below the board.
+        for i in 7..config.current().len() {
+            println!("{: >29}{}", "", config.current().print_entry(i));
+        }
         println!("{: >29}{}", "", config.confirm_string());
         print!("{: >29}> ", "");
         let _ = stdout().flush().unwrap();
@@ -351,7 +491,7 @@ impl Game {
         println!("  \x1b[41m{}\x1b[0m", self.error);
 
         // Print color based on turn
-        println!("{: >6}\u{250c}\u{2500} {} to move {:\u{2500}>12}\u{2510}", "", self.to_move.to_string(), "");
+        println!("{: >6}\u{250c}\u{2500} {} to move {:\u{2500}>12}\u{2510}  {}", "", self.to_move.to_string(), "", self.annotation);
         print!("{: >6}\u{2514} ", "");
         let _ = stdout().flush().unwrap();
     }
@@ -359,6 +499,11 @@ impl Game {
     fn print_checkmate(&self) {
         self.print_active_board();
 
+        // Why a bot forfeited, when it did.
+        if !self.error.is_empty() {
+            println!("  \x1b[41m{}\x1b[0m", self.error);
+        }
+
         println!("{: >7}\u{250c}{:\u{2500}>12}\u{2510}", "", "");
         println!("{: >7}\u{2502} {} Wins \u{2502}", "", if self.to_move == Color::White { "Black" } else { "White" });
         println!("{: >7}\u{2514}{:\u{2500}>12}\u{2518}", "", "");
@@ -375,22 +520,143 @@ impl Game {
 
     fn print_active_board(&self) {
         let mut r = if self.orientation == Color::White { 7 } else { 0 };
-        println!("{: >5} {}{: >3}\u{250c}{:\u{2500}>30}\u{2510}", r + 1, self.print_rank(r), "", "");
+        println!("{: >5} {}{: >3}\u{250c}{}\u{2510}{}", r + 1, self.print_rank(r), "", self.print_history_title(), self.panel_line(0));
         r = if self.orientation == Color::White { 6 } else { 1 };
-        println!("{: >5} {}{: >3}\u{2502}{:<30}\u{2502}", r + 1, self.print_rank(r), "", self.print_notation_history(0));
+        println!("{: >5} {}{: >3}\u{2502}{:<30}\u{2502}{}", r + 1, self.print_rank(r), "", self.print_notation_history(0), self.panel_line(1));
         r = if self.orientation == Color::White { 5 } else { 2 };
-        println!("{: >5} {}{: >3}\u{2502}{:<30}\u{2502}", r + 1, self.print_rank(r), "", self.print_notation_history(1));
+        println!("{: >5} {}{: >3}\u{2502}{:<30}\u{2502}{}", r + 1, self.print_rank(r), "", self.print_notation_history(1), self.panel_line(2));
         r = if self.orientation == Color::White { 4 } else { 3 };
-        println!("{: >5} {}{: >3}\u{2502}{:<30}\u{2502}", r + 1, self.print_rank(r), "", self.print_notation_history(2));
+        println!("{: >5} {}{: >3}\u{2502}{:<30}\u{2502}{}", r + 1, self.print_rank(r), "", self.print_notation_history(2), self.panel_line(3));
         r = if self.orientation == Color::White { 3 } else { 4 };
-        println!("{: >5} {}{: >3}\u{2502}{:<30}\u{2502}", r + 1, self.print_rank(r), "", self.print_notation_history(3));
+        println!("{: >5} {}{: >3}\u{2502}{:<30}\u{2502}{}", r + 1, self.print_rank(r), "", self.print_notation_history(3), self.panel_line(4));
         r = if self.orientation == Color::White { 2 } else { 5 };
-        println!("{: >5} {}{: >3}\u{2514}{:\u{2500}>30}\u{2518}", r + 1, self.print_rank(r), "", "");
+        println!("{: >5} {}{: >3}\u{2514}{:\u{2500}>30}\u{2518}{}", r + 1, self.print_rank(r), "", "", self.panel_line(5));
         r = if self.orientation == Color::White { 1 } else { 6 };
-        println!("{: >5} {}{: >3}\x1b[47m{}\x1b[0m", r + 1, self.print_rank(r), "", self.cap_string(Color::Black));
+        println!("{: >5} {}{: >3}\x1b[47m{}\x1b[0m{: <w$}{}", r + 1, self.print_rank(r), "", self.cap_string(Color::Black), "", self.panel_line(6), w = self.cap_padding(Color::Black));
         r = if self.orientation == Color::White { 0 } else { 7 };
-        println!("{: >5} {}{: >3}\x1b[47m{}\x1b[0m", r + 1, self.print_rank(r), "", self.cap_string(Color::White));
-        println!("{: >5} {}", "", self.print_rank_label());
+        println!("{: >5} {}{: >3}\x1b[47m{}\x1b[0m{: <w$}{}", r + 1, self.print_rank(r), "", self.cap_string(Color::White), "", self.panel_line(7), w = self.cap_padding(Color::White));
+        println!("{: >5} {}{: >35}{}", "", self.print_rank_label(), "", self.panel_line(8));
+    }
+
+    // The top of the history box, with the opening's name in it once the game has one.
+    fn print_history_title(&self) -> String {
+        if self.opening.is_empty() {
+            return format!("{:\u{2500}>30}", "");
+        }
+        let name: String = self.opening.chars().take(27).collect();
+        return format!("\u{2500} {} {:\u{2500}>w$}", name, "", w = 27 - name.chars().count());
+    }
+
+    // Lines of the side panel drawn to the right of the history box. Empty when there is no panel.
+    fn panel_line(&self, row: usize) -> String {
+        if row >= self.panel.len() {
+            return "".to_string();
+        }
+        return format!("  {}", self.panel[row]);
+    }
+
+    // Pads the captured pieces out to the width of the history box so that the panel lines up.
+    fn cap_padding(&self, c: Color) -> usize {
+        let count = if c == Color::White { self.white_cap.len() } else { self.black_cap.len() };
+        return if count * 2 < 32 { 32 - count * 2 } else { 0 };
+    }
+
+    pub fn set_panel(&mut self, lines: Vec<String>) {
+        self.panel = lines;
+    }
+
+    pub fn clear_panel(&mut self) {
+        self.panel.clear();
+    }
+
+    // Names the opening after the current position when the ECO table has it, keeping the name
+    // from earlier in the game otherwise.
+    pub fn update_opening(&mut self) {
+        if let Some(o) = eco::lookup(self) {
+            self.opening = o.to_string();
+        }
+    }
+
+    pub fn set_annotation(&mut self, a: String) {
+        self.annotation = a;
+    }
+
+    // Book moves for the current position with the share of the book weight each one has.
+    pub fn show_book(&mut self, moves: &Vec<(Move, u16)>) {
+        let mut lines = Vec::new();
+        let total: u32 = moves.iter().map(|(_, w)| *w as u32).sum();
+        lines.push(format!("\u{250c} Book {:\u{2500}>19}\u{2510}", ""));
+        if moves.len() == 0 {
+            lines.push(format!("\u{2502} {:<23} \u{2502}", "Out of book"));
+        }
+        for (m, w) in moves.iter().take(7) {
+            let share = if total > 0 { *w as f64 * 100.0 / total as f64 } else { 0.0 };
+            lines.push(format!("\u{2502} {:<12}{:>10.1}% \u{2502}", m.notation(), share));
+        }
+        lines.push(format!("\u{2514}{:\u{2500}>25}\u{2518}", ""));
+        self.set_panel(lines);
+    }
+
+    // Moves played from the current position in the game database, with how they scored for the
+    // side to move and the average rating of the players who played them.
+    pub fn show_explorer(&mut self, moves: &[Candidate]) {
+        let mut lines = Vec::new();
+        let total: u32 = moves.iter().map(|c| c.games).sum();
+        lines.push(format!("\u{250c} Explorer {:\u{2500}>21}\u{2510}", ""));
+        if moves.len() == 0 {
+            lines.push(format!("\u{2502} {:<29} \u{2502}", "Not in the database"));
+        } else {
+            lines.push(format!("\u{2502} {:<7}{:>5}{:>4}{:>4}{:>4}{:>5} \u{2502}", "Move", "Games", "W%", "D%", "L%", "Elo"));
+        }
+        for c in moves.iter().take(6) {
+            let rating = c.average_rating().map(|r| r.to_string()).unwrap_or(String::from("-"));
+            lines.push(format!("\u{2502} {:<7}{:>5}{:>4}{:>4}{:>4}{:>5} \u{2502}", c.san, c.games, c.percent(c.wins), c.percent(c.draws),
+                c.percent(c.losses), rating));
+        }
+        lines.push(format!("\u{2514} {:\u{2500}<30}\u{2518}", format!("{} games ", total)));
+        self.set_panel(lines);
+    }
+
+    // Term by term evaluation of the current position for the side panel, from white's point of view.
+    pub fn show_eval(&mut self, b: &Breakdown) {
+        let mut lines = Vec::new();
+        lines.push(format!("\u{250c} Eval {:>12}{:>6}{:>6} \u{2510}", "White", "Black", "Net"));
+        for t in TERMS {
+            lines.push(format!("\u{2502} {:<11}{:>6}{:>6}{:>6} \u{2502}", t.to_string(), b.side(t, Color::White), b.side(t, Color::Black), b.net(t)));
+        }
+        lines.push(format!("\u{2502} {:<11}{:>18} \u{2502}", "Total", b.total()));
+        lines.push(format!("\u{2514}{:\u{2500}>31}\u{2518}", ""));
+        self.set_panel(lines);
+    }
+
+    // Who is playing whom while watching two bots, with the state of the game and the keys.
+    pub fn show_spectator(&mut self, delay: Duration, status: &str) {
+        let name = |p: &Box<dyn Player>| p.id_string().chars().take(17).collect::<String>();
+        let mut lines = Vec::new();
+        lines.push(format!("\u{250c} Bot vs Bot {:\u{2500}>14}\u{2510}", ""));
+        lines.push(format!("\u{2502} White  {:<17} \u{2502}", name(self.player(Color::White))));
+        lines.push(format!("\u{2502} Black  {:<17} \u{2502}", name(self.player(Color::Black))));
+        lines.push(format!("\u{2502} Delay  {:<17} \u{2502}", format!("{}ms", delay.as_millis())));
+        lines.push(format!("\u{2502} {:<24} \u{2502}", status.chars().take(24).collect::<String>()));
+        lines.push(format!("\u{2502} {:<24} \u{2502}", "p pause  s step  q quit"));
+        lines.push(format!("\u{2514}{:\u{2500}>26}\u{2518}", ""));
+        self.set_panel(lines);
+    }
+
+    // The players of a saved game being stepped through, where it is up to and the keys.
+    pub fn show_replay(&mut self, white: &str, black: &str, status: &str, position: &str) {
+        let cut = |s: &str| s.chars().take(17).collect::<String>();
+        let mut lines = Vec::new();
+        lines.push(format!("\u{250c} Replay {:\u{2500}>18}\u{2510}", ""));
+        lines.push(format!("\u{2502} White  {:<17} \u{2502}", cut(white)));
+        lines.push(format!("\u{2502} Black  {:<17} \u{2502}", cut(black)));
+        lines.push(format!("\u{2502} {:<24} \u{2502}", status.chars().take(24).collect::<String>()));
+        lines.push(format!("\u{2502} {:<24} \u{2502}", position.chars().take(24).collect::<String>()));
+        lines.push(format!("\u{2502} {:<24} \u{2502}", "n next  p back  f flip"));
+        lines.push(format!("\u{2502} {:<24} \u{2502}", "s start  e end"));
+        lines.push(format!("\u{2502} {:<24} \u{2502}", "a analyze  q quit"));
+        lines.push(format!("\u{2514}{:\u{2500}>26}\u{2518}", ""));
+        self.set_panel(lines);
     }
 
     fn print_rank(&self, r: usize) -> String {
@@ -483,6 +749,7 @@ impl Game {
         match self.game_mode {
             GameMode::AgainstHumanLocal => String::from("2 Player Local"),
             GameMode::AgainstBotLocal => String::from("Against Bot"),
+            GameMode::BotVsBot => String::from("Bot vs Bot"),
         }
     }
 
@@ -787,7 +1054,7 @@ impl Game {
         return Some("No pieces can make that move".to_string());
     }
 
-    fn is_check_color(&self, color: Color) -> bool {
+    pub fn is_check_color(&self, color: Color) -> bool {
         if let Some((file, rank)) = self.find_king(color) {
             return self.is_attacked((file, rank), color);
         }
@@ -868,6 +1135,23 @@ impl Game {
         return moves;
     }
 
+    // Like list_valid_moves, with every promotion piece instead of only the queen.
+    pub fn list_valid_moves_with_underpromotions(&self) -> Vec<Move> {
+        let mut moves = Vec::new();
+        for m in self.list_valid_moves() {
+            if m.promotion == PieceKind::None {
+                moves.push(m);
+                continue;
+            }
+            for kind in [PieceKind::Queen, PieceKind::Rook, PieceKind::Bishop, PieceKind::Knight] {
+                let mut p = m;
+                p.promotion = kind;
+                moves.push(p);
+            }
+        }
+        return moves;
+    }
+
     // ================
     // Move Action
     // ================
@@ -914,6 +1198,12 @@ impl Game {
         return previous_state;
     }
 
+    // Passes the turn without moving. The empty history entry keeps en passant from carrying over.
+    pub fn do_null_move(&mut self) {
+        self.history.push(Move::new());
+        self.next_turn();
+    }
+
     pub fn next_turn(&mut self) {
         self.to_move = if self.to_move == Color::White { Color::Black } else { Color::White };
         self.turn_count += if self.to_move == Color::White { 1 } else { 0 };
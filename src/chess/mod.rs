@@ -1,9 +1,13 @@
 // Including all the files in chess/
 pub mod color;
+pub mod coordinate;
+pub mod fen;
 pub mod game;
 pub mod r#move;
 pub mod piece;
+pub mod pgn;
 pub mod player;
 pub mod rankfile;
 pub mod setup;
 pub mod strings;
+pub mod zobrist;
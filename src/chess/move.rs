@@ -8,6 +8,9 @@ pub enum MetaMove {
     Quit,
     Concede,
     Flip,
+    Eval,
+    Hint,
+    Explore,
 }
 
 #[derive(Copy, Clone)]
@@ -236,6 +239,12 @@ pub fn parse_notation(mut input: String, to_move: Color) -> Result<Move, String>
         return Ok(Move::new_meta(MetaMove::Concede));
     } else if input == "flip" {
         return Ok(Move::new_meta(MetaMove::Flip));
+    } else if input == "eval" {
+        return Ok(Move::new_meta(MetaMove::Eval));
+    } else if input == "hint" || input == "book" {
+        return Ok(Move::new_meta(MetaMove::Hint));
+    } else if input == "explore" {
+        return Ok(Move::new_meta(MetaMove::Explore));
     }
 
     if let Some(s) = validate_notation(&input) {
@@ -304,8 +313,9 @@ pub fn gen_pawn_moves(piece: Piece, file: File, rank: Rank) -> Vec<Move> {
         return moves;
     }
 
-    // Double Pawn move
-    if r > 1 || direction == 1 {
+    // Double Pawn move, only from the starting rank (a white pawn on the seventh would otherwise
+    // be sent off the board)
+    if (direction == 1 && r == 1) || (direction == -1 && r == 6) {
         moves.push(Move{
             dest: (File::from_index(f), Rank::from_index((r as i32 + (direction*2)) as usize)), origin: (file, rank),
             piece: piece, takes: false, check: false, checkmate: false, castle: false, long_castle: false,
@@ -475,7 +485,7 @@ pub fn gen_castles() -> Vec<Move> {
 
     moves.push(Move{
         dest: ((File::from_index(0), Rank::from_index(0))), origin: ((File::from_index(0), Rank::from_index(0))),
-        piece: piece, takes: false, check: false, checkmate: false, castle: false, long_castle: false,
+        piece: piece, takes: false, check: false, checkmate: false, castle: true, long_castle: false,
         pawn_double: true, en_passant: None, promotion: PieceKind::None, meta: MetaMove::None
     });
     moves.push(Move{
@@ -486,3 +496,66 @@ pub fn gen_castles() -> Vec<Move> {
 
     return moves;
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::chess::coordinate::{play_coordinate, to_coordinate};
+    use crate::chess::fen::parse_fen;
+    use crate::chess::piece::PieceKind;
+
+    fn moves(fen: &str) -> Vec<String> {
+        let game = parse_fen(fen).unwrap();
+        return game.list_valid_moves().iter().map(|m| to_coordinate(&game, m)).collect();
+    }
+
+    #[test]
+    fn double_pawn_push_only_from_the_starting_rank() {
+        let white = moves("4k3/8/8/8/8/4P3/8/4K3 w - - 0 1");
+        assert!(white.contains(&String::from("e3e4")));
+        assert!(!white.contains(&String::from("e3e5")));
+        let white = moves("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+        assert!(white.contains(&String::from("e2e4")));
+
+        let black = moves("4k3/4p3/8/8/8/8/8/4K3 b - - 0 1");
+        assert!(black.contains(&String::from("e7e5")));
+        let black = moves("4k3/8/4p3/8/8/8/8/4K3 b - - 0 1");
+        assert!(black.contains(&String::from("e6e5")));
+        assert!(!black.contains(&String::from("e6e4")));
+    }
+
+    #[test]
+    fn white_pawn_on_the_seventh_rank() {
+        let white = moves("k7/4P3/8/8/8/8/8/4K3 w - - 0 1");
+        assert!(white.contains(&String::from("e7e8q")));
+    }
+
+    #[test]
+    fn castling_both_ways() {
+        for (fen, short, long, rank) in [
+            ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", "e1g1", "e1c1", 0),
+            ("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1", "e8g8", "e8c8", 7),
+        ] {
+            let legal = moves(fen);
+            assert!(legal.contains(&String::from(short)), "{} in {}", short, fen);
+            assert!(legal.contains(&String::from(long)), "{} in {}", long, fen);
+
+            // The king and the rook both land on their squares, and the rook's corner is left empty.
+            for (text, king, rook, corner) in [(short, 6, 5, 7), (long, 2, 3, 0)] {
+                let mut game = parse_fen(fen).unwrap();
+                let color = game.current_color();
+                let m = play_coordinate(&mut game, text).unwrap();
+                assert!(m.castle || m.long_castle);
+                assert!(game.piece_at(king, rank).kind == PieceKind::King);
+                assert!(game.piece_at(rook, rank).kind == PieceKind::Rook);
+                assert!(game.piece_at(rook, rank).color == color);
+                assert!(game.piece_at(4, rank).kind == PieceKind::None);
+                assert!(game.piece_at(corner, rank).kind == PieceKind::None);
+            }
+        }
+
+        // Not through check: the f1 square is attacked, so only the long castle is left.
+        let legal = moves("4kr2/8/8/8/8/8/8/R3K2R w KQ - 0 1");
+        assert!(!legal.contains(&String::from("e1g1")));
+        assert!(legal.contains(&String::from("e1c1")));
+    }
+}
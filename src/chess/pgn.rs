@@ -0,0 +1,356 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::chess::game::Game;
+use crate::chess::piece::PieceKind;
+use crate::chess::r#move::*;
+use crate::chess::rankfile::tuple_to_square;
+
+// Reading games in Portable Game Notation. Comments, variations and annotations are skipped;
+// only the tags, the main line moves and the time each move took ([%emt] in a comment) are kept.
+
+#[derive(Clone)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<String>, // SAN, in the order played
+    pub times: Vec<Duration>, // how long each move took; empty unless every move has one
+    pub result: String,
+}
+
+impl PgnGame {
+    pub fn new() -> Self {
+        return PgnGame{tags: Vec::new(), moves: Vec::new(), times: Vec::new(), result: String::from("*")};
+    }
+
+    pub fn tag(&self, name: &str) -> Option<String> {
+        for (k, v) in &self.tags {
+            if k == name {
+                return Some(v.clone());
+            }
+        }
+        return None;
+    }
+}
+
+fn is_result(token: &str) -> bool {
+    return token == "1-0" || token == "0-1" || token == "1/2-1/2" || token == "*";
+}
+
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.trim().trim_start_matches('[').trim_end_matches(']');
+    let space = inner.find(' ')?;
+    let name = inner[..space].to_string();
+    let value = inner[space..].trim().trim_matches('"').to_string();
+    return Some((name, value));
+}
+
+// "0:01:05.5" as in [%emt] and [%clk] comments.
+fn parse_clock(text: &str) -> Option<Duration> {
+    let mut seconds = 0.0;
+    for part in text.split(':') {
+        seconds = seconds * 60.0 + part.parse::<f64>().ok().filter(|s| *s >= 0.0)?;
+    }
+    return Some(Duration::from_secs_f64(seconds));
+}
+
+pub fn clock_string(time: Duration) -> String {
+    let seconds = time.as_secs_f64();
+    let whole = seconds as u64;
+    return format!("{}:{:02}:{:04.1}", whole / 3600, whole / 60 % 60, seconds - (whole / 60 * 60) as f64);
+}
+
+// Splits movetext into SAN tokens, dropping move numbers, comments, variations and NAGs. The time
+// in an [%emt] comment is kept as a token of its own, "%emt" and the time.
+fn movetext_tokens(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut comment = false;
+    let mut comment_text = String::new();
+    let mut line_comment = false;
+    let mut depth = 0;
+
+    for c in text.chars() {
+        if line_comment {
+            if c == '\n' {
+                line_comment = false;
+            }
+            continue;
+        }
+        if comment {
+            if c == '}' {
+                comment = false;
+                // Comments inside variations are skipped like the rest of them.
+                if let Some(start) = comment_text.find("[%emt ").filter(|_| depth == 0) {
+                    let rest = &comment_text[start + 6..];
+                    if let Some(end) = rest.find(']') {
+                        tokens.push(format!("%emt {}", rest[..end].trim()));
+                    }
+                }
+                comment_text.clear();
+            } else {
+                comment_text.push(c);
+            }
+            continue;
+        }
+        match c {
+            '{' => {
+                if !current.is_empty() && depth == 0 {
+                    tokens.push(current.clone());
+                    current.clear();
+                }
+                comment = true;
+            },
+            ';' => line_comment = true,
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ if depth > 0 => {},
+            ' ' | '\n' | '\r' | '\t' => {
+                if !current.is_empty() {
+                    tokens.push(current.clone());
+                    current.clear();
+                }
+            },
+            _ => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+
+    let mut moves = Vec::new();
+    for t in tokens {
+        // A result starts with a digit too.
+        if is_result(&t) {
+            moves.push(t);
+            continue;
+        }
+        // "12." or "12..." may be glued to the move that follows
+        let t = t.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+        if t.is_empty() || t.starts_with('$') {
+            continue;
+        }
+        moves.push(t.to_string());
+    }
+    return moves;
+}
+
+pub fn parse_pgn(text: &str) -> Vec<PgnGame> {
+    let mut games = Vec::new();
+    let mut game = PgnGame::new();
+    let mut movetext = String::new();
+    let mut in_moves = false;
+
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') && !trimmed.starts_with("[%") {
+            if in_moves {
+                finish_game(&mut game, &movetext);
+                games.push(game);
+                game = PgnGame::new();
+                movetext.clear();
+                in_moves = false;
+            }
+            if let Some(tag) = parse_tag(trimmed) {
+                game.tags.push(tag);
+            }
+        } else if !trimmed.is_empty() {
+            in_moves = true;
+            movetext.push_str(line);
+            movetext.push('\n');
+        }
+    }
+    if in_moves || game.tags.len() > 0 {
+        finish_game(&mut game, &movetext);
+        games.push(game);
+    }
+    return games;
+}
+
+fn finish_game(game: &mut PgnGame, movetext: &str) {
+    let mut times = Vec::new();
+    for t in movetext_tokens(movetext) {
+        if let Some(time) = t.strip_prefix("%emt ") {
+            // Each time goes with the move before it.
+            if let Some(d) = parse_clock(time).filter(|_| times.len() + 1 == game.moves.len()) {
+                times.push(d);
+            }
+        } else if is_result(&t) {
+            game.result = t;
+        } else {
+            game.moves.push(t);
+        }
+    }
+    if times.len() == game.moves.len() {
+        game.times = times;
+    }
+    if let Some(r) = game.tag("Result") {
+        if game.result == "*" && is_result(&r) {
+            game.result = r;
+        }
+    }
+}
+
+// Resolves a SAN move against the legal moves of the position. Going through the legal move list
+// rather than Game::disambiguate means a piece that is pinned does not count as a second candidate.
+pub fn resolve_san(game: &Game, san: &str) -> Result<Move, String> {
+    let mut text = san.trim_end_matches(['!', '?']).trim_end_matches(['+', '#']).to_string();
+    if text.starts_with("0-0") {
+        text = text.replace('0', "O");
+    }
+    let parsed = parse_notation(text, game.current_color())?;
+    if parsed.meta != MetaMove::None {
+        return Err(format!("Not a move: {}", san));
+    }
+
+    let mut found: Vec<Move> = Vec::new();
+    for mut m in game.list_valid_moves() {
+        if parsed.castle || parsed.long_castle {
+            if m.castle == parsed.castle && m.long_castle == parsed.long_castle {
+                found.push(m);
+            }
+            continue;
+        }
+        if m.castle || m.long_castle || m.piece.kind != parsed.piece.kind || m.dest != parsed.dest {
+            continue;
+        }
+        if (parsed.origin.0.is_valid() && parsed.origin.0 != m.origin.0)
+            || (parsed.origin.1.is_valid() && parsed.origin.1 != m.origin.1)
+        {
+            continue;
+        }
+        if m.promotion != PieceKind::None {
+            if parsed.promotion == PieceKind::None {
+                continue;
+            }
+            m.promotion = parsed.promotion;
+        }
+        found.push(m);
+    }
+
+    return match found.len() {
+        0 => Err(format!("Illegal move: {}", san)),
+        1 => Ok(found[0]),
+        _ => Err(format!("Ambiguous move: {}", san)),
+    };
+}
+
+// Plays a SAN move on the game and hands the turn over.
+pub fn play_san(game: &mut Game, san: &str) -> Result<Move, String> {
+    let m = resolve_san(game, san)?;
+    game.do_move(m);
+    game.next_turn();
+    return Ok(m);
+}
+
+// ================
+// Writing
+// ================
+
+// Standard Algebraic Notation for a legal move, with just enough of the origin square to tell it
+// apart from the other legal moves of the same kind of piece to the same square.
+pub fn to_san(game: &Game, m: &Move) -> String {
+    let mut san = if m.castle {
+        String::from("O-O")
+    } else if m.long_castle {
+        String::from("O-O-O")
+    } else {
+        let takes = m.takes || m.en_passant.is_some();
+        let mut origin = String::new();
+        if m.piece.kind == PieceKind::Pawn {
+            if takes {
+                origin = m.origin.0.to_string();
+            }
+        } else {
+            let others: Vec<Move> = game.list_valid_moves().into_iter()
+                .filter(|o| o.piece.kind == m.piece.kind && o.dest == m.dest && o.origin != m.origin)
+                .collect();
+            if others.len() > 0 {
+                if others.iter().all(|o| o.origin.0 != m.origin.0) {
+                    origin = m.origin.0.to_string();
+                } else if others.iter().all(|o| o.origin.1 != m.origin.1) {
+                    origin = m.origin.1.to_string();
+                } else {
+                    origin = format!("{}{}", m.origin.0.to_string(), m.origin.1.to_string());
+                }
+            }
+        }
+        let promotion = if m.promotion != PieceKind::None { format!("={}", m.promotion.to_letter()) } else { String::new() };
+        format!("{}{}{}{}{}", m.piece.to_letter(), origin, if takes { "x" } else { "" }, tuple_to_square(m.dest), promotion)
+    };
+
+    let mut next = game.clone();
+    next.do_move(*m);
+    next.next_turn();
+    if next.is_check() {
+        san.push(if next.any_valid_moves() { '+' } else { '#' });
+    }
+    return san;
+}
+
+impl PgnGame {
+    // The game as PGN text: the tags, then the movetext wrapped at 80 columns and the result.
+    pub fn to_string(&self) -> String {
+        let mut text = String::new();
+        for (name, value) in &self.tags {
+            text.push_str(&format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\"")));
+        }
+        text.push('\n');
+
+        let black_first = match self.tag("FEN") {
+            Some(fen) => fen.split_whitespace().nth(1) == Some("b"),
+            None => false,
+        };
+        let mut tokens = Vec::new();
+        for (i, san) in self.moves.iter().enumerate() {
+            let ply = i + if black_first { 1 } else { 0 };
+            // A move and its time are kept on one line.
+            let san = match self.times.get(i) {
+                Some(t) if self.times.len() == self.moves.len() => format!("{} {{[%emt {}]}}", san, clock_string(*t)),
+                _ => san.clone(),
+            };
+            if ply % 2 == 0 {
+                tokens.push(format!("{}. {}", ply / 2 + 1, san));
+            } else if i == 0 {
+                tokens.push(format!("1... {}", san));
+            } else {
+                tokens.push(san);
+            }
+        }
+        tokens.push(self.result.clone());
+
+        let mut line = String::new();
+        for t in tokens {
+            if line.len() + t.len() + 1 > 80 {
+                text.push_str(line.trim_end());
+                text.push('\n');
+                line.clear();
+            }
+            line.push_str(&t);
+            line.push(' ');
+        }
+        text.push_str(line.trim_end());
+        text.push_str("\n\n");
+        return text;
+    }
+}
+
+// Today's date in the PGN Date tag format, YYYY.MM.DD, in UTC.
+pub fn today() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    // Days since 1970-01-01 to a civil date, counting in 400 year eras from 0000-03-01.
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    return format!("{:04}.{:02}.{:02}", year, month, day);
+}
+
+// The time of day in UTC, HH:MM:SS, as in the UTCTime tag.
+pub fn time_now() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    return format!("{:02}:{:02}:{:02}", secs / 3600 % 24, secs / 60 % 60, secs % 60);
+}
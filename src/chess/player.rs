@@ -1,7 +1,45 @@
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+use crate::bitbase::Bitbases;
+use crate::book::BookConfig;
 use crate::chess::game::Game;
 use crate::chess::r#move::Move;
+use crate::tablebase::Tablebase;
+
+// Limits on one search, as an engine protocol gives them. With neither a depth, a time nor
+// infinite the player searches as it normally would.
+#[derive(Copy, Clone)]
+pub struct Limits {
+    pub depth: Option<u32>,
+    pub movetime: Option<Duration>,
+    pub infinite: bool, // until stopped
+}
+
+impl Limits {
+    pub fn new() -> Self {
+        return Limits{depth: None, movetime: None, infinite: false};
+    }
+}
+
+// What a searching player has found so far, reported after each finished depth. The score is in
+// centipawns for the side to move; `mate` is in moves, negative when it is the side to move that
+// gets mated.
+#[derive(Clone)]
+pub struct Thinking {
+    pub depth: u32,
+    pub score: i32,
+    pub mate: Option<i32>,
+    pub nodes: u64,
+    pub pv: Vec<Move>,
+}
+
+// Told about each finished depth of a search.
+pub type Report = Box<dyn FnMut(&Thinking) + Send>;
 
-pub trait Player: PlayerClone {
+// Players are Send so that a Game, which owns both of its players, can be handed to a search thread.
+pub trait Player: PlayerClone + Send {
     fn reset(&mut self);
 
     fn get_move(&self, _game: &Game) -> Result<Move, String>;
@@ -17,6 +55,29 @@ pub trait Player: PlayerClone {
     fn id_string(&self) -> String {
         return String::from("Player");
     }
+
+    // A fixed rating for bots whose strength does not change, which their rated games are measured
+    // against. Players without one are rated by their results.
+    fn anchor_rating(&self) -> Option<f64> {
+        return None;
+    }
+
+    // Players that search can consult an opening book. Others ignore it.
+    fn set_book(&mut self, _book: Option<BookConfig>) {}
+
+    // Likewise for endgame tablebases.
+    fn set_tablebase(&mut self, _tablebase: Option<Arc<Tablebase>>) {}
+
+    fn set_bitbases(&mut self, _bitbases: Option<Arc<Bitbases>>) {}
+
+    // Called with the position after the opponent's move, before this player is asked for a move.
+    fn opponent_moved(&mut self, _game: &Game) {}
+
+    // Picks a move within the limits, giving up early once `stop` is set. Players that cannot be
+    // limited or stopped just pick their move as usual and report nothing.
+    fn think(&self, game: &Game, _limits: Limits, _stop: Arc<AtomicBool>, _report: Report) -> Result<Move, String> {
+        return self.get_move(game);
+    }
 }
 
 pub trait PlayerClone {
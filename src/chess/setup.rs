@@ -1,8 +1,21 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::chess::color::Color;
 use crate::chess::game::{Game, StartColor};
 use crate::chess::player::Player;
 
 use crate::bots::human::Human;
 use crate::bots::bogobot::Bogobot;
+use crate::bots::engine::{EngineConfig, UciEngine};
+use crate::bots::mcts::{Budget, MctsBot, MctsOptions};
+use crate::bots::searchbot::{level_name, SearchBot, SearchOptions, MAX_LEVEL};
+use crate::bitbase::{Bitbases, BITBASE_DIR};
+use crate::book::{Book, BookConfig, Selection, BOOK_FILE};
+use crate::eval::nnue::{Network, NETWORK_FILE};
+use crate::eval::weights::{Weights, WEIGHTS_FILE};
+use crate::storage::data_file;
+use crate::tablebase::{Tablebase, TABLEBASE_DIR};
 
 //  Title:
 //  1. Opponent
@@ -11,8 +24,39 @@ use crate::bots::bogobot::Bogobot;
 //      2. Bot
 //          Title:
 //          [List the bots]
-//      3. Auto (auto replay a saved game)
-//      4. Back
+//          Monte Carlo Bot
+//              Title:
+//              1. MctsBot
+//              2. Budget (prompts for iterations or a time)
+//              3. Seed (prompts for a number, empty for random)
+//              4. Back
+//          External UCI Engine
+//              Title:
+//              1. Play the Engine
+//              2. Engine Path (prompts for the engine's executable)
+//              3. Engine Options (prompts for UCI options as Name=Value pairs)
+//              4. Engine Move Time (prompts for milliseconds)
+//              5. Back
+//          Search Options
+//              Title:
+//              [Toggle each search technique, pondering, and the neural network evaluation]
+//          Search Threads (prompts for a number)
+//          Bot Strength
+//              Title:
+//              [A few preset levels]
+//              Custom Level (prompts for a level)
+//      3. Watch Bot vs Bot
+//          Title:
+//          1. White Bot
+//              Title:
+//              [List the bots]
+//          2. Black Bot
+//              Title:
+//              [List the bots]
+//          3. Move Delay (prompts for milliseconds)
+//          4. Back
+//      4. Auto (auto replay a saved game)
+//      5. Back
 //  2. Color (for playing against a bot, is human black or white)
 //      Title:
 //      1. Human plays White
@@ -20,17 +64,116 @@ use crate::bots::bogobot::Bogobot;
 //      3. Human plays Random
 //      4. Back
 //  3. Toggle Board Flip
-//  4. Back
+//  4. Opening Book
+//      Title:
+//      1. Book File (prompts for a path)
+//      2. Book Depth (prompts for a number of moves)
+//      3. Toggle Book Selection
+//      4. Back
+//  5. Tablebases (prompts for a directory of Syzygy files)
+//  6. Player Names
+//      Title:
+//      1. Player One (prompts for a name, empty for none)
+//      2. Player Two (prompts for a name, empty for none)
+//      3. Back
+//  7. Back
 
 #[derive(Clone)]
 pub struct Setup {
     stack: Vec<SetupMenu>,
     confirm: String,
+    search: SearchOptions,
+    mcts: MctsOptions,
+    engine: EngineConfig,
+    weights: Arc<Weights>,
+    network: Option<Arc<Network>>,
+    book: Option<Arc<Book>>,
+    book_path: String,
+    book_depth: u32,
+    book_selection: Selection,
+    tablebase: Option<Arc<Tablebase>>,
+    tablebase_dir: String,
+    bitbases: Option<Arc<Bitbases>>, // whatever `jchess bitbase` has generated
+    spectate_delay: Duration, // between moves when watching two bots
+    names: [String; 2],       // the human players' profiles, empty for an unrated player
+    prompt: Option<Entry>,  // set while waiting for typed input instead of a menu number
 }
 
 impl Setup {
     pub fn new() -> Self {
-        Setup{stack: vec![SetupMenu{ent: Entry::Base, entries: vec![Entry::SelectOpponent, Entry::SelectColor, Entry::DoFlip, Entry::Back]}], confirm: String::new()}
+        let book_path = data_file(BOOK_FILE);
+        let tablebase_dir = data_file(TABLEBASE_DIR);
+        Setup{
+            stack: vec![Setup::base_menu()],
+            confirm: String::new(),
+            search: SearchOptions::new(),
+            mcts: MctsOptions::new(),
+            engine: EngineConfig::new(),
+            weights: Arc::new(Setup::startup_weights()),
+            network: match Network::load(&data_file(NETWORK_FILE)) { Ok(n) => Some(Arc::new(n)), Err(_) => None },
+            book: match Book::load(&book_path) { Ok(b) => Some(Arc::new(b)), Err(_) => None },
+            book_path: book_path.display().to_string(),
+            book_depth: 10,
+            book_selection: Selection::WeightedRandom,
+            tablebase: match Tablebase::open(&tablebase_dir) { Ok(t) => Some(Arc::new(t)), Err(_) => None },
+            tablebase_dir: tablebase_dir.display().to_string(),
+            bitbases: match Bitbases::load_dir(&data_file(BITBASE_DIR)) { Ok(b) => Some(Arc::new(b)), Err(_) => None },
+            spectate_delay: Duration::from_millis(500),
+            names: [String::new(), String::new()],
+            prompt: None,
+        }
+    }
+
+    // The interactive game still starts with weights that do not read, on the defaults, after
+    // saying what is wrong with them.
+    fn startup_weights() -> Weights {
+        return match Weights::load_or_default(&data_file(WEIGHTS_FILE)) {
+            Ok(w) => w,
+            Err(e) => {
+                eprintln!("{}; using the default evaluation weights", e);
+                Weights::new()
+            },
+        };
+    }
+
+    fn base_menu() -> SetupMenu {
+        return SetupMenu{ent: Entry::Base, entries: vec![Entry::SelectOpponent, Entry::SelectColor, Entry::DoFlip, Entry::OpeningBook, Entry::Tablebases, Entry::Players, Entry::Back]};
+    }
+
+    // Return to the top menu, keeping the configured options.
+    pub fn reset_menu(&mut self) {
+        self.stack = vec![Setup::base_menu()];
+        self.confirm = String::new();
+        self.prompt = None;
+    }
+
+    pub fn awaiting_input(&self) -> bool {
+        return self.prompt.is_some();
+    }
+
+    // Handles typed input for the entry that asked for it.
+    pub fn input(&mut self, text: String, game: &mut Game) {
+        let prompt = self.prompt;
+        self.prompt = None;
+        match prompt {
+            Some(Entry::BookPath) => self.set_book_path(text, game),
+            Some(Entry::BookDepth) => self.set_book_depth(text, game),
+            Some(Entry::Tablebases) => self.set_tablebase_dir(text, game),
+            Some(Entry::Threads) => self.set_threads(text, game),
+            Some(Entry::MctsBudget) => self.set_mcts_budget(text, game),
+            Some(Entry::MctsSeed) => self.set_mcts_seed(text, game),
+            Some(Entry::MoveDelay) => self.set_move_delay(text),
+            Some(Entry::EnginePath) => self.set_engine_path(text, game),
+            Some(Entry::EngineOptions) => self.set_engine_options(text, game),
+            Some(Entry::EngineMovetime) => self.set_engine_movetime(text, game),
+            Some(Entry::PlayerOneName) => self.set_name(0, text, game),
+            Some(Entry::PlayerTwoName) => self.set_name(1, text, game),
+            Some(Entry::CustomLevel) => match text.parse::<u32>() {
+                Ok(l) if (1..=MAX_LEVEL).contains(&l) => self.set_level(game, l),
+                _ => self.confirm = format!("Level must be a number from 1 to {}", MAX_LEVEL),
+            },
+            _ => return,
+        }
     }
 
     pub fn current(&self) -> &SetupMenu {
@@ -48,12 +191,51 @@ impl Setup {
             Entry::HumanOpp => self.select_config(Entry::HumanOpp, game),
             Entry::BotOpp => self.select_menu(Entry::BotOpp),
             Entry::Bogobot => self.select_config(Entry::Bogobot, game),
+            Entry::SearchBot => self.select_config(Entry::SearchBot, game),
+            Entry::MctsMenu => self.select_menu(Entry::MctsMenu),
+            Entry::MctsBot => self.select_config(Entry::MctsBot, game),
+            Entry::MctsBudget => self.ask(Entry::MctsBudget, format!("Iterations, or a time such as 5s ({}):", self.mcts.budget.to_string())),
+            Entry::MctsSeed => self.ask(Entry::MctsSeed, format!("Seed, empty for random ({}):", match self.mcts.seed {
+                Some(s) => s.to_string(),
+                None => String::from("random"),
+            })),
+            Entry::EngineMenu => self.select_menu(Entry::EngineMenu),
+            Entry::EngineBot => self.select_config(Entry::EngineBot, game),
+            Entry::EnginePath => self.ask(Entry::EnginePath, format!("Engine path ({}):", self.engine.path)),
+            Entry::EngineOptions => self.ask(Entry::EngineOptions, format!("UCI options as Name=Value, ... ({}):", self.engine.options_string())),
+            Entry::EngineMovetime => self.ask(Entry::EngineMovetime, format!("Engine time per move in ms ({}):", self.engine.movetime.as_millis())),
+            Entry::SearchOptions => self.select_menu(Entry::SearchOptions),
+            Entry::NullMove => self.select_config(Entry::NullMove, game),
+            Entry::LateMoveReductions => self.select_config(Entry::LateMoveReductions, game),
+            Entry::PrincipalVariation => self.select_config(Entry::PrincipalVariation, game),
+            Entry::CheckExtensions => self.select_config(Entry::CheckExtensions, game),
+            Entry::AspirationWindows => self.select_config(Entry::AspirationWindows, game),
+            Entry::Ponder => self.select_config(Entry::Ponder, game),
+            Entry::Nnue => self.select_config(Entry::Nnue, game),
+            Entry::Threads => self.ask(Entry::Threads, format!("Search threads ({}):", self.search.threads)),
+            Entry::Strength => self.select_menu(Entry::Strength),
+            Entry::Level(l) => self.set_level(game, l),
+            Entry::CustomLevel => self.ask(Entry::CustomLevel, format!("Level, 1 to {} ({}):", MAX_LEVEL, self.search.level)),
+            Entry::ReloadWeights => self.select_config(Entry::ReloadWeights, game),
+            Entry::Spectate => self.select_menu(Entry::Spectate),
+            Entry::WatchWhite => self.select_menu(Entry::WatchWhite),
+            Entry::WatchBlack => self.select_menu(Entry::WatchBlack),
+            Entry::Watch(color, kind) => self.set_watched(game, color, kind),
+            Entry::MoveDelay => self.ask(Entry::MoveDelay, format!("Delay between moves in ms ({}):", self.spectate_delay.as_millis())),
             Entry::Auto => self.select_config(Entry::Auto, game),
             Entry::SelectColor => self.select_menu(Entry::SelectColor),
             Entry::PlayAsWhite => self.select_config(Entry::PlayAsWhite, game),
             Entry::PlayAsBlack => self.select_config(Entry::PlayAsBlack, game),
             Entry::PlayAsRandom => self.select_config(Entry::PlayAsRandom, game),
             Entry::DoFlip => self.select_config(Entry::DoFlip, game),
+            Entry::OpeningBook => self.select_menu(Entry::OpeningBook),
+            Entry::BookPath => self.ask(Entry::BookPath, format!("Book file ({}):", self.book_path)),
+            Entry::BookDepth => self.ask(Entry::BookDepth, format!("Book depth in moves ({}):", self.book_depth)),
+            Entry::BookSelection => self.select_config(Entry::BookSelection, game),
+            Entry::Tablebases => self.ask(Entry::Tablebases, format!("Tablebase directory ({}):", self.tablebase_dir)),
+            Entry::Players => self.select_menu(Entry::Players),
+            Entry::PlayerOneName => self.ask(Entry::PlayerOneName, format!("Player one's name, - for none ({}):", self.names[0])),
+            Entry::PlayerTwoName => self.ask(Entry::PlayerTwoName, format!("Player two's name, - for none ({}):", self.names[1])),
             Entry::Back => return self.back(),
         };
         return false;
@@ -65,11 +247,80 @@ impl Setup {
             Entry::SelectOpponent => self.stack.push(SetupMenu{ent: Entry::SelectOpponent, entries: vec![
                 Entry::HumanOpp,
                 Entry::BotOpp,
+                Entry::Spectate,
                 //Entry::Auto,
                 Entry::Back
             ]}),
             Entry::BotOpp => self.stack.push(SetupMenu{ent: Entry::BotOpp, entries: vec![
                 Entry::Bogobot,
+                Entry::SearchBot,
+                Entry::MctsMenu,
+                Entry::EngineMenu,
+                Entry::SearchOptions,
+                Entry::Threads,
+                Entry::Strength,
+                Entry::ReloadWeights,
+                Entry::Back
+            ]}),
+            Entry::EngineMenu => self.stack.push(SetupMenu{ent: Entry::EngineMenu, entries: vec![
+                Entry::EngineBot,
+                Entry::EnginePath,
+                Entry::EngineOptions,
+                Entry::EngineMovetime,
+                Entry::Back
+            ]}),
+            Entry::Spectate => self.stack.push(SetupMenu{ent: Entry::Spectate, entries: vec![
+                Entry::WatchWhite,
+                Entry::WatchBlack,
+                Entry::MoveDelay,
+                Entry::Back
+            ]}),
+            Entry::WatchWhite => self.stack.push(SetupMenu{ent: Entry::WatchWhite, entries: vec![
+                Entry::Watch(Color::White, BotKind::Bogo),
+                Entry::Watch(Color::White, BotKind::Search),
+                Entry::Watch(Color::White, BotKind::Mcts),
+                Entry::Back
+            ]}),
+            Entry::WatchBlack => self.stack.push(SetupMenu{ent: Entry::WatchBlack, entries: vec![
+                Entry::Watch(Color::Black, BotKind::Bogo),
+                Entry::Watch(Color::Black, BotKind::Search),
+                Entry::Watch(Color::Black, BotKind::Mcts),
+                Entry::Back
+            ]}),
+            Entry::MctsMenu => self.stack.push(SetupMenu{ent: Entry::MctsMenu, entries: vec![
+                Entry::MctsBot,
+                Entry::MctsBudget,
+                Entry::MctsSeed,
+                Entry::Back
+            ]}),
+            Entry::Strength => self.stack.push(SetupMenu{ent: Entry::Strength, entries: vec![
+                Entry::Level(1),
+                Entry::Level(6),
+                Entry::Level(11),
+                Entry::Level(16),
+                Entry::Level(MAX_LEVEL),
+                Entry::CustomLevel,
+                Entry::Back
+            ]}),
+            Entry::SearchOptions => self.stack.push(SetupMenu{ent: Entry::SearchOptions, entries: vec![
+                Entry::NullMove,
+                Entry::LateMoveReductions,
+                Entry::PrincipalVariation,
+                Entry::CheckExtensions,
+                Entry::AspirationWindows,
+                Entry::Ponder,
+                Entry::Nnue,
+                Entry::Back
+            ]}),
+            Entry::OpeningBook => self.stack.push(SetupMenu{ent: Entry::OpeningBook, entries: vec![
+                Entry::BookPath,
+                Entry::BookDepth,
+                Entry::BookSelection,
+                Entry::Back
+            ]}),
+            Entry::Players => self.stack.push(SetupMenu{ent: Entry::Players, entries: vec![
+                Entry::PlayerOneName,
+                Entry::PlayerTwoName,
                 Entry::Back
             ]}),
             Entry::SelectColor => self.stack.push(SetupMenu{ent: Entry::SelectColor, entries: vec![
@@ -84,13 +335,25 @@ impl Setup {
 
     fn select_config(&mut self, menu: Entry, game: &mut Game) {
         match menu {
-            Entry::HumanOpp => self.set_opponent(game, Box::new(Human::new())),
+            Entry::HumanOpp => self.set_opponent(game, Box::new(self.human(1))),
             Entry::Bogobot => self.set_opponent(game, Box::new(Bogobot::new())),
+            Entry::SearchBot => self.set_opponent(game, Box::new(self.search_bot())),
+            Entry::MctsBot => self.set_opponent(game, Box::new(self.mcts_bot())),
+            Entry::EngineBot => self.play_engine(game),
+            Entry::NullMove => self.toggle_search(game, Entry::NullMove),
+            Entry::LateMoveReductions => self.toggle_search(game, Entry::LateMoveReductions),
+            Entry::PrincipalVariation => self.toggle_search(game, Entry::PrincipalVariation),
+            Entry::CheckExtensions => self.toggle_search(game, Entry::CheckExtensions),
+            Entry::AspirationWindows => self.toggle_search(game, Entry::AspirationWindows),
+            Entry::Ponder => self.toggle_search(game, Entry::Ponder),
+            Entry::Nnue => self.toggle_nnue(game),
+            Entry::ReloadWeights => self.reload_weights(),
             Entry::Auto => return,
             Entry::PlayAsWhite => self.set_start_color(game, StartColor::White),
             Entry::PlayAsBlack => self.set_start_color(game, StartColor::Black),
             Entry::PlayAsRandom => self.set_start_color(game, StartColor::Random),
             Entry::DoFlip => self.toggle_flip(game),
+            Entry::BookSelection => self.toggle_book_selection(game),
             _ => return,
         }
     }
@@ -103,6 +366,52 @@ impl Setup {
         return false;
     }
 
+    fn ask(&mut self, ent: Entry, question: String) {
+        self.prompt = Some(ent);
+        self.confirm = question;
+    }
+
+    pub fn search_bot(&self) -> SearchBot {
+        let mut bot = SearchBot::new(self.search, self.weights.clone());
+        bot.set_book(self.book_config());
+        bot.set_tablebase(self.tablebase.clone());
+        bot.set_bitbases(self.bitbases.clone());
+        bot.set_network(self.network.clone());
+        return bot;
+    }
+
+    fn mcts_bot(&self) -> MctsBot {
+        return MctsBot::new(self.mcts, self.weights.clone());
+    }
+
+    // Search and Monte Carlo bots are made with the options set in the bot menus.
+    fn bot(&self, kind: BotKind) -> Box<dyn Player> {
+        return match kind {
+            BotKind::Bogo => Box::new(Bogobot::new()),
+            BotKind::Search => Box::new(self.search_bot()),
+            BotKind::Mcts => Box::new(self.mcts_bot()),
+        };
+    }
+
+    pub fn book_config(&self) -> Option<BookConfig> {
+        return match &self.book {
+            Some(b) => Some(BookConfig{book: b.clone(), depth: self.book_depth, selection: self.book_selection}),
+            None => None,
+        };
+    }
+
+    pub fn tablebase(&self) -> Option<Arc<Tablebase>> {
+        return self.tablebase.clone();
+    }
+
+    pub fn weights(&self) -> Arc<Weights> {
+        return self.weights.clone();
+    }
+
+    pub fn spectate_delay(&self) -> Duration {
+        return self.spectate_delay;
+    }
+
     pub fn confirm_string(&self) -> String {
         return String::from(&self.confirm);
     }
@@ -121,10 +430,246 @@ impl Setup {
         }));
     }
 
+    // Toggling a search technique also makes a SearchBot with the new options the opponent.
+    fn toggle_search(&mut self, game: &mut Game, ent: Entry) {
+        let res = match ent {
+            Entry::NullMove => { self.search.null_move = !self.search.null_move; self.search.null_move },
+            Entry::LateMoveReductions => { self.search.late_move_reductions = !self.search.late_move_reductions; self.search.late_move_reductions },
+            Entry::PrincipalVariation => { self.search.pvs = !self.search.pvs; self.search.pvs },
+            Entry::CheckExtensions => { self.search.check_extensions = !self.search.check_extensions; self.search.check_extensions },
+            Entry::AspirationWindows => { self.search.aspiration_windows = !self.search.aspiration_windows; self.search.aspiration_windows },
+            Entry::Ponder => { self.search.ponder = !self.search.ponder; self.search.ponder },
+            Entry::Nnue => { self.search.nnue = !self.search.nnue; self.search.nnue },
+            _ => return,
+        };
+        self.set_opponent(game, Box::new(self.search_bot()));
+        self.confirm = String::from(format!("SearchBot {}: {}", ent.string(), if res { "on" } else { "off" }));
+    }
+
+    fn toggle_nnue(&mut self, game: &mut Game) {
+        if !self.search.nnue && self.network.is_none() {
+            self.confirm = format!("No network loaded from {}", data_file(NETWORK_FILE).display());
+            return;
+        }
+        self.toggle_search(game, Entry::Nnue);
+    }
+
+    fn set_threads(&mut self, text: String, game: &mut Game) {
+        match text.parse::<u32>() {
+            Ok(n) if n >= 1 => {
+                self.search.threads = n;
+                self.set_opponent(game, Box::new(self.search_bot()));
+                self.confirm = format!("SearchBot Threads: {}", n);
+            },
+            _ => self.confirm = String::from("Thread count must be a number of at least 1"),
+        }
+    }
+
+    // Changing the budget or seed also makes an MctsBot with them the opponent.
+    fn set_mcts_budget(&mut self, text: String, game: &mut Game) {
+        match Budget::parse(&text) {
+            Some(b) => {
+                self.mcts.budget = b;
+                self.set_opponent(game, Box::new(self.mcts_bot()));
+            },
+            None => self.confirm = String::from("Budget must be a number of iterations or a time such as 5s"),
+        }
+    }
+
+    fn set_mcts_seed(&mut self, text: String, game: &mut Game) {
+        if text.trim().is_empty() {
+            self.mcts.seed = None;
+        } else {
+            match text.trim().parse::<u64>() {
+                Ok(s) => self.mcts.seed = Some(s),
+                Err(_) => {
+                    self.confirm = String::from("Seed must be a number");
+                    return;
+                },
+            }
+        }
+        self.set_opponent(game, Box::new(self.mcts_bot()));
+    }
+
+    // Starts the engine and makes it the opponent. Changing the path, options or move time does the
+    // same once there is a path, so that a mistake shows up straight away.
+    fn play_engine(&mut self, game: &mut Game) {
+        match UciEngine::start(&self.engine) {
+            Ok(e) => self.set_opponent(game, Box::new(e)),
+            Err(e) => self.confirm = e,
+        }
+    }
+
+    fn set_engine_path(&mut self, text: String, game: &mut Game) {
+        if text.is_empty() {
+            return;
+        }
+        self.engine.path = text;
+        self.play_engine(game);
+    }
+
+    fn set_engine_options(&mut self, text: String, game: &mut Game) {
+        match EngineConfig::parse_options(&text) {
+            Ok(o) => self.engine.options = o,
+            Err(e) => {
+                self.confirm = e;
+                return;
+            },
+        }
+        if self.engine.path.is_empty() {
+            self.confirm = format!("Set Engine Options: {}", self.engine.options_string());
+            return;
+        }
+        self.play_engine(game);
+    }
+
+    fn set_engine_movetime(&mut self, text: String, game: &mut Game) {
+        match text.parse::<u64>() {
+            Ok(ms) if ms > 0 => self.engine.movetime = Duration::from_millis(ms),
+            _ => {
+                self.confirm = String::from("Move time must be a number of milliseconds");
+                return;
+            },
+        }
+        if self.engine.path.is_empty() {
+            self.confirm = format!("Set Engine Move Time: {}ms", self.engine.movetime.as_millis());
+            return;
+        }
+        self.play_engine(game);
+    }
+
+    fn set_level(&mut self, game: &mut Game, level: u32) {
+        self.search.level = level;
+        self.set_opponent(game, Box::new(self.search_bot()));
+    }
+
+    // Takes effect for SearchBots created from here on. Reloads the network file too.
+    fn reload_weights(&mut self) {
+        let path = data_file(WEIGHTS_FILE);
+        self.confirm = match Weights::load(&path) {
+            Ok(w) => {
+                self.weights = Arc::new(w);
+                String::from("Loaded Eval Weights")
+            },
+            Err(e) => e,
+        };
+        // The network is optional, so only a network that loads is mentioned.
+        if let Ok(n) = Network::load(&data_file(NETWORK_FILE)) {
+            self.network = Some(Arc::new(n));
+            self.confirm += ", Loaded Network";
+        }
+    }
+
+    fn set_book_path(&mut self, text: String, game: &mut Game) {
+        if text.is_empty() {
+            return;
+        }
+        match Book::load(std::path::Path::new(&text)) {
+            Ok(b) => {
+                self.confirm = format!("Loaded Book: {} entries", b.len());
+                self.book = Some(Arc::new(b));
+                self.book_path = text;
+                game.set_book(self.book_config());
+            },
+            Err(e) => self.confirm = e,
+        }
+    }
+
+    fn set_book_depth(&mut self, text: String, game: &mut Game) {
+        match text.parse::<u32>() {
+            Ok(d) => {
+                self.book_depth = d;
+                self.confirm = format!("Set Book Depth: {} moves", d);
+                game.set_book(self.book_config());
+            },
+            Err(_) => self.confirm = String::from("Book depth must be a number"),
+        }
+    }
+
+    fn set_tablebase_dir(&mut self, text: String, game: &mut Game) {
+        if text.is_empty() {
+            return;
+        }
+        match Tablebase::open(std::path::Path::new(&text)) {
+            Ok(t) => {
+                self.confirm = format!("Loaded Tablebases: {} tables, up to {} pieces", t.len(), t.max_pieces);
+                self.tablebase = Some(Arc::new(t));
+                self.tablebase_dir = text;
+                game.set_tablebase(self.tablebase());
+            },
+            Err(e) => self.confirm = e,
+        }
+    }
+
+    fn toggle_book_selection(&mut self, game: &mut Game) {
+        self.book_selection = if self.book_selection == Selection::Best { Selection::WeightedRandom } else { Selection::Best };
+        self.confirm = format!("Set Book Selection: {}", self.book_selection.to_string());
+        game.set_book(self.book_config());
+    }
+
+    // Player one is the user again after watching two bots.
     fn set_opponent(&mut self, game: &mut Game, opp: Box<dyn Player>) {
         self.confirm = String::from(format!("Set Opponent: {}", opp.id_string()));
+        if game.player_one().is_bot() {
+            game.set_player_one(Box::new(self.human(0)));
+        }
         game.set_player_two(opp);
     }
+
+    // Player one plays white and player two black when watching. If the other side is not a bot
+    // yet it gets the same kind of bot, so that there is a game to watch straight away.
+    fn set_watched(&mut self, game: &mut Game, color: Color, kind: BotKind) {
+        if color == Color::White {
+            game.set_player_one(self.bot(kind));
+        } else {
+            game.set_player_two(self.bot(kind));
+        }
+        if !game.is_spectating() {
+            if color == Color::White {
+                game.set_player_two(self.bot(kind));
+            } else {
+                game.set_player_one(self.bot(kind));
+            }
+        }
+        self.confirm = format!("Watching {} vs {}", game.player_one().id_string(), game.player_two().id_string());
+    }
+
+    // Player one or two, as a human under the profile set for them.
+    fn human(&self, player: usize) -> Human {
+        return if self.names[player].is_empty() { Human::new() } else { Human::named(&self.names[player]) };
+    }
+
+    // Names the human on that side, if it is one. Nothing typed keeps the name; "-" clears it.
+    fn set_name(&mut self, player: usize, text: String, game: &mut Game) {
+        if text.is_empty() {
+            return;
+        }
+        let name = if text == "-" { "" } else { text.as_str() };
+        if name.contains('\t') || name == "Human" {
+            self.confirm = format!("Not a name: {}", name);
+            return;
+        }
+        self.names[player] = name.to_string();
+        if player == 0 && game.player_one().is_human() {
+            game.set_player_one(Box::new(self.human(0)));
+        } else if player == 1 && game.player_two().is_human() {
+            game.set_player_two(Box::new(self.human(1)));
+        }
+        self.confirm = if name.is_empty() { format!("Player {} is unrated", player + 1) } else { format!("Player {}: {}", player + 1, name) };
+    }
+
+    fn set_move_delay(&mut self, text: String) {
+        if text.is_empty() {
+            return;
+        }
+        match text.parse::<u64>() {
+            Ok(ms) => {
+                self.spectate_delay = Duration::from_millis(ms);
+                self.confirm = format!("Set Move Delay: {}ms", ms);
+            },
+            Err(_) => self.confirm = String::from("Delay must be a number of milliseconds"),
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -138,6 +683,10 @@ impl SetupMenu {
         return self.ent;
     }
 
+    pub fn len(&self) -> usize {
+        return self.entries.len();
+    }
+
     pub fn print_entry(&self, idx: usize) -> String {
         if idx >= self.entries.len() {
             return String::from("");
@@ -153,12 +702,48 @@ pub enum Entry {
         HumanOpp,
         BotOpp,
             Bogobot,
+            SearchBot,
+            MctsMenu,
+                MctsBot,
+                MctsBudget,
+                MctsSeed,
+            EngineMenu,
+                EngineBot,
+                EnginePath,
+                EngineOptions,
+                EngineMovetime,
+            SearchOptions,
+                NullMove,
+                LateMoveReductions,
+                PrincipalVariation,
+                CheckExtensions,
+                AspirationWindows,
+                Ponder,
+                Nnue,
+            Threads,
+            Strength,
+                Level(u32),
+                CustomLevel,
+            ReloadWeights,
+        Spectate,
+            WatchWhite,
+            WatchBlack,
+                Watch(Color, BotKind),
+            MoveDelay,
         Auto,
     SelectColor,
         PlayAsWhite,
         PlayAsBlack,
         PlayAsRandom,
     DoFlip,
+    OpeningBook,
+        BookPath,
+        BookDepth,
+        BookSelection,
+    Tablebases,
+    Players,
+        PlayerOneName,
+        PlayerTwoName,
     Back,
 }
 
@@ -170,13 +755,66 @@ impl Entry {
             Entry::HumanOpp => String::from("Play against Human"),
             Entry::BotOpp => String::from("Select a Bot"),
             Entry::Bogobot => String::from("BogoBot"),
+            Entry::SearchBot => String::from("SearchBot"),
+            Entry::MctsMenu => String::from("Monte Carlo Bot"),
+            Entry::MctsBot => String::from("MctsBot"),
+            Entry::MctsBudget => String::from("MCTS Budget"),
+            Entry::MctsSeed => String::from("MCTS Seed"),
+            Entry::EngineMenu => String::from("External UCI Engine"),
+            Entry::EngineBot => String::from("Play the Engine"),
+            Entry::EnginePath => String::from("Engine Path"),
+            Entry::EngineOptions => String::from("Engine Options"),
+            Entry::EngineMovetime => String::from("Engine Move Time"),
+            Entry::SearchOptions => String::from("Search Options"),
+            Entry::NullMove => String::from("Null Move Pruning"),
+            Entry::LateMoveReductions => String::from("Late Move Reductions"),
+            Entry::PrincipalVariation => String::from("Principal Variation Search"),
+            Entry::CheckExtensions => String::from("Check Extensions"),
+            Entry::AspirationWindows => String::from("Aspiration Windows"),
+            Entry::Ponder => String::from("Pondering"),
+            Entry::Nnue => String::from("Neural Network Eval"),
+            Entry::Threads => String::from("Search Threads"),
+            Entry::Strength => String::from("Bot Strength"),
+            Entry::Level(l) => level_name(*l),
+            Entry::CustomLevel => String::from("Custom Level"),
+            Entry::ReloadWeights => String::from("Reload Eval Weights"),
+            Entry::Spectate => String::from("Watch Bot vs Bot"),
+            Entry::WatchWhite => String::from("White Bot"),
+            Entry::WatchBlack => String::from("Black Bot"),
+            Entry::Watch(_, kind) => kind.to_string(),
+            Entry::MoveDelay => String::from("Move Delay"),
             Entry::Auto => String::from("Auto-play"),
             Entry::SelectColor => String::from("Change Color"),
             Entry::PlayAsWhite => String::from("Play as White"),
             Entry::PlayAsBlack => String::from("Play as Black"),
             Entry::PlayAsRandom => String::from("Play as Random"),
             Entry::DoFlip => String::from("Toggle Board Flip"),
+            Entry::OpeningBook => String::from("Opening Book"),
+            Entry::BookPath => String::from("Book File"),
+            Entry::BookDepth => String::from("Book Depth"),
+            Entry::BookSelection => String::from("Toggle Book Selection"),
+            Entry::Tablebases => String::from("Endgame Tablebases"),
+            Entry::Players => String::from("Player Names"),
+            Entry::PlayerOneName => String::from("Player One"),
+            Entry::PlayerTwoName => String::from("Player Two"),
             Entry::Back => String::from("Back"),
         }
     }
 }
+
+#[derive(Clone, Copy)]
+pub enum BotKind {
+    Bogo,
+    Search,
+    Mcts,
+}
+
+impl BotKind {
+    pub fn to_string(self) -> String {
+        return match self {
+            BotKind::Bogo => String::from("BogoBot"),
+            BotKind::Search => String::from("SearchBot"),
+            BotKind::Mcts => String::from("MctsBot"),
+        };
+    }
+}
@@ -0,0 +1,112 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::chess::pgn::parse_pgn;
+use crate::input::args::Args;
+use crate::storage::data_file;
+
+use super::query::{Query, FIELDS};
+use super::{Database, DATABASE_FILE};
+
+// The game database from the command line:
+//   jchess db import [--db FILE] PGN...
+//   jchess db search [--db FILE] [--limit N] [--player NAME] [--white NAME] [--black NAME]
+//                    [--result R] [--date D] [--eco E] [--opening NAME] [--fen FEN] [--material M]
+//   jchess db show [--db FILE] N
+//   jchess db stats [--db FILE]
+// Imports add to the database that is there. Games are numbered from 1 in the order they were
+// imported.
+
+const USAGE: &str = "Usage: jchess db import|search|show|stats [--db FILE] ...";
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let mut options = vec!["db", "limit"];
+    options.extend(FIELDS);
+    let parsed = Args::parse(args, &options, &[], USAGE)?;
+    let path = match parsed.get("db") {
+        Some(p) => PathBuf::from(p),
+        None => data_file(DATABASE_FILE),
+    };
+    let positional = &parsed.positional;
+    if positional.len() == 0 {
+        return Err(String::from(USAGE));
+    }
+    return match positional[0].as_str() {
+        "import" => import(&path, &positional[1..]),
+        "search" => search(&path, &parsed),
+        "show" => show(&path, &positional[1..]),
+        "stats" => stats(&path),
+        _ => Err(String::from(USAGE)),
+    };
+}
+
+fn import(path: &Path, files: &[String]) -> Result<(), String> {
+    if files.len() == 0 {
+        return Err(String::from("Usage: jchess db import [--db FILE] PGN..."));
+    }
+    let mut db = Database::load_or_new(path)?;
+    let before = db.games.len();
+    let mut skipped = 0;
+    for file in files {
+        let text = match fs::read_to_string(file) {
+            Ok(t) => t,
+            Err(e) => return Err(format!("Could not read {}: {}", file, e)),
+        };
+        for pgn in parse_pgn(&text) {
+            if !db.add(&pgn) {
+                skipped += 1;
+            }
+        }
+    }
+    db.reindex();
+    db.save(path)?;
+    println!("Imported {} games ({} skipped)", db.games.len() - before, skipped);
+    println!("{} now has {} games and {} positions", path.display(), db.games.len(), db.position_count());
+    return Ok(());
+}
+
+fn search(path: &Path, args: &Args) -> Result<(), String> {
+    let limit = args.get_or("limit", 20usize)?;
+    let mut query = Query::new();
+    for field in FIELDS {
+        if let Some(value) = args.get(field) {
+            query.set(field, &value)?;
+        }
+    }
+    let db = Database::load(path)?;
+    let found = db.search(&query);
+    for (id, ply) in found.iter().take(limit) {
+        let g = &db.games[*id];
+        let name = |s: &str| if s.is_empty() { String::from("?") } else { s.to_string() };
+        let at = if *ply > 0 { format!("  (ply {})", ply) } else { String::new() };
+        println!("{:>6}  {:<10}  {} - {}  {}  {}{}", id + 1, name(&g.date), name(&g.white), name(&g.black), g.result, g.eco, at);
+    }
+    if found.len() > limit {
+        println!("... {} more", found.len() - limit);
+    }
+    println!("{} of {} games match", found.len(), db.games.len());
+    return Ok(());
+}
+
+fn show(path: &Path, args: &[String]) -> Result<(), String> {
+    let db = Database::load(path)?;
+    let n = match args.first().map(|a| a.parse::<usize>()) {
+        Some(Ok(n)) if n >= 1 && n <= db.games.len() => n,
+        _ => return Err(format!("Usage: jchess db show [--db FILE] N, with N from 1 to {}", db.games.len())),
+    };
+    print!("{}", db.games[n - 1].to_pgn().to_string());
+    return Ok(());
+}
+
+fn stats(path: &Path) -> Result<(), String> {
+    let db = Database::load(path)?;
+    let count = |result: &str| db.games.iter().filter(|g| g.result == result).count();
+    let moves: usize = db.games.iter().map(|g| g.moves.len()).sum();
+    println!("{}", path.display());
+    println!("Games: {}", db.games.len());
+    println!("White wins: {}, black wins: {}, draws: {}, unfinished: {}", count("1-0"), count("0-1"), count("1/2-1/2"),
+        db.games.len() - count("1-0") - count("0-1") - count("1/2-1/2"));
+    println!("Moves: {}", moves);
+    println!("Positions indexed: {}", db.position_count());
+    return Ok(());
+}
@@ -0,0 +1,81 @@
+use std::cmp::Reverse;
+
+use crate::book::polyglot::decode_move;
+use crate::chess::color::Color;
+use crate::chess::game::Game;
+use crate::chess::pgn::to_san;
+use crate::chess::zobrist;
+
+use super::Database;
+
+// What was played from a position in the database's games: every move with how many games it was
+// played in, how those games went for the side that played it and the average rating of the
+// players who chose it. A game that comes back to the position counts only once.
+
+pub struct Candidate {
+    pub san: String,
+    pub games: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    rating_sum: u64,
+    rated: u32,
+}
+
+impl Candidate {
+    // The share of the games as a whole percentage.
+    pub fn percent(&self, count: u32) -> u32 {
+        return (count as f64 * 100.0 / self.games as f64).round() as u32;
+    }
+
+    // The average rating of the players who played the move, when any of them had one.
+    pub fn average_rating(&self) -> Option<u32> {
+        if self.rated == 0 {
+            return None;
+        }
+        return Some((self.rating_sum / self.rated as u64) as u32);
+    }
+}
+
+impl Database {
+    // The moves played from the position, the most played first.
+    pub fn explore(&self, game: &Game) -> Vec<Candidate> {
+        let color = game.current_color();
+        let mut found: Vec<(u16, Candidate)> = Vec::new();
+        for (id, ply) in self.find_position(zobrist::hash(game)) {
+            let g = &self.games[id];
+            let code = match g.moves.get(ply) {
+                Some(c) => *c,
+                None => continue,
+            };
+            let i = match found.iter().position(|f| f.0 == code) {
+                Some(i) => i,
+                None => {
+                    let san = match decode_move(game, code) {
+                        Some(m) => to_san(game, &m),
+                        None => continue,
+                    };
+                    found.push((code, Candidate{san: san, games: 0, wins: 0, draws: 0, losses: 0, rating_sum: 0, rated: 0}));
+                    found.len() - 1
+                },
+            };
+            let c = &mut found[i].1;
+            c.games += 1;
+            let won = if color == Color::White { "1-0" } else { "0-1" };
+            match g.result.as_str() {
+                "1/2-1/2" => c.draws += 1,
+                "*" => {},
+                r if r == won => c.wins += 1,
+                _ => c.losses += 1,
+            }
+            let rating = if color == Color::White { g.white_elo } else { g.black_elo };
+            if rating > 0 {
+                c.rating_sum += rating as u64;
+                c.rated += 1;
+            }
+        }
+        let mut candidates: Vec<Candidate> = found.into_iter().map(|f| f.1).collect();
+        candidates.sort_by_key(|c| Reverse(c.games));
+        return candidates;
+    }
+}
@@ -0,0 +1,424 @@
+pub mod command;
+pub mod explorer;
+pub mod query;
+
+use std::fs;
+use std::io::{stdout, Write};
+use std::path::Path;
+use std::process::Command;
+
+use crate::arena::start_position;
+use crate::book::polyglot::{decode_move, encode_move};
+use crate::chess::color::Color;
+use crate::chess::fen::parse_fen;
+use crate::chess::game::Game;
+use crate::chess::pgn::{play_san, to_san, PgnGame};
+use crate::chess::piece::PieceKind;
+use crate::chess::zobrist;
+use crate::eco;
+
+use query::Query;
+
+// A local database of games imported from PGN, kept compact and indexed for searching. Moves are
+// stored in the 16 bit Polyglot format and replayed from the start position when a game is read
+// back. Every position reached is indexed by its Polyglot hash, sorted so that a position is found
+// by binary search, and every game keeps the material balances it went through for material
+// searches.
+//
+// The file is the magic "JDB2", the number of games as a little-endian u32 and then each game:
+// its tags (white, black, event, date, result, ECO, opening and the start FEN, empty for the
+// initial position) as a u16 length and UTF-8 bytes, the players' ratings as two u16, 0 when not
+// known, the number of moves as a u16 and the moves, and the number of material keys as a u16 and
+// the keys as u64. "JDB1" files are the same without the ratings. After the games comes the
+// position index: the number of entries as a u32, then each entry's hash as a u64, game as a u32
+// and ply as a u16.
+
+pub const DATABASE_FILE: &str = "games.jdb";
+const MAGIC: &[u8; 4] = b"JDB2";
+const MAGIC_V1: &[u8; 4] = b"JDB1";
+
+// Games listed on one page of the database screen.
+pub const PAGE: usize = 15;
+
+pub struct DbGame {
+    pub white: String,
+    pub black: String,
+    pub event: String,
+    pub date: String, // YYYY.MM.DD, with ?? for what is not known
+    pub result: String,
+    pub eco: String,
+    pub opening: String,
+    pub fen: String, // empty for the initial position
+    pub white_elo: u16, // 0 when not known
+    pub black_elo: u16,
+    pub moves: Vec<u16>,
+    pub materials: Vec<u64>, // every material key the game went through, each once
+}
+
+// The number of each kind of piece for both sides, in four bits each: queens, rooks, bishops,
+// knights and pawns for white, then the same for black.
+pub fn material_key(game: &Game) -> u64 {
+    let mut key = 0;
+    for f in 0..8 {
+        for r in 0..8 {
+            let p = game.piece_at(f, r);
+            if let Some(i) = material_index(p.kind) {
+                key += 1 << (4 * (i + if p.color == Color::White { 0 } else { 5 }));
+            }
+        }
+    }
+    return key;
+}
+
+pub fn material_index(kind: PieceKind) -> Option<usize> {
+    return match kind {
+        PieceKind::Queen => Some(0),
+        PieceKind::Rook => Some(1),
+        PieceKind::Bishop => Some(2),
+        PieceKind::Knight => Some(3),
+        PieceKind::Pawn => Some(4),
+        _ => None,
+    };
+}
+
+impl DbGame {
+    fn start(&self) -> Game {
+        return start_position(if self.fen.is_empty() { None } else { Some(&self.fen) });
+    }
+
+    // Every position of the game in order, the start position first.
+    pub fn positions(&self) -> Vec<Game> {
+        let mut game = self.start();
+        let mut positions = vec![game.clone()];
+        for code in &self.moves {
+            let m = match decode_move(&game, *code) {
+                Some(m) => m,
+                None => break,
+            };
+            game.do_move(m);
+            game.next_turn();
+            positions.push(game.clone());
+        }
+        return positions;
+    }
+
+    pub fn to_pgn(&self) -> PgnGame {
+        let mut pgn = PgnGame::new();
+        let tag = |value: &str| if value.is_empty() { String::from("?") } else { value.to_string() };
+        pgn.tags = vec![
+            (String::from("Event"), tag(&self.event)),
+            (String::from("Date"), tag(&self.date)),
+            (String::from("White"), tag(&self.white)),
+            (String::from("Black"), tag(&self.black)),
+            (String::from("Result"), self.result.clone()),
+        ];
+        if !self.eco.is_empty() {
+            pgn.tags.push((String::from("ECO"), self.eco.clone()));
+        }
+        if !self.opening.is_empty() {
+            pgn.tags.push((String::from("Opening"), self.opening.clone()));
+        }
+        if self.white_elo > 0 {
+            pgn.tags.push((String::from("WhiteElo"), self.white_elo.to_string()));
+        }
+        if self.black_elo > 0 {
+            pgn.tags.push((String::from("BlackElo"), self.black_elo.to_string()));
+        }
+        if !self.fen.is_empty() {
+            pgn.tags.push((String::from("SetUp"), String::from("1")));
+            pgn.tags.push((String::from("FEN"), self.fen.clone()));
+        }
+        let mut game = self.start();
+        for code in &self.moves {
+            let m = match decode_move(&game, *code) {
+                Some(m) => m,
+                None => break,
+            };
+            pgn.moves.push(to_san(&game, &m));
+            game.do_move(m);
+            game.next_turn();
+        }
+        pgn.result = self.result.clone();
+        return pgn;
+    }
+}
+
+pub struct Database {
+    pub games: Vec<DbGame>,
+    positions: Vec<(u64, u32, u16)>, // hash, game and ply, sorted
+}
+
+// Reads the file a piece at a time, failing on a file that ends early.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.at + n > self.bytes.len() {
+            return Err(String::from("Database file is cut short"));
+        }
+        self.at += n;
+        return Ok(&self.bytes[self.at - n..self.at]);
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        return Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()));
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        return Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()));
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        return Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()));
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.u16()? as usize;
+        return Ok(String::from_utf8_lossy(self.take(len)?).to_string());
+    }
+}
+
+fn push_string(bytes: &mut Vec<u8>, s: &str) {
+    // Tags longer than a u16 length allows are cut, at a character boundary.
+    let mut end = s.len().min(u16::MAX as usize);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    bytes.extend_from_slice(&(end as u16).to_le_bytes());
+    bytes.extend_from_slice(&s.as_bytes()[..end]);
+}
+
+impl Database {
+    pub fn new() -> Self {
+        return Database{games: Vec::new(), positions: Vec::new()};
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let bytes = match fs::read(path) {
+            Ok(b) => b,
+            Err(e) => return Err(format!("Could not read {}: {}", path.display(), e)),
+        };
+        if bytes.len() < 8 || (&bytes[0..4] != MAGIC && &bytes[0..4] != MAGIC_V1) {
+            return Err(format!("{} is not a jchess database", path.display()));
+        }
+        let rated = &bytes[0..4] == MAGIC;
+        let mut reader = Reader{bytes: &bytes, at: 4};
+        let mut db = Database::new();
+        let count = reader.u32()?;
+        for _ in 0..count {
+            let mut game = DbGame{
+                white: reader.string()?,
+                black: reader.string()?,
+                event: reader.string()?,
+                date: reader.string()?,
+                result: reader.string()?,
+                eco: reader.string()?,
+                opening: reader.string()?,
+                fen: reader.string()?,
+                white_elo: 0,
+                black_elo: 0,
+                moves: Vec::new(),
+                materials: Vec::new(),
+            };
+            if rated {
+                game.white_elo = reader.u16()?;
+                game.black_elo = reader.u16()?;
+            }
+            for _ in 0..reader.u16()? {
+                game.moves.push(reader.u16()?);
+            }
+            for _ in 0..reader.u16()? {
+                game.materials.push(reader.u64()?);
+            }
+            db.games.push(game);
+        }
+        let entries = reader.u32()?;
+        db.positions.reserve(entries as usize);
+        for _ in 0..entries {
+            db.positions.push((reader.u64()?, reader.u32()?, reader.u16()?));
+        }
+        return Ok(db);
+    }
+
+    // Missing databases are empty; one that does not read is an error, so that an import does not
+    // write over it.
+    pub fn load_or_new(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            return Ok(Database::new());
+        }
+        return Database::load(path);
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&(self.games.len() as u32).to_le_bytes());
+        for g in &self.games {
+            for s in [&g.white, &g.black, &g.event, &g.date, &g.result, &g.eco, &g.opening, &g.fen] {
+                push_string(&mut bytes, s);
+            }
+            bytes.extend_from_slice(&g.white_elo.to_le_bytes());
+            bytes.extend_from_slice(&g.black_elo.to_le_bytes());
+            bytes.extend_from_slice(&(g.moves.len() as u16).to_le_bytes());
+            for m in &g.moves {
+                bytes.extend_from_slice(&m.to_le_bytes());
+            }
+            bytes.extend_from_slice(&(g.materials.len() as u16).to_le_bytes());
+            for k in &g.materials {
+                bytes.extend_from_slice(&k.to_le_bytes());
+            }
+        }
+        bytes.extend_from_slice(&(self.positions.len() as u32).to_le_bytes());
+        for (hash, game, ply) in &self.positions {
+            bytes.extend_from_slice(&hash.to_le_bytes());
+            bytes.extend_from_slice(&game.to_le_bytes());
+            bytes.extend_from_slice(&ply.to_le_bytes());
+        }
+        // Written next to the database first, so that a failed write leaves the old one.
+        let temp = path.with_extension("tmp");
+        return match fs::write(&temp, bytes).and_then(|_| fs::rename(&temp, path)) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Could not write {}: {}", path.display(), e)),
+        };
+    }
+
+    pub fn position_count(&self) -> usize {
+        return self.positions.len();
+    }
+
+    // Adds a game read from PGN. A move that does not read ends the game there. Returns false for
+    // a game without moves or with a start position that does not read.
+    pub fn add(&mut self, pgn: &PgnGame) -> bool {
+        let fen = pgn.tag("FEN").unwrap_or_default();
+        let mut game = if fen.is_empty() {
+            start_position(None)
+        } else {
+            match parse_fen(&fen) {
+                Ok(g) => g,
+                Err(_) => return false,
+            }
+        };
+        let id = self.games.len() as u32;
+        let tag = |name: &str| pgn.tag(name).filter(|v| v != "?").unwrap_or_default();
+        let mut record = DbGame{
+            white: tag("White"),
+            black: tag("Black"),
+            event: tag("Event"),
+            date: tag("Date"),
+            result: pgn.result.clone(),
+            eco: tag("ECO"),
+            opening: match (tag("Opening"), tag("Variation")) {
+                (o, v) if v.is_empty() => o,
+                (o, v) => format!("{}, {}", o, v),
+            },
+            fen: fen,
+            white_elo: tag("WhiteElo").parse().unwrap_or(0),
+            black_elo: tag("BlackElo").parse().unwrap_or(0),
+            moves: Vec::new(),
+            materials: vec![material_key(&game)],
+        };
+        let mut positions = vec![(zobrist::hash(&game), id, 0)];
+        let mut opening = None;
+        for san in pgn.moves.iter().take(u16::MAX as usize - 1) {
+            let color = game.current_color();
+            let m = match play_san(&mut game, san) {
+                Ok(m) => m,
+                Err(_) => break,
+            };
+            record.moves.push(encode_move(&m, color));
+            positions.push((zobrist::hash(&game), id, record.moves.len() as u16));
+            let key = material_key(&game);
+            if !record.materials.contains(&key) {
+                record.materials.push(key);
+            }
+            if record.fen.is_empty() {
+                opening = eco::lookup(&game).or(opening);
+            }
+        }
+        // Games without an ECO tag are classified from the table.
+        if let (true, Some(o)) = (record.eco.is_empty(), opening) {
+            record.eco = o.code.to_string();
+            record.opening = o.name.to_string();
+        }
+        if record.moves.len() == 0 {
+            return false;
+        }
+        self.games.push(record);
+        self.positions.extend(positions);
+        return true;
+    }
+
+    // Sorts the position index after games were added.
+    pub fn reindex(&mut self) {
+        self.positions.sort_unstable();
+    }
+
+    // The games and plies where the position with this hash came up, in game order.
+    pub fn find_position(&self, hash: u64) -> Vec<(usize, usize)> {
+        let start = self.positions.partition_point(|e| e.0 < hash);
+        let mut found: Vec<(usize, usize)> = self.positions[start..].iter().take_while(|e| e.0 == hash)
+            .map(|e| (e.1 as usize, e.2 as usize)).collect();
+        found.sort_unstable();
+        // Only the first time in each game.
+        found.dedup_by_key(|f| f.0);
+        return found;
+    }
+
+    // The games matching the query, in the order they were imported, with the ply to show each one
+    // from: where the position or the material was first reached, or the start.
+    pub fn search(&self, query: &Query) -> Vec<(usize, usize)> {
+        let candidates: Vec<(usize, usize)> = match query.position {
+            Some(hash) => self.find_position(hash),
+            None => (0..self.games.len()).map(|g| (g, 0)).collect(),
+        };
+        let mut found = Vec::new();
+        for (id, ply) in candidates {
+            let game = &self.games[id];
+            if !query.matches_tags(game) {
+                continue;
+            }
+            let ply = match &query.material {
+                Some(pattern) if !game.materials.iter().any(|k| pattern.matches(*k)) => continue,
+                // Where the material came up, unless a position was asked for too.
+                Some(pattern) if query.position.is_none() => {
+                    game.positions().iter().position(|p| pattern.matches(material_key(p))).unwrap_or(0)
+                },
+                _ => ply,
+            };
+            found.push((id, ply));
+        }
+        return found;
+    }
+}
+
+// One page of the games found, as (game, ply) pairs from `Database::search`.
+pub fn print_list(db: &Database, found: &[(usize, usize)], page: usize, query: &str, message: &str) {
+    let _ = Command::new("clear").status();
+    let cut = |s: &str, n: usize| s.chars().take(n).collect::<String>();
+    println!();
+    if query.is_empty() {
+        println!("  Game Database, {} games", db.games.len());
+    } else {
+        println!("  Game Database, {} of {} games matching \"{}\"", found.len(), db.games.len(), query);
+    }
+    println!();
+    if found.len() == 0 {
+        println!("  No games");
+    } else {
+        println!("  {:>6}  {:<10}  {:<16}  {:<16}  {:<7}  {:>5}  {:<3}  Opening", "#", "Date", "White", "Black", "Result", "Moves", "ECO");
+        for (id, _) in found.iter().skip(page * PAGE).take(PAGE) {
+            let g = &db.games[*id];
+            println!("  {:>6}  {:<10}  {:<16}  {:<16}  {:<7}  {:>5}  {:<3}  {}", id + 1, g.date, cut(&g.white, 16), cut(&g.black, 16),
+                g.result, g.moves.len().div_ceil(2), g.eco, cut(&g.opening, 24));
+        }
+        println!();
+        println!("  Page {} of {}", page + 1, found.len().div_ceil(PAGE));
+    }
+    println!();
+    println!("  {}", message);
+    print!("  > ");
+    let _ = stdout().flush();
+}
@@ -0,0 +1,245 @@
+use crate::chess::fen::parse_fen;
+use crate::chess::piece::PieceKind;
+use crate::chess::zobrist;
+
+use super::{material_index, DbGame};
+
+// What to look for in the database. Every part that is given has to match: names and the opening
+// ignore case and match any part of the tag, the date and the ECO code match from the start or
+// fall in a range, the position has to come up somewhere in the game and the material has to be
+// on the board at some point.
+
+pub const FIELDS: [&str; 9] = ["player", "white", "black", "result", "date", "eco", "opening", "fen", "material"];
+
+// Material on the board, such as "R vs B" or "KRP vs KR": white's pieces before "vs", black's
+// after. Kings can be left out. Pawns only count when some are named on either side, so "R vs B"
+// matches with any pawns.
+#[derive(Clone)]
+pub struct MaterialPattern {
+    key: u64,
+    mask: u64,
+    text: String,
+}
+
+impl MaterialPattern {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let lower = text.to_lowercase();
+        let sides: Vec<&str> = if lower.contains("vs") {
+            lower.split("vs").collect()
+        } else {
+            lower.split(" v ").collect()
+        };
+        if sides.len() != 2 {
+            return Err(format!("Material should be white's pieces, \"vs\" and black's, like \"R vs B\": {}", text));
+        }
+        let mut key = 0u64;
+        let mut pawns = false;
+        for (side, pieces) in sides.iter().enumerate() {
+            let mut counts = [0u64; 5];
+            for c in pieces.chars().filter(|c| !c.is_whitespace()) {
+                let kind = match c {
+                    'k' => continue,
+                    'q' => PieceKind::Queen,
+                    'r' => PieceKind::Rook,
+                    'b' => PieceKind::Bishop,
+                    'n' => PieceKind::Knight,
+                    'p' => PieceKind::Pawn,
+                    _ => return Err(format!("Unknown piece '{}' in {}", c, text)),
+                };
+                let i = material_index(kind).unwrap();
+                pawns |= kind == PieceKind::Pawn;
+                counts[i] += 1;
+                if counts[i] > 15 {
+                    return Err(format!("Too many pieces in {}", text));
+                }
+            }
+            for (i, n) in counts.iter().enumerate() {
+                key |= n << (4 * (i + 5 * side));
+            }
+        }
+        let mut mask = 0u64;
+        for side in 0..2 {
+            for i in 0..5 {
+                if i != 4 || pawns {
+                    mask |= 0xf << (4 * (i + 5 * side));
+                }
+            }
+        }
+        return Ok(MaterialPattern{key: key, mask: mask, text: text.trim().to_string()});
+    }
+
+    pub fn matches(&self, key: u64) -> bool {
+        return key & self.mask == self.key;
+    }
+}
+
+#[derive(Clone)]
+pub struct Query {
+    pub player: Option<String>,
+    pub white: Option<String>,
+    pub black: Option<String>,
+    pub result: Option<String>,
+    pub date: Option<String>, // a prefix such as "2024.05", or a range "FROM..TO"
+    pub eco: Option<String>,  // a prefix such as "B", or a range "B20-B99"
+    pub opening: Option<String>,
+    pub fen: Option<String>,
+    pub position: Option<u64>, // the hash of the FEN's position
+    pub material: Option<MaterialPattern>,
+}
+
+// Whether the value falls in the range, compared as text and with the end taken as a prefix, or
+// starts with the pattern when it is not a range.
+fn in_range(value: &str, pattern: &str, separator: &str) -> bool {
+    let value = value.to_lowercase();
+    let pattern = pattern.to_lowercase();
+    return match pattern.split_once(separator) {
+        Some((from, to)) => {
+            (from.is_empty() || value.as_str() >= from)
+                && (to.is_empty() || value.as_str() <= to || value.starts_with(to))
+        },
+        None => value.starts_with(&pattern),
+    };
+}
+
+fn contains(value: &str, pattern: &Option<String>) -> bool {
+    return match pattern {
+        Some(p) => value.to_lowercase().contains(&p.to_lowercase()),
+        None => true,
+    };
+}
+
+impl Query {
+    pub fn new() -> Self {
+        return Query{
+            player: None,
+            white: None,
+            black: None,
+            result: None,
+            date: None,
+            eco: None,
+            opening: None,
+            fen: None,
+            position: None,
+            material: None,
+        };
+    }
+
+    // Sets one of the FIELDS; an empty value clears it.
+    pub fn set(&mut self, field: &str, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        let text = if value.is_empty() { None } else { Some(value.to_string()) };
+        match field {
+            "player" => self.player = text,
+            "white" => self.white = text,
+            "black" => self.black = text,
+            "date" => self.date = text,
+            "eco" => self.eco = text,
+            "opening" => self.opening = text,
+            "result" => {
+                self.result = match value {
+                    "" => None,
+                    "1-0" | "0-1" | "1/2-1/2" | "*" => text,
+                    "1/2" | "draw" => Some(String::from("1/2-1/2")),
+                    _ => return Err(format!("Results are 1-0, 0-1, 1/2-1/2 or *: {}", value)),
+                };
+            },
+            "fen" => {
+                self.position = match value {
+                    "" => None,
+                    _ => Some(zobrist::hash(&parse_fen(value)?)),
+                };
+                self.fen = text;
+            },
+            "material" => {
+                self.material = match value {
+                    "" => None,
+                    _ => Some(MaterialPattern::parse(value)?),
+                };
+            },
+            _ => return Err(format!("Unknown search field: {}, fields are {}", field, FIELDS.join(", "))),
+        }
+        return Ok(());
+    }
+
+    // A query typed as `field:value` words, such as "player:carlsen result:1-0 material:R vs B".
+    // Words without a field carry on the value before them, and a FEN takes the rest of the line.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut query = Query::new();
+        let mut parts: Vec<(String, String)> = Vec::new();
+        let mut rest = text.trim();
+        while !rest.is_empty() {
+            let (word, after) = match rest.split_once(char::is_whitespace) {
+                Some((w, a)) => (w, a.trim_start()),
+                None => (rest, ""),
+            };
+            match word.split_once(':') {
+                Some((field, value)) if FIELDS.contains(&field.to_lowercase().as_str()) => {
+                    let field = field.to_lowercase();
+                    if field == "fen" {
+                        parts.push((field, format!("{} {}", value, after)));
+                        break;
+                    }
+                    parts.push((field, value.to_string()));
+                },
+                _ => match parts.last_mut() {
+                    Some(part) => {
+                        part.1.push(' ');
+                        part.1.push_str(word);
+                    },
+                    None => return Err(format!("Search words are field:value, fields are {}", FIELDS.join(", "))),
+                },
+            }
+            rest = after;
+        }
+        for (field, value) in parts {
+            query.set(&field, &value)?;
+        }
+        return Ok(query);
+    }
+
+    pub fn matches_tags(&self, game: &DbGame) -> bool {
+        if let Some(p) = &self.player {
+            let p = p.to_lowercase();
+            if !game.white.to_lowercase().contains(&p) && !game.black.to_lowercase().contains(&p) {
+                return false;
+            }
+        }
+        if let Some(r) = &self.result {
+            if &game.result != r {
+                return false;
+            }
+        }
+        if let Some(d) = &self.date {
+            if !in_range(&game.date, d, "..") {
+                return false;
+            }
+        }
+        if let Some(e) = &self.eco {
+            if game.eco.is_empty() || !in_range(&game.eco, e, "-") {
+                return false;
+            }
+        }
+        return contains(&game.white, &self.white) && contains(&game.black, &self.black)
+            && contains(&game.opening, &self.opening);
+    }
+
+    // The query as it would be typed, fields in a fixed order.
+    pub fn to_string(&self) -> String {
+        let values = [
+            &self.player, &self.white, &self.black, &self.result, &self.date, &self.eco, &self.opening, &self.fen,
+            &self.material.as_ref().map(|m| m.text.clone()),
+        ];
+        let mut parts = Vec::new();
+        for (field, value) in FIELDS.iter().zip(values) {
+            if let Some(v) = value {
+                parts.push(format!("{}:{}", field, v));
+            }
+        }
+        // The FEN takes the rest of the line, so it goes last.
+        if let Some(i) = parts.iter().position(|p| p.starts_with("fen:")) {
+            let fen = parts.remove(i);
+            parts.push(fen);
+        }
+        return parts.join(" ");
+    }
+}
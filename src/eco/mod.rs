@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::arena::start_position;
+use crate::chess::game::Game;
+use crate::chess::pgn::play_san;
+use crate::chess::r#move::Move;
+use crate::chess::zobrist;
+
+// Opening names by ECO code. The table is built into the binary as lines of code, name and moves
+// from the initial position, and is looked up by the hash of the position each line ends in, so a
+// game that reaches a named position by another move order is still recognized. A game is named
+// after the last position it went through that is in the table, which is the longest line it
+// matches. When two lines end in the same position the first one in the table is used.
+
+const TABLE: &str = include_str!("eco.tsv");
+
+pub struct Opening {
+    pub code: &'static str,
+    pub name: &'static str,
+}
+
+impl Opening {
+    pub fn to_string(&self) -> String {
+        return format!("{} {}", self.code, self.name);
+    }
+}
+
+fn table() -> &'static HashMap<u64, Opening> {
+    static OPENINGS: OnceLock<HashMap<u64, Opening>> = OnceLock::new();
+    return OPENINGS.get_or_init(|| {
+        let mut openings = HashMap::new();
+        for line in TABLE.lines().filter(|l| !l.starts_with('#') && !l.trim().is_empty()) {
+            let fields: Vec<&'static str> = line.split('\t').collect();
+            if fields.len() != 3 {
+                continue;
+            }
+            let mut game = start_position(None);
+            if fields[2].split_whitespace().any(|san| play_san(&mut game, san).is_err()) {
+                continue;
+            }
+            openings.entry(zobrist::hash(&game)).or_insert(Opening{code: fields[0], name: fields[1]});
+        }
+        return openings;
+    });
+}
+
+// The opening the position is named after, if it is in the table.
+pub fn lookup(game: &Game) -> Option<&'static Opening> {
+    return table().get(&zobrist::hash(game));
+}
+
+// The opening of a game played from the initial position with these moves.
+pub fn classify(moves: &[Move]) -> Option<&'static Opening> {
+    let mut game = start_position(None);
+    let mut found = None;
+    for m in moves {
+        game.do_move(*m);
+        game.next_turn();
+        found = lookup(&game).or(found);
+    }
+    return found;
+}
@@ -0,0 +1,339 @@
+pub mod weights;
+pub mod nnue;
+pub mod tune;
+
+use crate::chess::color::Color;
+use crate::chess::game::Game;
+use crate::chess::piece::*;
+
+use weights::*;
+
+// Handcrafted evaluation over Game's board. Each term is scored separately for both sides as a
+// middlegame/endgame pair and tapered by the amount of material left on the board.
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Term {
+    Material,
+    Pst,
+    PawnStructure,
+    KingSafety,
+    Mobility,
+    Pieces,
+}
+
+pub const TERMS: [Term; 6] = [Term::Material, Term::Pst, Term::PawnStructure, Term::KingSafety, Term::Mobility, Term::Pieces];
+
+impl Term {
+    pub fn to_string(self) -> String {
+        match self {
+            Term::Material => "Material".to_string(),
+            Term::Pst => "PST".to_string(),
+            Term::PawnStructure => "Pawns".to_string(),
+            Term::KingSafety => "King Safety".to_string(),
+            Term::Mobility => "Mobility".to_string(),
+            Term::Pieces => "Pieces".to_string(),
+        }
+    }
+}
+
+fn color_index(c: Color) -> usize {
+    return if c == Color::White { 0 } else { 1 };
+}
+
+fn opposite(c: Color) -> Color {
+    return if c == Color::White { Color::Black } else { Color::White };
+}
+
+// Rank index counted from the given color's side of the board
+fn relative_rank(c: Color, r: usize) -> usize {
+    return if c == Color::White { r } else { 7 - r };
+}
+
+// The evaluation of a position, term by term and side by side.
+pub struct Breakdown {
+    pub phase: i32,
+    scores: [[Score; 2]; 6],
+}
+
+impl Breakdown {
+    fn add(&mut self, term: Term, c: Color, s: Score) {
+        self.scores[term as usize][color_index(c)] += s;
+    }
+
+    // The tapered score one side collects for a term
+    pub fn side(&self, term: Term, c: Color) -> i32 {
+        return self.scores[term as usize][color_index(c)].taper(self.phase);
+    }
+
+    // White's score minus black's score for a term
+    pub fn net(&self, term: Term) -> i32 {
+        return (self.scores[term as usize][0] - self.scores[term as usize][1]).taper(self.phase);
+    }
+
+    // Total from white's point of view
+    pub fn total(&self) -> i32 {
+        let mut sum = Score::zero();
+        for t in TERMS {
+            sum += self.scores[t as usize][0] - self.scores[t as usize][1];
+        }
+        return sum.taper(self.phase);
+    }
+}
+
+// A copy of the board so that the terms do not have to go through Game for every square.
+struct Position {
+    board: [[Piece; 8]; 8],
+    pawns: [[u8; 8]; 2], // pawn count per file, per color
+}
+
+impl Position {
+    fn from_game(game: &Game) -> Self {
+        let mut pos = Position {
+            board: [[Piece::make(PieceKind::None, Color::White, false, 0); 8]; 8],
+            pawns: [[0; 8]; 2],
+        };
+        for f in 0..8 {
+            for r in 0..8 {
+                let p = game.piece_at(f, r);
+                pos.board[f][r] = p;
+                if p.kind == PieceKind::Pawn {
+                    pos.pawns[color_index(p.color)][f] += 1;
+                }
+            }
+        }
+        return pos;
+    }
+
+    fn is(&self, f: i32, r: i32, kind: PieceKind, c: Color) -> bool {
+        if !(0..8).contains(&f) || !(0..8).contains(&r) {
+            return false;
+        }
+        let p = self.board[f as usize][r as usize];
+        return p.kind == kind && p.color == c;
+    }
+
+    fn pawns_on(&self, c: Color, f: i32) -> u8 {
+        if !(0..8).contains(&f) {
+            return 0;
+        }
+        return self.pawns[color_index(c)][f as usize];
+    }
+
+    // True if a pawn of color c stands on file f strictly ahead of rank r, as seen by the given side.
+    fn pawn_ahead(&self, c: Color, f: i32, r: usize, side: Color) -> bool {
+        if !(0..8).contains(&f) {
+            return false;
+        }
+        for rr in 0..8 {
+            if relative_rank(side, rr) > relative_rank(side, r) && self.is(f, rr as i32, PieceKind::Pawn, c) {
+                return true;
+            }
+        }
+        return false;
+    }
+
+    // Squares attacked by the piece on (f, r), regardless of what stands on them.
+    fn attacks(&self, f: usize, r: usize) -> Vec<(usize, usize)> {
+        let p = self.board[f][r];
+        let mut squares = Vec::new();
+        let (fi, ri) = (f as i32, r as i32);
+        match p.kind {
+            PieceKind::Pawn => {
+                let dir = if p.color == Color::White { 1 } else { -1 };
+                for df in [-1, 1] {
+                    self.push_square(&mut squares, fi + df, ri + dir);
+                }
+            },
+            PieceKind::Knight => {
+                for (df, dr) in [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)] {
+                    self.push_square(&mut squares, fi + df, ri + dr);
+                }
+            },
+            PieceKind::King => {
+                for (df, dr) in [(1, 1), (1, 0), (1, -1), (0, -1), (-1, -1), (-1, 0), (-1, 1), (0, 1)] {
+                    self.push_square(&mut squares, fi + df, ri + dr);
+                }
+            },
+            PieceKind::Bishop => self.slide(&mut squares, fi, ri, &[(1, 1), (1, -1), (-1, -1), (-1, 1)]),
+            PieceKind::Rook => self.slide(&mut squares, fi, ri, &[(1, 0), (0, -1), (-1, 0), (0, 1)]),
+            PieceKind::Queen => {
+                self.slide(&mut squares, fi, ri, &[(1, 1), (1, -1), (-1, -1), (-1, 1)]);
+                self.slide(&mut squares, fi, ri, &[(1, 0), (0, -1), (-1, 0), (0, 1)]);
+            },
+            PieceKind::None => {},
+        }
+        return squares;
+    }
+
+    fn push_square(&self, squares: &mut Vec<(usize, usize)>, f: i32, r: i32) {
+        if (0..8).contains(&f) && (0..8).contains(&r) {
+            squares.push((f as usize, r as usize));
+        }
+    }
+
+    fn slide(&self, squares: &mut Vec<(usize, usize)>, f: i32, r: i32, dirs: &[(i32, i32)]) {
+        for (df, dr) in dirs {
+            let mut tf = f + df;
+            let mut tr = r + dr;
+            while (0..8).contains(&tf) && (0..8).contains(&tr) {
+                squares.push((tf as usize, tr as usize));
+                if self.board[tf as usize][tr as usize].kind != PieceKind::None {
+                    break;
+                }
+                tf += df;
+                tr += dr;
+            }
+        }
+    }
+}
+
+pub fn breakdown(game: &Game, w: &Weights) -> Breakdown {
+    let pos = Position::from_game(game);
+    let mut b = Breakdown{phase: 0, scores: [[Score::zero(); 2]; 6]};
+    let mut bishops = [0; 2];
+    let mut kings = [(0, 0); 2];
+    let zones = [kings_zone(&pos, Color::White), kings_zone(&pos, Color::Black)];
+
+    for f in 0..8 {
+        for r in 0..8 {
+            let p = pos.board[f][r];
+            if p.kind == PieceKind::None {
+                continue;
+            }
+            let k = kind_index(p.kind);
+            let sq = relative_rank(p.color, r) * 8 + f;
+            b.phase += phase_value(p.kind);
+            b.add(Term::Material, p.color, w.material[k]);
+            b.add(Term::Pst, p.color, w.pst[k][sq]);
+
+            match p.kind {
+                PieceKind::Pawn => pawn_terms(&pos, w, &mut b, f, r),
+                PieceKind::King => kings[color_index(p.color)] = (f, r),
+                PieceKind::Bishop => bishops[color_index(p.color)] += 1,
+                _ => {},
+            }
+            if p.kind == PieceKind::Rook {
+                rook_terms(&pos, w, &mut b, f, p.color);
+            }
+            if p.kind == PieceKind::Knight || p.kind == PieceKind::Bishop {
+                outpost_terms(&pos, w, &mut b, f, r);
+            }
+            if p.kind != PieceKind::Pawn && p.kind != PieceKind::King {
+                mobility_terms(&pos, w, &mut b, f, r, &zones[color_index(opposite(p.color))]);
+            }
+        }
+    }
+
+    for c in [Color::White, Color::Black] {
+        if bishops[color_index(c)] >= 2 {
+            b.add(Term::Pieces, c, w.bishop_pair);
+        }
+        let (kf, kr) = kings[color_index(c)];
+        shield_terms(&pos, w, &mut b, kf, kr, c);
+    }
+
+    if b.phase > MAX_PHASE {
+        b.phase = MAX_PHASE;
+    }
+    return b;
+}
+
+// Score from the point of view of the side to move.
+pub fn evaluate(game: &Game, w: &Weights) -> i32 {
+    let total = breakdown(game, w).total();
+    return if game.current_color() == Color::White { total } else { -total };
+}
+
+fn pawn_terms(pos: &Position, w: &Weights, b: &mut Breakdown, f: usize, r: usize) {
+    let c = pos.board[f][r].color;
+    let fi = f as i32;
+
+    // Doubled pawns are charged once for each pawn behind the frontmost one.
+    if pos.pawn_ahead(c, fi, r, c) {
+        b.add(Term::PawnStructure, c, w.doubled_pawn);
+    }
+    if pos.pawns_on(c, fi - 1) == 0 && pos.pawns_on(c, fi + 1) == 0 {
+        b.add(Term::PawnStructure, c, w.isolated_pawn);
+    }
+    let enemy = opposite(c);
+    if !pos.pawn_ahead(enemy, fi - 1, r, c) && !pos.pawn_ahead(enemy, fi, r, c) && !pos.pawn_ahead(enemy, fi + 1, r, c) {
+        b.add(Term::PawnStructure, c, w.passed_pawn[relative_rank(c, r)]);
+    }
+}
+
+fn rook_terms(pos: &Position, w: &Weights, b: &mut Breakdown, f: usize, c: Color) {
+    if pos.pawns_on(c, f as i32) > 0 {
+        return;
+    }
+    if pos.pawns_on(opposite(c), f as i32) == 0 {
+        b.add(Term::Pieces, c, w.rook_open_file);
+    } else {
+        b.add(Term::Pieces, c, w.rook_semi_open_file);
+    }
+}
+
+// An outpost is a square in the enemy half, defended by a pawn, that no enemy pawn can ever attack.
+fn outpost_terms(pos: &Position, w: &Weights, b: &mut Breakdown, f: usize, r: usize) {
+    let c = pos.board[f][r].color;
+    let rel = relative_rank(c, r);
+    if !(3..=5).contains(&rel) {
+        return;
+    }
+    let fi = f as i32;
+    let behind = if c == Color::White { r as i32 - 1 } else { r as i32 + 1 };
+    if !pos.is(fi - 1, behind, PieceKind::Pawn, c) && !pos.is(fi + 1, behind, PieceKind::Pawn, c) {
+        return;
+    }
+    let enemy = opposite(c);
+    if pos.pawn_ahead(enemy, fi - 1, r, c) || pos.pawn_ahead(enemy, fi + 1, r, c) {
+        return;
+    }
+    b.add(Term::Pieces, c, w.outpost);
+}
+
+// The king's square and the squares around it.
+fn kings_zone(pos: &Position, c: Color) -> Vec<(usize, usize)> {
+    for f in 0..8 {
+        for r in 0..8 {
+            if pos.board[f][r].kind == PieceKind::King && pos.board[f][r].color == c {
+                let mut zone = pos.attacks(f, r);
+                zone.push((f, r));
+                return zone;
+            }
+        }
+    }
+    return Vec::new();
+}
+
+// Mobility counts the squares a piece attacks that are not held by its own side. A piece that
+// attacks the enemy king's zone also counts against the enemy's king safety.
+fn mobility_terms(pos: &Position, w: &Weights, b: &mut Breakdown, f: usize, r: usize, enemy_zone: &[(usize, usize)]) {
+    let p = pos.board[f][r];
+    let squares = pos.attacks(f, r);
+    let mut count = 0;
+    let mut attacks_king = false;
+    for (tf, tr) in squares {
+        let target = pos.board[tf][tr];
+        if target.kind == PieceKind::None || target.color != p.color {
+            count += 1;
+        }
+        if enemy_zone.contains(&(tf, tr)) {
+            attacks_king = true;
+        }
+    }
+    b.add(Term::Mobility, p.color, w.mobility[kind_index(p.kind)] * count);
+    if attacks_king {
+        b.add(Term::KingSafety, opposite(p.color), w.king_attacker);
+    }
+}
+
+// Pawns on the king's file and the files beside it, one or two ranks in front of the king.
+fn shield_terms(pos: &Position, w: &Weights, b: &mut Breakdown, kf: usize, kr: usize, c: Color) {
+    let dir = if c == Color::White { 1 } else { -1 };
+    for df in [-1, 0, 1] {
+        let f = kf as i32 + df;
+        if pos.is(f, kr as i32 + dir, PieceKind::Pawn, c) || pos.is(f, kr as i32 + 2 * dir, PieceKind::Pawn, c) {
+            b.add(Term::KingSafety, c, w.pawn_shield);
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::fs;
+use std::path::Path;
+
+use crate::chess::color::Color;
+use crate::chess::game::Game;
+use crate::chess::piece::{Piece, PieceKind};
+
+use super::weights::kind_index;
+
+// An efficiently updatable neural network evaluation. The inputs are the 768 piece-square features
+// (own or enemy piece, kind, square) seen from each side in turn, the squares flipped for black so
+// that both sides see their pieces from their own end of the board. Each side has an accumulator
+// holding the hidden layer before activation: the biases plus the weights of every active feature.
+// A move only switches a few features on and off, so the search updates the accumulators instead
+// of recomputing them.
+//
+// The output is the side to move's accumulator followed by the other side's, each clipped to
+// 0..QA, dotted with the output weights, plus the output bias, and scaled to centipawns by
+// SCALE / (QA * QB).
+//
+// Network file, all numbers little-endian:
+//   magic "JNN1"
+//   hidden size H as a u16
+//   feature weights: 768 * H i16, all H weights of feature 0 first, then feature 1 and so on
+//   hidden biases: H i16
+//   output weights: 2 * H i16, the side to move's half first
+//   output bias: i32, in QA * QB units
+// Feature index: (0 own, 1 enemy) * 384 + kind (pawn 0 to king 5) * 64 + rank * 8 + file, with
+// rank counted from the perspective's own side.
+
+pub const NETWORK_FILE: &str = "eval.nnue";
+const MAGIC: &[u8; 4] = b"JNN1";
+const FEATURES: usize = 768;
+const QA: i32 = 255;
+const QB: i32 = 64;
+const SCALE: i32 = 400;
+const MAX_EVAL: i32 = 20000;
+
+pub struct Network {
+    hidden: usize,
+    feature_weights: Vec<i16>,
+    biases: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i32,
+}
+
+// The hidden layer as each side sees it, before the activation.
+#[derive(Clone)]
+pub struct Accumulator {
+    white: Vec<i16>,
+    black: Vec<i16>,
+}
+
+fn feature(perspective: Color, p: Piece, f: usize, r: usize) -> usize {
+    let own = if p.color == perspective { 0 } else { 1 };
+    let rank = if perspective == Color::White { r } else { 7 - r };
+    return own * 384 + kind_index(p.kind) * 64 + rank * 8 + f;
+}
+
+impl Network {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let bytes = match fs::read(path) {
+            Ok(b) => b,
+            Err(e) => return Err(format!("Could not read {}: {}", path.display(), e)),
+        };
+        let bad = format!("{} is not a network file", path.display());
+        if bytes.len() < 6 || &bytes[0..4] != MAGIC {
+            return Err(bad);
+        }
+        let hidden = u16::from_le_bytes([bytes[4], bytes[5]]) as usize;
+        let count = FEATURES * hidden + 3 * hidden;
+        if hidden == 0 || bytes.len() != 6 + 2 * count + 4 {
+            return Err(bad);
+        }
+
+        let values: Vec<i16> = bytes[6..6 + 2 * count].chunks(2).map(|c| i16::from_le_bytes([c[0], c[1]])).collect();
+        let end = &bytes[6 + 2 * count..];
+        let (weights, rest) = values.split_at(FEATURES * hidden);
+        let (biases, output) = rest.split_at(hidden);
+        return Ok(Network{
+            hidden: hidden,
+            feature_weights: weights.to_vec(),
+            biases: biases.to_vec(),
+            output_weights: output.to_vec(),
+            output_bias: i32::from_le_bytes([end[0], end[1], end[2], end[3]]),
+        });
+    }
+
+    fn weights_of(&self, feature: usize) -> &[i16] {
+        return &self.feature_weights[feature * self.hidden..(feature + 1) * self.hidden];
+    }
+
+    fn add(&self, acc: &mut Accumulator, p: Piece, f: usize, r: usize) {
+        for (a, w) in acc.white.iter_mut().zip(self.weights_of(feature(Color::White, p, f, r))) {
+            *a = a.wrapping_add(*w);
+        }
+        for (a, w) in acc.black.iter_mut().zip(self.weights_of(feature(Color::Black, p, f, r))) {
+            *a = a.wrapping_add(*w);
+        }
+    }
+
+    fn remove(&self, acc: &mut Accumulator, p: Piece, f: usize, r: usize) {
+        for (a, w) in acc.white.iter_mut().zip(self.weights_of(feature(Color::White, p, f, r))) {
+            *a = a.wrapping_sub(*w);
+        }
+        for (a, w) in acc.black.iter_mut().zip(self.weights_of(feature(Color::Black, p, f, r))) {
+            *a = a.wrapping_sub(*w);
+        }
+    }
+
+    // Computes the accumulators of a position from scratch.
+    pub fn refresh(&self, game: &Game) -> Accumulator {
+        let mut acc = Accumulator{white: self.biases.clone(), black: self.biases.clone()};
+        for f in 0..8 {
+            for r in 0..8 {
+                let p = game.piece_at(f, r);
+                if p.kind != PieceKind::None {
+                    self.add(&mut acc, p, f, r);
+                }
+            }
+        }
+        return acc;
+    }
+
+    // Brings `acc` from the accumulators of `before` to those of `after`, one move later. Only the
+    // squares that changed are touched, which covers castling, en passant and promotions alike.
+    pub fn update(&self, acc: &mut Accumulator, before: &Game, after: &Game) {
+        for f in 0..8 {
+            for r in 0..8 {
+                let old = before.piece_at(f, r);
+                let new = after.piece_at(f, r);
+                if old.kind == new.kind && old.color == new.color {
+                    continue;
+                }
+                if old.kind != PieceKind::None {
+                    self.remove(acc, old, f, r);
+                }
+                if new.kind != PieceKind::None {
+                    self.add(acc, new, f, r);
+                }
+            }
+        }
+    }
+
+    // Centipawns for the side to move. The sum is kept in i64, since a large enough network would
+    // overflow i32 once scaled, and the result is clamped so that it never reaches mate scores.
+    pub fn evaluate(&self, acc: &Accumulator, to_move: Color) -> i32 {
+        let (us, them) = if to_move == Color::White { (&acc.white, &acc.black) } else { (&acc.black, &acc.white) };
+        let (own_weights, enemy_weights) = self.output_weights.split_at(self.hidden);
+        let mut sum: i64 = 0;
+        for (a, w) in us.iter().zip(own_weights) {
+            sum += ((*a as i32).clamp(0, QA) * *w as i32) as i64;
+        }
+        for (a, w) in them.iter().zip(enemy_weights) {
+            sum += ((*a as i32).clamp(0, QA) * *w as i32) as i64;
+        }
+        let eval = (sum + self.output_bias as i64) * SCALE as i64 / (QA * QB) as i64;
+        return eval.clamp(-MAX_EVAL as i64, MAX_EVAL as i64) as i32;
+    }
+}
+
+impl Accumulator {
+    pub fn copy_from(&mut self, other: &Accumulator) {
+        self.white.clone_from(&other.white);
+        self.black.clone_from(&other.black);
+    }
+}
@@ -0,0 +1,257 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use crate::chess::fen::parse_fen;
+use crate::chess::game::Game;
+use crate::chess::pgn::{parse_pgn, play_san};
+use crate::input::args::Args;
+use crate::storage::data_file;
+
+use super::breakdown;
+use super::evaluate;
+use super::weights::*;
+
+// Tunes the evaluation weights on positions labeled with the result of their game:
+//   jchess tune [--out FILE] [--passes N] [--skip-plies N] [--max-positions N] FILE...
+// Files ending in .pgn have every position of every game taken, after the first few plies; other
+// files hold one position per line, a FEN followed by the result from white's side as 1-0, 0-1,
+// 1/2-1/2, 1.0, 0.5 or 0.0, optionally in brackets or quotes. The `FEN | eval | result` lines
+// written by `jchess selfplay` are read too; the eval is not used.
+//
+// Texel's method: the evaluation is turned into an expected score with a logistic curve, and the
+// mean squared difference to the actual results is minimised by nudging one weight at a time,
+// keeping every change that lowers it. The evaluation is not taken on the position itself but at
+// the end of a capture-only search from it, so that it is never scored in the middle of an
+// exchange. The weights start from the output file and are written back to it after every pass.
+
+const USAGE: &str = "Usage: jchess tune [--out FILE] [--passes N] [--skip-plies N] [--max-positions N] FILE...";
+
+// Captures are followed at most this many plies deep when looking for the quiet position.
+const MAX_QUIET_PLY: u32 = 8;
+const INFINITY: i32 = 1000000;
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &["out", "passes", "skip-plies", "max-positions"], &[], USAGE)?;
+    let passes = args.get_or("passes", 100u32)?;
+    let skip_plies = args.get_or("skip-plies", 8usize)?;
+    let max_positions = args.get_or("max-positions", 0usize)?;
+    let out = match args.get("out") {
+        Some(p) => PathBuf::from(p),
+        None => data_file(WEIGHTS_FILE),
+    };
+    if args.positional.len() == 0 {
+        return Err(String::from(USAGE));
+    }
+
+    let mut weights = if out.exists() { Weights::load(&out)? } else { Weights::new() };
+    let mut positions = Vec::new();
+    for path in &args.positional {
+        let text = match fs::read_to_string(path) {
+            Ok(t) => t,
+            Err(e) => return Err(format!("Could not read {}: {}", path, e)),
+        };
+        if path.to_lowercase().ends_with(".pgn") {
+            read_pgn(&text, skip_plies, &mut positions);
+        } else {
+            read_labeled(&text, &mut positions)?;
+        }
+    }
+    if max_positions > 0 {
+        positions.truncate(max_positions);
+    }
+
+    // The quiet positions are found once, with the starting weights.
+    let positions: Vec<(Game, f64)> = positions.into_iter()
+        .filter(|(g, _)| g.any_valid_moves())
+        .map(|(g, result)| (quiet(&g, &weights, -INFINITY, INFINITY, 0).1, result))
+        .collect();
+    if positions.len() == 0 {
+        return Err(String::from("No positions to tune on"));
+    }
+    println!("{} positions", positions.len());
+
+    let k = fit_k(&positions, &weights);
+    let mut best = error(&positions, &weights, k);
+    println!("K = {:.3}, error {:.6}", k, best);
+
+    let count = weights.params_mut().len();
+    for pass in 1..=passes {
+        let start = Instant::now();
+        let mut changed = 0;
+        for i in 0..count {
+            for endgame in [false, true] {
+                for step in [1, -1] {
+                    nudge(&mut weights, i, endgame, step);
+                    let e = error(&positions, &weights, k);
+                    if e < best {
+                        best = e;
+                        changed += 1;
+                        break;
+                    }
+                    nudge(&mut weights, i, endgame, -step);
+                }
+            }
+        }
+        weights.save(&out)?;
+        println!("Pass {}: error {:.6}, {} weights changed, {:.1}s", pass, best, changed, start.elapsed().as_secs_f64());
+        if changed == 0 {
+            break;
+        }
+    }
+    println!("Wrote {}", out.display());
+    return Ok(());
+}
+
+fn parse_result(text: &str) -> Option<f64> {
+    let text = text.trim().trim_end_matches(';').trim_start_matches("c9").trim();
+    return match text.trim_matches(|c| c == '[' || c == ']' || c == '"') {
+        "1-0" | "1.0" | "1" => Some(1.0),
+        "0-1" | "0.0" | "0" => Some(0.0),
+        "1/2-1/2" | "0.5" => Some(0.5),
+        _ => None,
+    };
+}
+
+// A FEN (four fields, and the two move counters if they are there) followed by the result.
+fn read_labeled(text: &str, positions: &mut Vec<(Game, f64)>) -> Result<(), String> {
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('|').collect();
+        if fields.len() == 3 {
+            let result = parse_result(fields[2]).ok_or(format!("Line {}: bad result", n + 1))?;
+            match parse_fen(fields[0]) {
+                Ok(g) => positions.push((g, result)),
+                Err(e) => return Err(format!("Line {}: {}", n + 1, e)),
+            }
+            continue;
+        }
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let mut fen_len = 4.min(tokens.len());
+        while fen_len < 6 && fen_len + 1 < tokens.len() && tokens[fen_len].parse::<u32>().is_ok() {
+            fen_len += 1;
+        }
+        let result = match parse_result(&tokens[fen_len..].join(" ")) {
+            Some(r) => r,
+            None => return Err(format!("Line {}: expected a FEN followed by a result", n + 1)),
+        };
+        match parse_fen(&tokens[..fen_len].join(" ")) {
+            Ok(g) => positions.push((g, result)),
+            Err(e) => return Err(format!("Line {}: {}", n + 1, e)),
+        }
+    }
+    return Ok(());
+}
+
+// Every position after the first plies of each finished game, up to a move that cannot be read,
+// except those in check.
+fn read_pgn(text: &str, skip_plies: usize, positions: &mut Vec<(Game, f64)>) {
+    for pgn in parse_pgn(text) {
+        let result = match pgn.result.as_str() {
+            "1-0" => 1.0,
+            "0-1" => 0.0,
+            "1/2-1/2" => 0.5,
+            _ => continue,
+        };
+        let mut game = match pgn.tag("FEN") {
+            Some(fen) => match parse_fen(&fen) {
+                Ok(g) => g,
+                Err(_) => continue,
+            },
+            None => {
+                let mut g = Game::new();
+                g.default_board();
+                g
+            },
+        };
+        for (ply, san) in pgn.moves.iter().enumerate() {
+            // A position in check has no quiet evaluation to speak of.
+            if ply >= skip_plies && !game.is_check() {
+                positions.push((game.clone(), result));
+            }
+            if play_san(&mut game, san).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+// A capture-only search that also returns the position its score comes from.
+fn quiet(game: &Game, w: &Weights, mut alpha: i32, beta: i32, ply: u32) -> (i32, Game) {
+    let stand_pat = evaluate(game, w);
+    let mut leaf = game.clone();
+    if stand_pat >= beta || ply >= MAX_QUIET_PLY {
+        return (stand_pat, leaf);
+    }
+    if stand_pat > alpha {
+        alpha = stand_pat;
+    }
+    for m in game.list_valid_moves() {
+        if !m.takes && m.en_passant.is_none() {
+            continue;
+        }
+        let mut next = game.clone();
+        next.do_move(m);
+        next.next_turn();
+        let (score, l) = quiet(&next, w, -beta, -alpha, ply + 1);
+        if -score > alpha {
+            alpha = -score;
+            leaf = l;
+            if alpha >= beta {
+                break;
+            }
+        }
+    }
+    return (alpha, leaf);
+}
+
+fn nudge(weights: &mut Weights, i: usize, endgame: bool, step: i32) {
+    let mut params = weights.params_mut();
+    let s = &mut params[i].1;
+    if endgame {
+        s.1 += step;
+    } else {
+        s.0 += step;
+    }
+}
+
+fn expected(eval: i32, k: f64) -> f64 {
+    return 1.0 / (1.0 + 10f64.powf(-k * eval as f64 / 400.0));
+}
+
+// Mean squared difference between the results and the expected scores, both from white's side.
+fn error(positions: &[(Game, f64)], w: &Weights, k: f64) -> f64 {
+    let mut sum = 0.0;
+    for (game, result) in positions {
+        let eval = breakdown(game, w).total();
+        sum += (result - expected(eval, k)).powi(2);
+    }
+    return sum / positions.len() as f64;
+}
+
+// The scaling constant that fits the starting weights best, so that tuning changes the weights
+// rather than their overall scale.
+fn fit_k(positions: &[(Game, f64)], w: &Weights) -> f64 {
+    let mut k = 1.0;
+    let mut best = error(positions, w, k);
+    for step in [0.1, 0.01, 0.001] {
+        loop {
+            let up = error(positions, w, k + step);
+            let down = error(positions, w, (k - step).max(step));
+            if up < best && up <= down {
+                k += step;
+                best = up;
+            } else if down < best {
+                k = (k - step).max(step);
+                best = down;
+            } else {
+                break;
+            }
+        }
+    }
+    return k;
+}
+
@@ -0,0 +1,312 @@
+use std::fs;
+use std::ops::{Add, AddAssign, Mul, Sub};
+use std::path::Path;
+
+use crate::chess::piece::PieceKind;
+use crate::chess::rankfile::{File, Rank, tuple_to_square};
+
+// Evaluation weights. Every weight is a pair of middlegame and endgame values which the
+// evaluation blends by game phase. The weights can be saved to and loaded from a plain text
+// file with one `name middlegame endgame` entry per line, so that they can be tuned without
+// recompiling.
+
+pub const WEIGHTS_FILE: &str = "eval.weights";
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Score(pub i32, pub i32);
+
+impl Score {
+    pub fn zero() -> Self {
+        return Score(0, 0);
+    }
+
+    // Blend the middlegame and endgame values. Phase runs from 0 (bare kings) to MAX_PHASE.
+    pub fn taper(self, phase: i32) -> i32 {
+        return (self.0 * phase + self.1 * (MAX_PHASE - phase)) / MAX_PHASE;
+    }
+}
+
+impl Add for Score {
+    type Output = Score;
+    fn add(self, other: Score) -> Score {
+        return Score(self.0 + other.0, self.1 + other.1);
+    }
+}
+
+impl Sub for Score {
+    type Output = Score;
+    fn sub(self, other: Score) -> Score {
+        return Score(self.0 - other.0, self.1 - other.1);
+    }
+}
+
+impl AddAssign for Score {
+    fn add_assign(&mut self, other: Score) {
+        self.0 += other.0;
+        self.1 += other.1;
+    }
+}
+
+impl Mul<i32> for Score {
+    type Output = Score;
+    fn mul(self, n: i32) -> Score {
+        return Score(self.0 * n, self.1 * n);
+    }
+}
+
+// Phase contributed by each knight, bishop, rook and queen. The starting position is MAX_PHASE.
+pub const MAX_PHASE: i32 = 24;
+
+pub fn phase_value(kind: PieceKind) -> i32 {
+    return match kind {
+        PieceKind::Knight => 1,
+        PieceKind::Bishop => 1,
+        PieceKind::Rook => 2,
+        PieceKind::Queen => 4,
+        _ => 0,
+    };
+}
+
+// Index into the per-piece tables. Pawn through King, None is not stored.
+pub fn kind_index(kind: PieceKind) -> usize {
+    return match kind {
+        PieceKind::Pawn => 0,
+        PieceKind::Knight => 1,
+        PieceKind::Bishop => 2,
+        PieceKind::Rook => 3,
+        PieceKind::Queen => 4,
+        PieceKind::King => 5,
+        PieceKind::None => 0,
+    };
+}
+
+const KIND_NAMES: [&str; 6] = ["pawn", "knight", "bishop", "rook", "queen", "king"];
+
+#[derive(Clone)]
+pub struct Weights {
+    pub material: [Score; 6],
+    pub pst: [[Score; 64]; 6],       // indexed by rank * 8 + file, from white's side of the board
+    pub doubled_pawn: Score,
+    pub isolated_pawn: Score,
+    pub passed_pawn: [Score; 8],     // by rank, counted from the pawn's own side
+    pub pawn_shield: Score,
+    pub king_attacker: Score,
+    pub mobility: [Score; 6],
+    pub bishop_pair: Score,
+    pub rook_open_file: Score,
+    pub rook_semi_open_file: Score,
+    pub outpost: Score,
+}
+
+// Default piece-square tables, written as seen from white with rank 8 at the top.
+const PAWN_MG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     50,  50,  50,  50,  50,  50,  50,  50,
+     10,  10,  20,  30,  30,  20,  10,  10,
+      5,   5,  10,  25,  25,  10,   5,   5,
+      0,   0,   0,  20,  20,   0,   0,   0,
+      5,  -5, -10,   0,   0, -10,  -5,   5,
+      5,  10,  10, -20, -20,  10,  10,   5,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+const PAWN_EG: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     80,  80,  80,  80,  80,  80,  80,  80,
+     50,  50,  50,  50,  50,  50,  50,  50,
+     30,  30,  30,  30,  30,  30,  30,  30,
+     15,  15,  15,  15,  15,  15,  15,  15,
+      5,   5,   5,   5,   5,   5,   5,   5,
+      0,   0,   0,   0,   0,   0,   0,   0,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+const KNIGHT: [i32; 64] = [
+    -50, -40, -30, -30, -30, -30, -40, -50,
+    -40, -20,   0,   0,   0,   0, -20, -40,
+    -30,   0,  10,  15,  15,  10,   0, -30,
+    -30,   5,  15,  20,  20,  15,   5, -30,
+    -30,   0,  15,  20,  20,  15,   0, -30,
+    -30,   5,  10,  15,  15,  10,   5, -30,
+    -40, -20,   0,   5,   5,   0, -20, -40,
+    -50, -40, -30, -30, -30, -30, -40, -50,
+];
+
+const BISHOP: [i32; 64] = [
+    -20, -10, -10, -10, -10, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,  10,  10,   5,   0, -10,
+    -10,   5,   5,  10,  10,   5,   5, -10,
+    -10,   0,  10,  10,  10,  10,   0, -10,
+    -10,  10,  10,  10,  10,  10,  10, -10,
+    -10,   5,   0,   0,   0,   0,   5, -10,
+    -20, -10, -10, -10, -10, -10, -10, -20,
+];
+
+const ROOK: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+      5,  10,  10,  10,  10,  10,  10,   5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+     -5,   0,   0,   0,   0,   0,   0,  -5,
+      0,   0,   0,   5,   5,   0,   0,   0,
+];
+
+const QUEEN: [i32; 64] = [
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+    -10,   0,   0,   0,   0,   0,   0, -10,
+    -10,   0,   5,   5,   5,   5,   0, -10,
+     -5,   0,   5,   5,   5,   5,   0,  -5,
+      0,   0,   5,   5,   5,   5,   0,  -5,
+    -10,   5,   5,   5,   5,   5,   0, -10,
+    -10,   0,   5,   0,   0,   0,   0, -10,
+    -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+
+const KING_MG: [i32; 64] = [
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -30, -40, -40, -50, -50, -40, -40, -30,
+    -20, -30, -30, -40, -40, -30, -30, -20,
+    -10, -20, -20, -20, -20, -20, -20, -10,
+     20,  20,   0,   0,   0,   0,  20,  20,
+     20,  30,  10,   0,   0,  10,  30,  20,
+];
+
+const KING_EG: [i32; 64] = [
+    -50, -40, -30, -20, -20, -30, -40, -50,
+    -30, -20, -10,   0,   0, -10, -20, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  30,  40,  40,  30, -10, -30,
+    -30, -10,  20,  30,  30,  20, -10, -30,
+    -30, -30,   0,   0,   0,   0, -30, -30,
+    -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+// Turns a table written with rank 8 first into one indexed by rank * 8 + file.
+fn table(mg: &[i32; 64], eg: &[i32; 64]) -> [Score; 64] {
+    let mut t = [Score::zero(); 64];
+    for r in 0..8 {
+        for f in 0..8 {
+            t[r * 8 + f] = Score(mg[(7 - r) * 8 + f], eg[(7 - r) * 8 + f]);
+        }
+    }
+    return t;
+}
+
+fn square_name(sq: usize) -> String {
+    return tuple_to_square((File::from_index(sq % 8), Rank::from_index(sq / 8)));
+}
+
+impl Weights {
+    pub fn new() -> Self {
+        Weights {
+            material: [Score(100, 120), Score(320, 300), Score(330, 320), Score(500, 520), Score(900, 950), Score(0, 0)],
+            pst: [
+                table(&PAWN_MG, &PAWN_EG),
+                table(&KNIGHT, &KNIGHT),
+                table(&BISHOP, &BISHOP),
+                table(&ROOK, &ROOK),
+                table(&QUEEN, &QUEEN),
+                table(&KING_MG, &KING_EG),
+            ],
+            doubled_pawn: Score(-10, -20),
+            isolated_pawn: Score(-10, -15),
+            passed_pawn: [Score(0, 0), Score(5, 10), Score(10, 20), Score(15, 35), Score(25, 60), Score(40, 90), Score(60, 130), Score(0, 0)],
+            pawn_shield: Score(10, 0),
+            king_attacker: Score(-15, -5),
+            mobility: [Score(0, 0), Score(4, 4), Score(5, 5), Score(2, 4), Score(1, 2), Score(0, 0)],
+            bishop_pair: Score(30, 50),
+            rook_open_file: Score(25, 10),
+            rook_semi_open_file: Score(12, 6),
+            outpost: Score(20, 10),
+        }
+    }
+
+    // Every tunable weight along with the name it is saved under.
+    pub fn params_mut(&mut self) -> Vec<(String, &mut Score)> {
+        let mut params: Vec<(String, &mut Score)> = Vec::new();
+        for (i, s) in self.material.iter_mut().enumerate().take(5) {
+            params.push((format!("material.{}", KIND_NAMES[i]), s));
+        }
+        for (i, t) in self.pst.iter_mut().enumerate() {
+            for (sq, s) in t.iter_mut().enumerate() {
+                params.push((format!("pst.{}.{}", KIND_NAMES[i], square_name(sq)), s));
+            }
+        }
+        params.push((String::from("pawn.doubled"), &mut self.doubled_pawn));
+        params.push((String::from("pawn.isolated"), &mut self.isolated_pawn));
+        for (i, s) in self.passed_pawn.iter_mut().enumerate() {
+            params.push((format!("pawn.passed.{}", i + 1), s));
+        }
+        params.push((String::from("king.shield"), &mut self.pawn_shield));
+        params.push((String::from("king.attacker"), &mut self.king_attacker));
+        for (i, s) in self.mobility.iter_mut().enumerate().skip(1).take(4) {
+            params.push((format!("mobility.{}", KIND_NAMES[i]), s));
+        }
+        params.push((String::from("piece.bishop_pair"), &mut self.bishop_pair));
+        params.push((String::from("piece.rook_open_file"), &mut self.rook_open_file));
+        params.push((String::from("piece.rook_semi_open_file"), &mut self.rook_semi_open_file));
+        params.push((String::from("piece.outpost"), &mut self.outpost));
+        return params;
+    }
+
+    // Weights missing from the file keep their default value.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = match fs::read_to_string(path) {
+            Ok(t) => t,
+            Err(e) => return Err(format!("Could not read {}: {}", path.display(), e)),
+        };
+
+        let mut weights = Weights::new();
+        {
+            let mut params = weights.params_mut();
+            for (n, line) in text.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                if parts.len() != 3 {
+                    return Err(format!("Line {}: expected `name middlegame endgame`", n + 1));
+                }
+                let mg = parts[1].parse::<i32>();
+                let eg = parts[2].parse::<i32>();
+                if mg.is_err() || eg.is_err() {
+                    return Err(format!("Line {}: weights must be integers", n + 1));
+                }
+                match params.iter_mut().find(|(name, _)| name == parts[0]) {
+                    Some((_, s)) => **s = Score(mg.unwrap(), eg.unwrap()),
+                    None => return Err(format!("Line {}: unknown weight {}", n + 1, parts[0])),
+                }
+            }
+        }
+        return Ok(weights);
+    }
+
+    // The default weights when there is no file; loading never writes one, which is left to
+    // `jchess tune`. A file that does not read is an error rather than a silent fall back to the
+    // defaults.
+    pub fn load_or_default(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            return Ok(Weights::new());
+        }
+        return Weights::load(path).map_err(|e| format!("{}: {}", path.display(), e));
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let mut copy = self.clone();
+        let mut text = String::from("# jchess evaluation weights: name middlegame endgame\n");
+        for (name, s) in copy.params_mut() {
+            text.push_str(&format!("{} {} {}\n", name, s.0, s.1));
+        }
+        return match fs::write(path, text) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Could not write {}: {}", path.display(), e)),
+        };
+    }
+}
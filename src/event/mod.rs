@@ -0,0 +1,395 @@
+pub mod pairing;
+
+use std::fs;
+use std::io::{stdout, Write};
+use std::path::Path;
+use std::process::Command;
+
+use crate::bots::from_spec;
+use crate::bots::human::Human;
+use crate::chess::color::Color;
+use crate::chess::pgn::today;
+use crate::chess::player::Player;
+
+use pairing::History;
+
+// A tournament for people and bots taking turns at one terminal, such as a lunchtime Swiss. Players
+// are registered before the first round, bots by their spec as the subcommands take them. Each
+// round is paired once the last one has all its results; results come from the games as they end
+// or are typed in. Standings are broken by Buchholz (the opponents' scores added up) and then
+// Sonneborn-Berger (the scores of the opponents beaten, and half those of the opponents drawn).
+// The whole tournament is kept in a small text file so that it can be picked up again later.
+
+pub const EVENT_FILE: &str = "tournament.txt";
+
+// A bye counts as a win.
+const BYE_POINTS: f64 = 1.0;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Format {
+    Swiss(usize), // rounds
+    RoundRobin,
+}
+
+impl Format {
+    // "swiss 5" or "round robin".
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let words: Vec<String> = text.split_whitespace().map(|w| w.to_lowercase()).collect();
+        return match words.iter().map(|w| w.as_str()).collect::<Vec<&str>>().as_slice() {
+            ["swiss", n] => match n.parse::<usize>() {
+                Ok(r) if r >= 1 => Ok(Format::Swiss(r)),
+                _ => Err(format!("Not a number of rounds: {}", n)),
+            },
+            ["round", "robin"] | ["roundrobin"] => Ok(Format::RoundRobin),
+            _ => Err(String::from("Format must be swiss N or round robin")),
+        };
+    }
+
+    pub fn to_string(self) -> String {
+        return match self {
+            Format::Swiss(r) => format!("Swiss, {} rounds", r),
+            Format::RoundRobin => String::from("Round robin"),
+        };
+    }
+}
+
+pub struct Entrant {
+    pub name: String,
+    pub bot: Option<String>, // the bot's spec; None for a person
+}
+
+impl Entrant {
+    pub fn player(&self) -> Result<Box<dyn Player>, String> {
+        return match &self.bot {
+            Some(spec) => from_spec(spec),
+            None => Ok(Box::new(Human::named(&self.name))),
+        };
+    }
+}
+
+#[derive(Clone)]
+pub struct Board {
+    pub white: usize,
+    pub black: usize,
+    pub result: Option<String>, // "1-0", "0-1" or "1/2-1/2"
+}
+
+pub struct Round {
+    pub boards: Vec<Board>,
+    pub bye: Option<usize>,
+}
+
+pub struct Standing {
+    pub player: usize,
+    pub score: f64,
+    pub buchholz: f64,
+    pub sonneborn_berger: f64,
+}
+
+pub struct Event {
+    pub name: String,
+    pub format: Format,
+    pub players: Vec<Entrant>,
+    pub rounds: Vec<Round>,
+}
+
+// "1-0", "0-1" and "1/2-1/2", with 1/2 and = taken for a draw.
+fn parse_result(text: &str) -> Option<&'static str> {
+    return match text {
+        "1-0" => Some("1-0"),
+        "0-1" => Some("0-1"),
+        "1/2-1/2" | "1/2" | "=" => Some("1/2-1/2"),
+        _ => None,
+    };
+}
+
+fn white_points(result: &str) -> f64 {
+    return match result {
+        "1-0" => 1.0,
+        "0-1" => 0.0,
+        _ => 0.5,
+    };
+}
+
+impl Event {
+    pub fn new(name: &str) -> Self {
+        return Event{name: name.to_string(), format: Format::Swiss(5), players: Vec::new(), rounds: Vec::new()};
+    }
+
+    pub fn default_name() -> String {
+        return format!("Tournament {}", today());
+    }
+
+    pub fn total_rounds(&self) -> usize {
+        return match self.format {
+            Format::Swiss(r) => r,
+            Format::RoundRobin => (self.players.len() + self.players.len() % 2).max(1) - 1,
+        };
+    }
+
+    fn round_complete(&self) -> bool {
+        return match self.rounds.last() {
+            Some(r) => r.boards.iter().all(|b| b.result.is_some()),
+            None => true,
+        };
+    }
+
+    pub fn finished(&self) -> bool {
+        return self.rounds.len() > 0 && self.rounds.len() >= self.total_rounds() && self.round_complete();
+    }
+
+    pub fn add_player(&mut self, name: &str, bot: Option<String>) -> Result<(), String> {
+        let name = name.trim();
+        if self.rounds.len() > 0 {
+            return Err(String::from("Players can only join before the first round"));
+        }
+        if name.is_empty() || name.contains('\t') {
+            return Err(String::from("A player needs a name"));
+        }
+        if self.players.iter().any(|p| p.name.eq_ignore_ascii_case(name)) {
+            return Err(format!("There is already a player called {}", name));
+        }
+        if let Some(spec) = &bot {
+            from_spec(spec)?;
+        }
+        self.players.push(Entrant{name: name.to_string(), bot: bot});
+        return Ok(());
+    }
+
+    pub fn set_format(&mut self, format: Format) -> Result<(), String> {
+        if self.rounds.len() > 0 {
+            return Err(String::from("The format is fixed once the first round is paired"));
+        }
+        self.format = format;
+        return Ok(());
+    }
+
+    // The first board of the current round still to be played.
+    pub fn next_board(&self) -> Option<usize> {
+        return self.rounds.last()?.boards.iter().position(|b| b.result.is_none());
+    }
+
+    // Records a result on a board of the current round, replacing any there was.
+    pub fn record(&mut self, board: usize, result: &str) -> Result<(), String> {
+        let result = parse_result(result).ok_or(format!("Not a result: {}", result))?;
+        let round = match self.rounds.last_mut() {
+            Some(r) => r,
+            None => return Err(String::from("No round has been paired yet")),
+        };
+        match round.boards.get_mut(board) {
+            Some(b) => b.result = Some(result.to_string()),
+            None => return Err(format!("There is no board {}", board + 1)),
+        }
+        return Ok(());
+    }
+
+    // "2 1-0": the board number and the result.
+    pub fn record_text(&mut self, text: &str) -> Result<(), String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let board = match words.first().map(|w| w.parse::<usize>()) {
+            Some(Ok(b)) if b >= 1 && words.len() == 2 => b - 1,
+            _ => return Err(String::from("Give the board number and the result, such as 2 1-0")),
+        };
+        return self.record(board, words[1]);
+    }
+
+    pub fn pair_next_round(&mut self) -> Result<(), String> {
+        if self.players.len() < 2 {
+            return Err(String::from("A tournament needs at least two players"));
+        }
+        if !self.round_complete() {
+            return Err(String::from("The current round still has games to play"));
+        }
+        if self.rounds.len() >= self.total_rounds() {
+            return Err(String::from("The tournament is over"));
+        }
+        let number = self.rounds.len() + 1;
+        let (pairs, bye) = match self.format {
+            Format::RoundRobin => pairing::round_robin(self.players.len(), self.rounds.len()),
+            Format::Swiss(_) => {
+                let scores: Vec<f64> = (0..self.players.len()).map(|p| self.score(p)).collect();
+                // By score, and then in the order of registration.
+                let mut ranked: Vec<usize> = (0..self.players.len()).collect();
+                ranked.sort_by(|a, b| scores[*b].total_cmp(&scores[*a]).then(a.cmp(b)));
+                pairing::swiss(&ranked, &scores, &self.history(), number)
+            },
+        };
+        let boards = pairs.iter().map(|(w, b)| Board{white: *w, black: *b, result: None}).collect();
+        self.rounds.push(Round{boards: boards, bye: bye});
+        return Ok(());
+    }
+
+    fn history(&self) -> History {
+        let n = self.players.len();
+        let mut history = History{opponents: vec![Vec::new(); n], colors: vec![Vec::new(); n], had_bye: vec![false; n]};
+        for round in &self.rounds {
+            for b in &round.boards {
+                history.opponents[b.white].push(b.black);
+                history.opponents[b.black].push(b.white);
+                history.colors[b.white].push(Color::White);
+                history.colors[b.black].push(Color::Black);
+            }
+            if let Some(p) = round.bye {
+                history.had_bye[p] = true;
+            }
+        }
+        return history;
+    }
+
+    // Each finished game of a player as (opponent, points).
+    fn games_of(&self, player: usize) -> Vec<(usize, f64)> {
+        let mut games = Vec::new();
+        for round in &self.rounds {
+            for b in &round.boards {
+                let points = match &b.result {
+                    Some(r) => white_points(r),
+                    None => continue,
+                };
+                if b.white == player {
+                    games.push((b.black, points));
+                } else if b.black == player {
+                    games.push((b.white, 1.0 - points));
+                }
+            }
+        }
+        return games;
+    }
+
+    pub fn score(&self, player: usize) -> f64 {
+        let byes = self.rounds.iter().filter(|r| r.bye == Some(player)).count() as f64;
+        return self.games_of(player).iter().fold(byes * BYE_POINTS, |sum, (_, p)| sum + p);
+    }
+
+    // Best first: by score, Buchholz, Sonneborn-Berger, and then the order of registration.
+    pub fn standings(&self) -> Vec<Standing> {
+        let scores: Vec<f64> = (0..self.players.len()).map(|p| self.score(p)).collect();
+        let mut standings: Vec<Standing> = (0..self.players.len()).map(|p| {
+            let games = self.games_of(p);
+            Standing{
+                player: p,
+                score: scores[p],
+                buchholz: games.iter().fold(0.0, |sum, (o, _)| sum + scores[*o]),
+                sonneborn_berger: games.iter().fold(0.0, |sum, (o, points)| sum + points * scores[*o]),
+            }
+        }).collect();
+        standings.sort_by(|a, b| b.score.total_cmp(&a.score)
+            .then(b.buchholz.total_cmp(&a.buchholz))
+            .then(b.sonneborn_berger.total_cmp(&a.sonneborn_berger))
+            .then(a.player.cmp(&b.player)));
+        return standings;
+    }
+
+    // Tab separated lines: the name, the format, the players in order, then each round's boards
+    // and bye.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let mut text = String::from("# jchess tournament\n");
+        text.push_str(&format!("name\t{}\n", self.name));
+        text.push_str(&match self.format {
+            Format::Swiss(r) => format!("format\tswiss\t{}\n", r),
+            Format::RoundRobin => String::from("format\tround robin\n"),
+        });
+        for p in &self.players {
+            text.push_str(&match &p.bot {
+                Some(spec) => format!("bot\t{}\t{}\n", p.name, spec),
+                None => format!("player\t{}\n", p.name),
+            });
+        }
+        for round in &self.rounds {
+            text.push_str("round\n");
+            for b in &round.boards {
+                text.push_str(&format!("board\t{}\t{}\t{}\n", b.white, b.black, b.result.clone().unwrap_or(String::from("*"))));
+            }
+            if let Some(p) = round.bye {
+                text.push_str(&format!("bye\t{}\n", p));
+            }
+        }
+        return match fs::write(path, text) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Could not write {}: {}", path.display(), e)),
+        };
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = match fs::read_to_string(path) {
+            Ok(t) => t,
+            Err(e) => return Err(format!("Could not read {}: {}", path.display(), e)),
+        };
+        let mut event = Event::new(&Event::default_name());
+        for (n, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let bad = || format!("{} line {}: {}", path.display(), n + 1, line);
+            let fields: Vec<&str> = line.split('\t').collect();
+            let player = |i: usize| fields.get(i).and_then(|f| f.parse::<usize>().ok()).filter(|p| *p < event.players.len());
+            match (fields[0], fields.len()) {
+                ("name", 2) => event.name = fields[1].to_string(),
+                ("format", _) => event.format = Format::parse(&fields[1..].join(" ")).map_err(|_| bad())?,
+                ("player", 2) => event.players.push(Entrant{name: fields[1].to_string(), bot: None}),
+                ("bot", 3) => event.players.push(Entrant{name: fields[1].to_string(), bot: Some(fields[2].to_string())}),
+                ("round", 1) => event.rounds.push(Round{boards: Vec::new(), bye: None}),
+                ("board", 4) => {
+                    let board = match (player(1), player(2), fields[3]) {
+                        (Some(w), Some(b), "*") => Board{white: w, black: b, result: None},
+                        (Some(w), Some(b), r) => Board{white: w, black: b, result: Some(parse_result(r).ok_or_else(bad)?.to_string())},
+                        _ => return Err(bad()),
+                    };
+                    event.rounds.last_mut().ok_or_else(bad)?.boards.push(board);
+                },
+                ("bye", 2) => {
+                    let p = player(1).ok_or_else(bad)?;
+                    event.rounds.last_mut().ok_or_else(bad)?.bye = Some(p);
+                },
+                _ => return Err(bad()),
+            }
+        }
+        return Ok(event);
+    }
+
+    // The tournament screen: the standings, the current round and the menu, with a message or
+    // question under it.
+    pub fn print(&self, menu: &[&str], message: &str) {
+        let _ = Command::new("clear").status();
+        println!();
+        let round = match self.rounds.len() {
+            0 => String::from("not started"),
+            r if self.finished() => format!("finished after {} rounds", r),
+            r => format!("round {} of {}", r, self.total_rounds()),
+        };
+        println!("  {} - {}, {}", self.name, self.format.to_string(), round);
+        println!();
+
+        let width = self.players.iter().map(|p| p.name.len()).max().unwrap_or(0).max(4);
+        if self.players.len() == 0 {
+            println!("  No players yet");
+        } else {
+            println!("  {:>4}  {:<width$}  {:>5}  {:>8}  {:>6}", "Rank", "Name", "Score", "Buchholz", "S-B", width = width);
+            for (rank, s) in self.standings().iter().enumerate() {
+                let p = &self.players[s.player];
+                let kind = if p.bot.is_some() { "  (bot)" } else { "" };
+                println!("  {:>4}  {:<width$}  {:>5.1}  {:>8.1}  {:>6.2}{}", rank + 1, p.name, s.score, s.buchholz,
+                    s.sonneborn_berger, kind, width = width);
+            }
+        }
+
+        if let Some(r) = self.rounds.last() {
+            println!();
+            println!("  Round {}", self.rounds.len());
+            for (i, b) in r.boards.iter().enumerate() {
+                let pairing = format!("{} - {}", self.players[b.white].name, self.players[b.black].name);
+                println!("  {:>4}. {:<w$}  {}", i + 1, pairing, b.result.clone().unwrap_or(String::from("*")), w = 2 * width + 3);
+            }
+            if let Some(p) = r.bye {
+                println!("  {:>4}  {:<w$}  bye", "", self.players[p].name, w = 2 * width + 3);
+            }
+        }
+
+        println!();
+        for (i, entry) in menu.iter().enumerate() {
+            println!("  {}. {}", i + 1, entry);
+        }
+        println!("  {}", message);
+        print!("  > ");
+        let _ = stdout().flush();
+    }
+}
@@ -0,0 +1,178 @@
+use crate::chess::color::Color;
+
+// Pairings for the next round, as (white, black) and the player who sits out, if any.
+pub type Pairing = (Vec<(usize, usize)>, Option<usize>);
+
+// What the pairings go by: who has played whom, with which colors, and who has had a bye.
+pub struct History {
+    pub opponents: Vec<Vec<usize>>,
+    pub colors: Vec<Vec<Color>>,
+    pub had_bye: Vec<bool>,
+}
+
+// The circle method: player n - 1 stays put while the others turn around the table one place a
+// round. With an odd number of players the empty seat is the bye.
+pub fn round_robin(players: usize, round: usize) -> Pairing {
+    let seats = players + players % 2;
+    let turning = seats - 1;
+    let mut pairs = Vec::new();
+    let mut bye = None;
+    for i in 0..seats / 2 {
+        let (a, b) = if i == 0 {
+            (seats - 1, round % turning)
+        } else {
+            ((round + i) % turning, (round + turning - i) % turning)
+        };
+        if a >= players || b >= players {
+            bye = Some(if a >= players { b } else { a });
+            continue;
+        }
+        // The fixed seat changes color every round, the others by their place at the table.
+        let a_white = if i == 0 { round % 2 == 1 } else { i % 2 == 1 };
+        pairs.push(if a_white { (a, b) } else { (b, a) });
+    }
+    return (pairs, bye);
+}
+
+// Gives up on a set of pairing rules after this many tries, so that a field that cannot be paired
+// under them does not take forever to find out.
+const MAX_TRIES: usize = 100000;
+
+struct Pairer<'a> {
+    scores: &'a [f64],
+    history: &'a History,
+    rematches: bool,
+    colors: bool, // whether color rules can rule out a pairing
+    tries: usize,
+}
+
+// Dutch-style Swiss pairing. `ranked` is the field from first to last by score (and then by
+// seed). Within each score group the top half plays the bottom half in order, first against first;
+// a player left over floats down to the next group. Players do not meet twice, and never get the
+// same color three times in a row or two more whites than blacks (or the reverse). When the field
+// cannot be paired that way the color rules are dropped first, and then rematches are allowed.
+// With an odd field, the lowest ranked player who has not had a bye yet sits out.
+pub fn swiss(ranked: &[usize], scores: &[f64], history: &History, round: usize) -> Pairing {
+    for (rematches, colors) in [(false, true), (false, false), (true, false)] {
+        let mut pairer = Pairer{scores: scores, history: history, rematches: rematches, colors: colors, tries: 0};
+        if let Some((pairs, bye)) = pairer.pair_field(ranked) {
+            let pairs = pairs.iter().map(|(a, b)| assign_colors(history, *a, *b, round)).collect();
+            return (pairs, bye);
+        }
+    }
+    // Not reached with rematches allowed, which always pairs the field.
+    return (Vec::new(), None);
+}
+
+impl<'a> Pairer<'a> {
+    fn pair_field(&mut self, ranked: &[usize]) -> Option<Pairing> {
+        let mut byes: Vec<Option<usize>> = vec![None];
+        if ranked.len() % 2 == 1 {
+            byes = ranked.iter().rev().filter(|p| !self.history.had_bye[**p]).map(|p| Some(*p)).collect();
+            // Once everybody has had one, byes go round again from the bottom.
+            if byes.len() == 0 {
+                byes = ranked.iter().rev().map(|p| Some(*p)).collect();
+            }
+        }
+        for bye in byes {
+            let rest: Vec<usize> = ranked.iter().filter(|p| Some(**p) != bye).copied().collect();
+            let mut pairs = Vec::new();
+            if self.pair_rest(&rest, &mut pairs) {
+                return Some((pairs, bye));
+            }
+        }
+        return None;
+    }
+
+    // Pairs the highest ranked player left, and then the rest, backtracking when that fails.
+    fn pair_rest(&mut self, rest: &[usize], pairs: &mut Vec<(usize, usize)>) -> bool {
+        if rest.len() == 0 {
+            return true;
+        }
+        self.tries += 1;
+        if self.tries > MAX_TRIES {
+            return false;
+        }
+        let top = rest[0];
+        for i in self.preference(rest) {
+            let other = rest[i];
+            if !self.rematches && self.history.opponents[top].contains(&other) {
+                continue;
+            }
+            if self.colors && !colors_allow(self.history, top, other) {
+                continue;
+            }
+            let remaining: Vec<usize> = rest[1..].iter().filter(|p| **p != other).copied().collect();
+            pairs.push((top, other));
+            if self.pair_rest(&remaining, pairs) {
+                return true;
+            }
+            pairs.pop();
+        }
+        return false;
+    }
+
+    // The order in which opponents are tried for rest[0], as indices into rest. The players with
+    // the same score are taken as a group: the top of it plays the middle first (the first of the
+    // bottom half), then on down the bottom half, then back up the top half; the next groups down
+    // come after.
+    fn preference(&self, rest: &[usize]) -> Vec<usize> {
+        let score = self.scores[rest[0]];
+        let group = rest.iter().take_while(|p| self.scores[**p] == score).count();
+        let half = group / 2;
+        let mut order: Vec<usize> = (half.max(1)..group).collect();
+        order.extend((1..half).rev());
+        order.extend(group..rest.len());
+        return order;
+    }
+}
+
+// Whites minus blacks so far.
+fn color_difference(history: &History, player: usize) -> i32 {
+    return history.colors[player].iter().map(|c| if *c == Color::White { 1 } else { -1 }).sum();
+}
+
+// The color a player has to have next, if any: the other one after two of the same in a row, or
+// when two ahead on one color.
+fn due_color(history: &History, player: usize) -> Option<Color> {
+    let colors = &history.colors[player];
+    let difference = color_difference(history, player);
+    if difference >= 2 {
+        return Some(Color::Black);
+    }
+    if difference <= -2 {
+        return Some(Color::White);
+    }
+    if colors.len() >= 2 && colors[colors.len() - 1] == colors[colors.len() - 2] {
+        return Some(if colors[colors.len() - 1] == Color::White { Color::Black } else { Color::White });
+    }
+    return None;
+}
+
+fn colors_allow(history: &History, a: usize, b: usize) -> bool {
+    return match (due_color(history, a), due_color(history, b)) {
+        (Some(x), Some(y)) => x != y,
+        _ => true,
+    };
+}
+
+// White goes to whoever is due it, then to whoever has had fewer whites, then to whoever had black
+// the last time the two had different colors. Otherwise the higher ranked player, `a`, has white in
+// odd rounds.
+fn assign_colors(history: &History, a: usize, b: usize, round: usize) -> (usize, usize) {
+    match (due_color(history, a), due_color(history, b)) {
+        (Some(Color::White), _) | (_, Some(Color::Black)) => return (a, b),
+        (Some(Color::Black), _) | (_, Some(Color::White)) => return (b, a),
+        _ => {},
+    }
+    let (da, db) = (color_difference(history, a), color_difference(history, b));
+    if da != db {
+        return if da < db { (a, b) } else { (b, a) };
+    }
+    for (ca, cb) in history.colors[a].iter().rev().zip(history.colors[b].iter().rev()) {
+        if ca != cb {
+            return if *ca == Color::Black { (a, b) } else { (b, a) };
+        }
+    }
+    return if round % 2 == 1 { (a, b) } else { (b, a) };
+}
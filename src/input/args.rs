@@ -0,0 +1,53 @@
+// Command line arguments for the subcommands: `--name value` options and plain positional values.
+
+pub struct Args {
+    options: Vec<(String, String)>,
+    pub positional: Vec<String>,
+}
+
+impl Args {
+    // `options` take a value and `flags` do not. Anything else starting with "--", `--help`
+    // included, is an error that shows `usage`, as is an option with no value after it.
+    pub fn parse(args: &[String], options: &[&str], flags: &[&str], usage: &str) -> Result<Self, String> {
+        let mut parsed = Args{options: Vec::new(), positional: Vec::new()};
+        let mut i = 0;
+        while i < args.len() {
+            if let Some(name) = args[i].strip_prefix("--") {
+                if flags.contains(&name) {
+                    parsed.options.push((name.to_string(), String::new()));
+                } else if !options.contains(&name) {
+                    return Err(if name == "help" { usage.to_string() } else { format!("Unknown option: --{}\n{}", name, usage) });
+                } else if i + 1 >= args.len() || args[i + 1].starts_with("--") {
+                    return Err(format!("Missing value for --{}\n{}", name, usage));
+                } else {
+                    parsed.options.push((name.to_string(), args[i + 1].clone()));
+                    i += 1;
+                }
+            } else {
+                parsed.positional.push(args[i].clone());
+            }
+            i += 1;
+        }
+        return Ok(parsed);
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        for (n, v) in &self.options {
+            if n == name {
+                return Some(v.clone());
+            }
+        }
+        return None;
+    }
+
+    // Parses an option's value, falling back to the default when it is not given.
+    pub fn get_or<T: std::str::FromStr>(&self, name: &str, default: T) -> Result<T, String> {
+        return match self.get(name) {
+            Some(v) => match v.parse::<T>() {
+                Ok(x) => Ok(x),
+                Err(_) => Err(format!("Invalid value for --{}: {}", name, v)),
+            },
+            None => Ok(default),
+        };
+    }
+}
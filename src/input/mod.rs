@@ -1,8 +1,46 @@
+pub mod args;
+
 use std::io::stdin;
+use std::sync::{Mutex, OnceLock};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+// Stdin is read by one thread for the whole program, so that waiting for a line can time out (as
+// the spectator mode does between moves) without a half-finished read taking the next line.
+static LINES: OnceLock<Mutex<Receiver<String>>> = OnceLock::new();
+
+fn lines() -> &'static Mutex<Receiver<String>> {
+    return LINES.get_or_init(|| {
+        let (send, receive) = mpsc::channel();
+        thread::spawn(move || {
+            let mut input = String::new();
+            while let Ok(n) = stdin().read_line(&mut input) {
+                if n == 0 || send.send(String::from(input.trim())).is_err() {
+                    return;
+                }
+                input.clear();
+            }
+        });
+        return Mutex::new(receive);
+    });
+}
+
+// None once stdin is closed.
+pub fn next_line() -> Option<String> {
+    return lines().lock().unwrap().recv().ok();
+}
+
+pub enum Typed {
+    Line(String),
+    Nothing, // the time ran out
+    Closed,
+}
 
-pub fn read_line() -> String {
-    let mut input = String::new();
-    input.clear();
-    stdin().read_line(&mut input).expect("Failed to read input");
-    String::from(input.trim())
+pub fn read_line_timeout(timeout: Duration) -> Typed {
+    return match lines().lock().unwrap().recv_timeout(timeout) {
+        Ok(line) => Typed::Line(line),
+        Err(RecvTimeoutError::Timeout) => Typed::Nothing,
+        Err(RecvTimeoutError::Disconnected) => Typed::Closed,
+    };
 }
@@ -1,14 +1,49 @@
+mod archive;
+mod arena;
+mod bitbase;
+mod book;
 mod bots;
 mod chess;
+mod database;
+mod eco;
+mod eval;
+mod event;
 mod input;
+mod profile;
+mod protocol;
+mod storage;
+mod tablebase;
 
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
+
+use arena::{DrawRules, Termination};
+use chess::color::Color;
 use chess::game::Game;
+use chess::pgn::{play_san, to_san, PgnGame};
+use chess::player::{Limits, Player, Thinking};
 use chess::r#move::{MetaMove, Move};
+use chess::game::StartColor;
 use chess::setup::Setup;
+use event::{Event, Format, EVENT_FILE};
 use input::*;
+use archive::ARCHIVE_FILE;
+use database::query::Query;
+use profile::{Profiles, PROFILE_FILE};
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() > 0 {
+        if let Err(e) = run_command(&args) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let mut game = Game::new();
+    let mut config = Setup::new();
     let exit = false;
 
     game.default_board();
@@ -16,35 +51,104 @@ fn main() {
     while !exit {
         game.title();
         game.fancy_print();
-        let input = read_line();
+        // The end of input quits, as there is no one left to play.
+        let input = match next_line() {
+            Some(l) => l,
+            None => break,
+        };
 
         if input == "1" {
-            game_loop(&mut game);
-            let _ = read_line();
+            if let Some(result) = game_loop(&mut game, &config, "Casual game", "-") {
+                rate_game(&game, result);
+            }
+            if next_line().is_none() {
+                break;
+            }
         } else if input == "2" {
-            setup_loop(&mut game);
+            setup_loop(&mut game, &mut config);
         } else if input == "3" {
+            tournament_loop(&game, &config);
+        } else if input == "4" {
+            stats_loop();
+        } else if input == "5" {
+            archive_loop(&config);
+        } else if input == "6" {
+            database_loop(&config);
+        } else if input == "7" {
             break;
         }
     }
 }
 
-fn game_loop(game: &mut Game) {
+// Subcommands run instead of the interactive game when arguments are given.
+fn run_command(args: &[String]) -> Result<(), String> {
+    return match args[0].as_str() {
+        "book" => book::builder::run(&args[1..]),
+        "bitbase" => bitbase::generate::run(&args[1..]),
+        "tune" => eval::tune::run(&args[1..]),
+        "selfplay" => arena::selfplay::run(&args[1..]),
+        "match" | "tournament" => arena::tournament::run(&args[1..]),
+        "uci" => protocol::uci::run(&args[1..]),
+        "xboard" => protocol::xboard::run(&args[1..]),
+        "db" => database::command::run(&args[1..]),
+        _ => Err(format!("Unknown command: {}\nCommands: book, bitbase, tune, selfplay, match, uci, xboard, db", args[0])),
+    };
+}
+
+// The side that lost, as a result.
+fn loss_for(color: Color) -> Option<&'static str> {
+    return Some(if color == Color::White { "0-1" } else { "1-0" });
+}
+
+// Plays a game to the end and adds it to the archive. Returns its result ("1-0", "0-1" or
+// "1/2-1/2"), or None when it was quit before it was decided.
+fn game_loop(game: &mut Game, config: &Setup, event: &str, round: &str) -> Option<&'static str> {
+    let mut times = Vec::new();
+    let (result, termination) = if game.is_spectating() {
+        spectate_loop(game, config, &mut times)
+    } else {
+        play_loop(game, config, &mut times)
+    };
+    let pgn = archive::to_pgn(game, event, round, result.unwrap_or("*"), termination, &times);
+    if let Err(e) = archive::append(&storage::data_file(ARCHIVE_FILE), &pgn) {
+        println!("  {}", e);
+    }
+    return result;
+}
+
+// The game at the board, with how it ended. The time each move took is added to `times`.
+fn play_loop(game: &mut Game, config: &Setup, times: &mut Vec<Duration>) -> (Option<&'static str>, Termination) {
     let mut history: Vec<Game> = Vec::new();
     let mut m : Move;
 
     game.start_game();
     let mut quit : bool = false;
+    let mut result = None;
+    let mut termination = Termination::Abandoned;
+    let mut turn_start = Instant::now();
+    // The game database while the explorer is shown, which it is until "explore" is entered again.
+    let mut explorer: Option<database::Database> = None;
     while !quit
     {
         if !game.any_valid_moves() {
             if game.is_check() {
                 game.set_checkmate();
+                result = loss_for(game.current_color());
+                termination = Termination::Checkmate;
             } else {
                 game.set_stalemate();
+                result = Some("1/2-1/2");
+                termination = Termination::Stalemate;
             }
             quit = true;
         }
+        game.set_annotation(match config.tablebase() {
+            Some(tb) => match tb.probe_wdl(game) {
+                Some(wdl) => tablebase::wdl_string(wdl),
+                None => String::new(),
+            },
+            None => String::new(),
+        });
         game.fancy_print();
         if quit {
             break;
@@ -52,9 +156,22 @@ fn game_loop(game: &mut Game) {
 
         m = match game.current_player().get_move(&game) {
             Ok(mo) => mo,
+            // A bot would only give the same answer again, so it forfeits.
+            Err(s) if game.current_player().is_bot() => {
+                game.set_error(s);
+                game.set_concede();
+                game.fancy_print();
+                result = loss_for(game.current_color());
+                termination = Termination::Forfeit;
+                break;
+            },
             Err(s) => {game.set_error(s); continue},
         };
         if m.meta == MetaMove::Quit || m.meta == MetaMove::Concede {
+            if m.meta == MetaMove::Concede {
+                result = loss_for(game.current_color());
+                termination = Termination::Resignation;
+            }
             game.set_concede();
             quit = true;
             game.clear_hl();
@@ -63,6 +180,29 @@ fn game_loop(game: &mut Game) {
         } else if m.meta == MetaMove::Flip {
             game.flip_board();
             continue;
+        } else if m.meta == MetaMove::Eval {
+            game.show_eval(&eval::breakdown(game, &config.weights()));
+            continue;
+        } else if m.meta == MetaMove::Hint {
+            match config.book_config() {
+                Some(b) => game.show_book(&b.book.moves(game)),
+                None => game.set_error(String::from("No opening book loaded")),
+            }
+            continue;
+        } else if m.meta == MetaMove::Explore {
+            if explorer.take().is_some() {
+                game.clear_panel();
+                continue;
+            }
+            match database::Database::load_or_new(&storage::data_file(database::DATABASE_FILE)) {
+                Ok(db) if db.games.len() == 0 => game.set_error(String::from("No games in the database; add some with jchess db import")),
+                Ok(db) => {
+                    game.show_explorer(&db.explore(game));
+                    explorer = Some(db);
+                },
+                Err(e) => game.set_error(e),
+            }
+            continue;
         }
 
         if let Some(s) = game.disambiguate(&mut m) {
@@ -71,6 +211,7 @@ fn game_loop(game: &mut Game) {
         }
 
         game.clear_notes();
+        game.clear_panel();
         game.clear_hl();
         let mut tmp = game.clone();
         let _ = tmp.do_move(m);
@@ -81,20 +222,342 @@ fn game_loop(game: &mut Game) {
             let mut prev = game.do_move(m);
             prev.clear_hl();
             history.push(prev);
+            times.push(turn_start.elapsed());
+            turn_start = Instant::now();
         }
 
         game.next_turn();
+        game.update_opening();
+        game.notify_player_to_move();
+        if let Some(db) = &explorer {
+            game.show_explorer(&db.explore(game));
+        }
+    }
+    return (result, termination);
+}
+
+// Two bots play each other on the board while the user watches. A move is made every delay; a
+// line of "p" pauses or resumes, "s" makes one move while paused, and "q" stops the game.
+fn spectate_loop(game: &mut Game, config: &Setup, times: &mut Vec<Duration>) -> (Option<&'static str>, Termination) {
+    let delay = config.spectate_delay();
+    let mut m : Move;
+
+    game.start_game();
+    let mut draws = DrawRules::new(game);
+    let mut paused = false;
+    loop {
+        if !game.any_valid_moves() {
+            let outcome = if game.is_check() {
+                game.set_checkmate();
+                (loss_for(game.current_color()), Termination::Checkmate)
+            } else {
+                game.set_stalemate();
+                (Some("1/2-1/2"), Termination::Stalemate)
+            };
+            game.show_spectator(delay, "Game over");
+            game.fancy_print();
+            return outcome;
+        }
+        if let Some(t) = draws.check(game) {
+            game.set_stalemate();
+            game.show_spectator(delay, &t.to_string());
+            game.fancy_print();
+            return (Some("1/2-1/2"), t);
+        }
+        game.show_spectator(delay, if paused { "Paused" } else { "Playing" });
+        game.fancy_print();
+
+        // Waits out the delay, or for a step or resume while paused. Anything else typed in the
+        // meantime is ignored.
+        let deadline = Instant::now() + delay;
+        let mut quit = false;
+        loop {
+            let wait = if paused { Duration::from_secs(60) } else { deadline.saturating_duration_since(Instant::now()) };
+            match read_line_timeout(wait) {
+                Typed::Line(l) if l == "q" => quit = true,
+                Typed::Line(l) if l == "s" && paused => break,
+                Typed::Line(l) if l == "p" => {
+                    paused = !paused;
+                    game.show_spectator(delay, if paused { "Paused" } else { "Playing" });
+                    game.fancy_print();
+                    continue;
+                },
+                Typed::Line(_) | Typed::Nothing => {},
+                Typed::Closed if paused => quit = true,
+                Typed::Closed => std::thread::sleep(deadline.saturating_duration_since(Instant::now())),
+            }
+            if quit || (!paused && Instant::now() >= deadline) {
+                break;
+            }
+        }
+        if quit {
+            game.show_spectator(delay, "Stopped");
+            game.fancy_print();
+            return (None, Termination::Abandoned);
+        }
+
+        let thinking = Instant::now();
+        m = match game.current_player().get_move(game) {
+            Ok(mo) => mo,
+            Err(s) => {
+                game.set_concede();
+                game.show_spectator(delay, &s);
+                game.fancy_print();
+                return (loss_for(game.current_color()), Termination::Forfeit);
+            },
+        };
+        if let Some(s) = game.disambiguate(&mut m) {
+            game.set_concede();
+            game.show_spectator(delay, &s);
+            game.fancy_print();
+            return (loss_for(game.current_color()), Termination::Forfeit);
+        }
+        times.push(thinking.elapsed());
+        let _ = game.do_move(m);
+        game.next_turn();
+        game.update_opening();
+        draws.record(game, &m);
+        game.notify_player_to_move();
+    }
+}
+
+// Rates a finished game for the players' profiles and shows how their ratings moved.
+fn rate_game(game: &Game, result: &str) {
+    let path = storage::data_file(PROFILE_FILE);
+    let mut profiles = Profiles::new();
+    if path.exists() {
+        // A file that does not read is left alone rather than written over.
+        profiles = match Profiles::load(&path) {
+            Ok(p) => p,
+            Err(e) => {
+                println!("  {}", e);
+                return;
+            },
+        };
+    }
+    let changes = profiles.record(game.player(Color::White), game.player(Color::Black), result);
+    if changes.len() == 0 {
+        return;
+    }
+    if let Err(e) = profiles.save(&path) {
+        println!("  {}", e);
+        return;
+    }
+    for c in changes {
+        println!("  {}", c);
+    }
+}
+
+// The rated players, and the details of any of them by name.
+fn stats_loop() {
+    let path = storage::data_file(PROFILE_FILE);
+    let ask = "Name a player to see their games, or press enter to go back:";
+    let (profiles, mut message) = match Profiles::load(&path) {
+        Ok(p) => (p, String::from(ask)),
+        Err(_) if !path.exists() => (Profiles::new(), String::from(ask)),
+        Err(e) => (Profiles::new(), e),
+    };
+    loop {
+        profiles.print_summary(&message);
+        let name = match next_line() {
+            Some(l) if !l.is_empty() => l,
+            _ => return,
+        };
+        match profiles.get(&name) {
+            Some(p) => {
+                profiles.print_profile(p);
+                if next_line().is_none() {
+                    return;
+                }
+                message = String::from(ask);
+            },
+            None => message = format!("No player called {}. {}", name, ask),
+        }
+    }
+}
+
+// The archived games, newest first, a page at a time.
+fn archive_loop(config: &Setup) {
+    let ask = "A number replays a game; f and some words filter the list, f alone clears it; n and p turn the page; enter goes back:";
+    let (games, mut message) = match archive::load(&storage::data_file(ARCHIVE_FILE)) {
+        Ok(g) => (g, String::from(ask)),
+        Err(e) => (Vec::new(), e),
+    };
+    let mut filter = String::new();
+    let mut page = 0;
+    loop {
+        let shown: Vec<usize> = (0..games.len()).rev().filter(|i| archive::matches(&games[*i], &filter)).collect();
+        page = page.min(shown.len().saturating_sub(1) / archive::PAGE);
+        archive::print_list(&games, &shown, page, &filter, &message);
+        message = String::from(ask);
+        let input = match next_line() {
+            Some(l) if !l.is_empty() => l,
+            _ => return,
+        };
+        if input == "n" {
+            page += 1;
+        } else if input == "p" {
+            page = page.saturating_sub(1);
+        } else if input == "f" || input.starts_with("f ") {
+            filter = input[1..].trim().to_string();
+            page = 0;
+        } else {
+            match input.parse::<usize>() {
+                Ok(n) if n >= 1 && n <= games.len() => replay_loop(&games[n - 1], 0, config),
+                _ => message = format!("No game {}. {}", input, ask),
+            }
+        }
     }
 }
 
-fn setup_loop(game: &mut Game) {
+// The games imported with `jchess db import`. A search replaces the one before it and a number
+// replays a game from where the searched position or material first came up.
+fn database_loop(config: &Setup) {
+    let ask = "A number replays a game; s and field:value words search, s alone shows all; n and p turn the page; enter goes back:";
+    let (db, mut message) = match database::Database::load_or_new(&storage::data_file(database::DATABASE_FILE)) {
+        Ok(db) => (db, String::from(ask)),
+        Err(e) => (database::Database::new(), e),
+    };
+    let mut query = Query::new();
+    let mut found = db.search(&query);
+    let mut page = 0;
+    loop {
+        page = page.min(found.len().saturating_sub(1) / database::PAGE);
+        database::print_list(&db, &found, page, &query.to_string(), &message);
+        message = String::from(ask);
+        let input = match next_line() {
+            Some(l) if !l.is_empty() => l,
+            _ => return,
+        };
+        if input == "n" {
+            page += 1;
+        } else if input == "p" {
+            page = page.saturating_sub(1);
+        } else if input == "s" || input.starts_with("s ") {
+            match Query::parse(&input[1..]) {
+                Ok(q) => {
+                    query = q;
+                    found = db.search(&query);
+                    page = 0;
+                },
+                Err(e) => message = format!("{}. {}", e, ask),
+            }
+        } else {
+            let n = input.parse::<usize>().unwrap_or(0);
+            match found.iter().find(|f| f.0 + 1 == n) {
+                Some((id, ply)) => replay_loop(&db.games[*id].to_pgn(), *ply, config),
+                None => message = format!("No game {} in the list. {}", input, ask),
+            }
+        }
+    }
+}
+
+// How long SearchBot looks at a position of a replayed game.
+const ANALYSIS_TIME: Duration = Duration::from_secs(2);
+
+// Steps through a saved game from the ply given. Enter or "n" goes a move forward and "p" one back,
+// "s" and "e" go to the start and the end, and "a" has SearchBot, as set up, look at the position.
+fn replay_loop(pgn: &PgnGame, start: usize, config: &Setup) {
+    // A move that does not read ends the game there.
+    let mut positions = vec![arena::start_position(pgn.tag("FEN").as_deref())];
+    for san in &pgn.moves {
+        let mut next = positions[positions.len() - 1].clone();
+        next.clear_hl();
+        if play_san(&mut next, san).is_err() {
+            break;
+        }
+        positions.push(next);
+    }
+    let last = positions.len() - 1;
+    let status = format!("{} {}", pgn.result, pgn.tag("Termination").unwrap_or_default());
+    let mut ply = start.min(last);
+    let mut flipped = false;
+    let mut analysis = String::new();
+    loop {
+        let mut shown = positions[ply].clone();
+        if flipped {
+            shown.flip_board();
+        }
+        let time = match ply {
+            0 => String::new(),
+            p => pgn.times.get(p - 1).map(|t| format!("  {:.1}s", t.as_secs_f64())).unwrap_or_default(),
+        };
+        shown.show_position();
+        shown.show_replay(&pgn.tag("White").unwrap_or(String::from("?")), &pgn.tag("Black").unwrap_or(String::from("?")),
+            &status, &format!("Ply {} of {}{}", ply, last, time));
+        shown.set_annotation(analysis.clone());
+        shown.fancy_print();
+        analysis.clear();
+        let input = match next_line() {
+            Some(l) => l,
+            None => return,
+        };
+        match input.as_str() {
+            "" | "n" => ply = (ply + 1).min(last),
+            "p" => ply = ply.saturating_sub(1),
+            "s" => ply = 0,
+            "e" => ply = last,
+            "f" => flipped = !flipped,
+            "a" => analysis = analyze(&positions[ply], config),
+            "q" => return,
+            _ => {},
+        }
+    }
+}
+
+// SearchBot's best line for the position, with the score from white's point of view.
+fn analyze(game: &Game, config: &Setup) -> String {
+    if !game.any_valid_moves() {
+        return String::from("Game over");
+    }
+    let bot = config.search_bot();
+    let mut limits = Limits::new();
+    limits.movetime = Some(ANALYSIS_TIME);
+    let found: Arc<Mutex<Option<Thinking>>> = Arc::new(Mutex::new(None));
+    let report = found.clone();
+    let best = bot.think(game, limits, Arc::new(AtomicBool::new(false)), Box::new(move |t: &Thinking| {
+        *report.lock().unwrap() = Some(t.clone());
+    }));
+    let best = match best {
+        Ok(m) => m,
+        Err(e) => return e,
+    };
+    let sign = if game.current_color() == Color::White { 1 } else { -1 };
+    let (score, line) = match found.lock().unwrap().take() {
+        Some(t) => {
+            let score = match t.mate {
+                Some(n) => format!("{}M{}", if n * sign < 0 { "-" } else { "" }, n.abs()),
+                None => format!("{:+.2}", (t.score * sign) as f64 / 100.0),
+            };
+            (format!("{}, depth {}", score, t.depth), if t.pv.len() > 0 { t.pv } else { vec![best] })
+        },
+        None => (String::from("no score"), vec![best]),
+    };
+    let mut replay = game.clone();
+    let mut sans = Vec::new();
+    for m in line.iter().take(5) {
+        sans.push(to_san(&replay, m));
+        replay.do_move(*m);
+        replay.next_turn();
+    }
+    return format!("Best: {} ({})", sans.join(" "), score);
+}
+
+fn setup_loop(game: &mut Game, config: &mut Setup) {
     let mut quit = false;
-    let mut config = Setup::new();
+    config.reset_menu();
 
     game.start_setup();
     while !quit {
-        game.fancy_print_setup(&config);
-        let input = read_line();
+        game.fancy_print_setup(config);
+        let input = match next_line() {
+            Some(l) => l,
+            None => return,
+        };
+        if config.awaiting_input() {
+            config.input(input, game);
+            continue;
+        }
         let idx = match input.parse::<usize>() {
             Ok(i) => i - 1,
             Err(_) => continue,
@@ -104,3 +567,120 @@ fn setup_loop(game: &mut Game) {
         }
     }
 }
+
+// Runs the tournament kept in EVENT_FILE, starting one if there is none. Its games are played on a
+// copy of the game, so the players picked in Setup stay as they are.
+fn tournament_loop(game: &Game, config: &Setup) {
+    let path = storage::data_file(EVENT_FILE);
+    let mut message = String::new();
+    let mut event = if path.exists() {
+        match Event::load(&path) {
+            Ok(e) => e,
+            Err(e) => {
+                message = e;
+                Event::new(&Event::default_name())
+            },
+        }
+    } else {
+        Event::new(&Event::default_name())
+    };
+    let menu = ["Play Next Game", "Enter a Result", "Pair Next Round", "Add a Player", "Add a Bot", "Format",
+        "New Tournament", "Back"];
+
+    loop {
+        event.print(&menu, &message);
+        message.clear();
+        let input = match next_line() {
+            Some(l) => l.trim().to_string(),
+            None => return,
+        };
+        // Asks a question under the menu; None when nothing is typed, or at the end of input.
+        let ask = |event: &Event, question: &str| -> Option<String> {
+            event.print(&menu, question);
+            return next_line().map(|l| l.trim().to_string()).filter(|l| !l.is_empty());
+        };
+        let changed = match input.as_str() {
+            "1" => match event.next_board() {
+                Some(board) => play_tournament_game(&mut event, board, game, config),
+                None => Err(String::from("No games to play; pair the next round")),
+            },
+            "2" => match ask(&event, "Board and result, such as 2 1-0:") {
+                Some(text) => event.record_text(&text),
+                None => continue,
+            },
+            "3" => event.pair_next_round(),
+            "4" => match ask(&event, "Name:") {
+                Some(name) => event.add_player(&name, None),
+                None => continue,
+            },
+            "5" => {
+                let spec = match ask(&event, &format!("{}\n  Bot:", bots::SPEC_HELP)) {
+                    Some(s) => s,
+                    None => continue,
+                };
+                match ask(&event, "Name:") {
+                    Some(name) => event.add_player(&name, Some(spec)),
+                    None => continue,
+                }
+            },
+            "6" => match ask(&event, "Format (swiss N or round robin):") {
+                Some(text) => Format::parse(&text).and_then(|f| event.set_format(f)),
+                None => continue,
+            },
+            "7" => match ask(&event, "Type yes to drop this tournament and start a new one:") {
+                Some(answer) if answer == "yes" => {
+                    let name = ask(&event, "Name:").unwrap_or(Event::default_name());
+                    event = Event::new(&name);
+                    Ok(())
+                },
+                _ => continue,
+            },
+            "8" => return,
+            _ => continue,
+        };
+        message = match changed.and_then(|_| event.save(&path)) {
+            Ok(_) if event.finished() => String::from("The tournament is over"),
+            Ok(_) => String::new(),
+            Err(e) => e,
+        };
+    }
+}
+
+// Plays a board of the current round and records its result. A person always sits on player
+// one's side of the game, with the color of the pairing, as Setup does against a bot.
+fn play_tournament_game(event: &mut Event, board: usize, game: &Game, config: &Setup) -> Result<(), String> {
+    let pairing = event.rounds.last().unwrap().boards[board].clone();
+    let (white, black) = (&event.players[pairing.white], &event.players[pairing.black]);
+    let mut game = game.clone();
+    match (white.bot.is_some(), black.bot.is_some()) {
+        (false, true) => {
+            game.set_player_one(white.player()?);
+            game.set_player_two(black.player()?);
+            game.set_start_color(StartColor::White);
+        },
+        (true, false) => {
+            game.set_player_one(black.player()?);
+            game.set_player_two(white.player()?);
+            game.set_start_color(StartColor::Black);
+        },
+        _ => {
+            game.set_player_one(white.player()?);
+            game.set_player_two(black.player()?);
+        },
+    }
+
+    event.print(&[], &format!("Board {}: {} - {}, press enter to start", board + 1, white.name, black.name));
+    if next_line().is_none() {
+        return Ok(());
+    }
+    let round = event.rounds.len().to_string();
+    let result = game_loop(&mut game, config, &event.name, &round);
+    if let Some(r) = result {
+        rate_game(&game, r);
+    }
+    let _ = next_line();
+    return match result {
+        Some(r) => event.record(board, r),
+        None => Err(String::from("The game was stopped; enter its result by hand")),
+    };
+}
@@ -0,0 +1,109 @@
+use std::f64::consts::PI;
+
+// Glicko-2 (Glickman, "Example of the Glicko-2 system"). A rating comes with a deviation, how
+// unsure it is, and a volatility, how erratic the player's results are. Each finished game is a
+// rating period of its own, so the deviation shrinks as games are played and grows again a little
+// with every update by the volatility.
+
+pub const INITIAL_RATING: f64 = 1500.0;
+pub const INITIAL_DEVIATION: f64 = 350.0;
+pub const INITIAL_VOLATILITY: f64 = 0.06;
+
+// How much the volatility may change; the paper suggests 0.3 to 1.2.
+const TAU: f64 = 0.5;
+// Between the Glicko scale and the internal Glicko-2 one.
+const SCALE: f64 = 173.7178;
+const EPSILON: f64 = 0.000001;
+
+#[derive(Copy, Clone)]
+pub struct Rating {
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+}
+
+fn g(phi: f64) -> f64 {
+    return 1.0 / (1.0 + 3.0 * phi * phi / (PI * PI)).sqrt();
+}
+
+fn expected(mu: f64, mu_j: f64, phi_j: f64) -> f64 {
+    return 1.0 / (1.0 + (-g(phi_j) * (mu - mu_j)).exp());
+}
+
+impl Rating {
+    pub fn new() -> Self {
+        return Rating{rating: INITIAL_RATING, deviation: INITIAL_DEVIATION, volatility: INITIAL_VOLATILITY};
+    }
+
+    // A rating that is taken as exact, such as a bot's anchor.
+    pub fn fixed(rating: f64) -> Self {
+        return Rating{rating: rating, deviation: 0.0, volatility: 0.0};
+    }
+
+    // The rating after one rating period with these games, as (opponent, points) with points 1,
+    // 0.5 or 0.
+    pub fn update(&self, games: &[(Rating, f64)]) -> Rating {
+        let mu = (self.rating - INITIAL_RATING) / SCALE;
+        let phi = self.deviation / SCALE;
+        let sigma = self.volatility;
+        if games.len() == 0 {
+            let deviation = ((phi * phi + sigma * sigma).sqrt() * SCALE).min(INITIAL_DEVIATION);
+            return Rating{rating: self.rating, deviation: deviation, volatility: sigma};
+        }
+
+        // The estimated variance of the rating from the games alone, and the improvement they
+        // point to.
+        let mut v_inverse = 0.0;
+        let mut delta_sum = 0.0;
+        for (opponent, points) in games {
+            let mu_j = (opponent.rating - INITIAL_RATING) / SCALE;
+            let phi_j = opponent.deviation / SCALE;
+            let e = expected(mu, mu_j, phi_j);
+            v_inverse += g(phi_j) * g(phi_j) * e * (1.0 - e);
+            delta_sum += g(phi_j) * (points - e);
+        }
+        let v = 1.0 / v_inverse;
+        let delta = v * delta_sum;
+
+        // The new volatility, by the Illinois algorithm (step 5 of the paper).
+        let a = (sigma * sigma).ln();
+        let f = |x: f64| {
+            let ex = x.exp();
+            return ex * (delta * delta - phi * phi - v - ex) / (2.0 * (phi * phi + v + ex).powi(2)) - (x - a) / (TAU * TAU);
+        };
+        let mut big_a = a;
+        let mut big_b = if delta * delta > phi * phi + v {
+            (delta * delta - phi * phi - v).ln()
+        } else {
+            let mut k = 1.0;
+            while f(a - k * TAU) < 0.0 {
+                k += 1.0;
+            }
+            a - k * TAU
+        };
+        let mut f_a = f(big_a);
+        let mut f_b = f(big_b);
+        while (big_b - big_a).abs() > EPSILON {
+            let big_c = big_a + (big_a - big_b) * f_a / (f_b - f_a);
+            let f_c = f(big_c);
+            if f_c * f_b <= 0.0 {
+                big_a = big_b;
+                f_a = f_b;
+            } else {
+                f_a /= 2.0;
+            }
+            big_b = big_c;
+            f_b = f_c;
+        }
+        let volatility = (big_a / 2.0).exp();
+
+        let phi_star = (phi * phi + volatility * volatility).sqrt();
+        let phi_new = 1.0 / (1.0 / (phi_star * phi_star) + 1.0 / v).sqrt();
+        let mu_new = mu + phi_new * phi_new * delta_sum;
+        return Rating{
+            rating: mu_new * SCALE + INITIAL_RATING,
+            deviation: (phi_new * SCALE).min(INITIAL_DEVIATION),
+            volatility: volatility,
+        };
+    }
+}
@@ -0,0 +1,340 @@
+pub mod glicko;
+
+use std::fs;
+use std::io::{stdout, Write};
+use std::path::Path;
+use std::process::Command;
+
+use crate::chess::pgn::today;
+use crate::chess::player::Player;
+
+use glicko::Rating;
+
+// Named player profiles and their Glicko-2 ratings, kept in a local file. A game is rated when
+// both sides can be told apart: a person who has given a name in Setup (an unnamed "Human" is
+// not rated), or a bot. Bots with an anchor rating keep it, so that their games pin the scale
+// down; other bots, such as external engines, get a profile under their name like a person. Every
+// rated game is kept, which is where the record, the rating history and the results per opponent
+// on the stats screen come from.
+
+pub const PROFILE_FILE: &str = "profiles.txt";
+
+// A side of a game as far as ratings go.
+enum Side {
+    Profile(String),
+    Anchor(String, f64),
+}
+
+impl Side {
+    fn of(player: &Box<dyn Player>) -> Option<Self> {
+        let name = player.id_string();
+        if player.is_human() && name == "Human" {
+            return None;
+        }
+        return match player.anchor_rating() {
+            Some(r) => Some(Side::Anchor(name, r)),
+            None => Some(Side::Profile(name)),
+        };
+    }
+
+    fn name(&self) -> &str {
+        return match self {
+            Side::Profile(n) | Side::Anchor(n, _) => n,
+        };
+    }
+}
+
+pub struct Profile {
+    pub name: String,
+    pub rating: Rating,
+}
+
+// A rated game with both sides' ratings after it.
+pub struct RatedGame {
+    pub date: String,
+    pub white: String,
+    pub black: String,
+    pub result: String, // "1-0", "0-1" or "1/2-1/2"
+    pub white_rating: f64,
+    pub black_rating: f64,
+}
+
+impl RatedGame {
+    // The points `name` scored, the opponent and the opponent's rating after the game, if `name`
+    // played in it.
+    fn for_player(&self, name: &str) -> Option<(f64, &str, f64)> {
+        let white_points = match self.result.as_str() {
+            "1-0" => 1.0,
+            "0-1" => 0.0,
+            _ => 0.5,
+        };
+        if self.white == name {
+            return Some((white_points, &self.black, self.black_rating));
+        }
+        if self.black == name {
+            return Some((1.0 - white_points, &self.white, self.white_rating));
+        }
+        return None;
+    }
+
+    fn rating_of(&self, name: &str) -> Option<f64> {
+        if self.white == name {
+            return Some(self.white_rating);
+        }
+        if self.black == name {
+            return Some(self.black_rating);
+        }
+        return None;
+    }
+}
+
+// A player's results against one opponent.
+pub struct Matchup {
+    pub opponent: String,
+    pub games: u32,
+    pub points: f64,
+    pub opponent_ratings: f64, // added up, as they were before each game
+}
+
+impl Matchup {
+    // The "algorithm of 400": the opponents' average rating, plus 400 for each win and minus 400
+    // for each loss, per game.
+    pub fn performance(&self) -> f64 {
+        let wins_minus_losses = 2.0 * self.points - self.games as f64;
+        return (self.opponent_ratings + 400.0 * wins_minus_losses) / self.games as f64;
+    }
+}
+
+pub struct Profiles {
+    pub profiles: Vec<Profile>,
+    pub games: Vec<RatedGame>,
+}
+
+impl Profiles {
+    pub fn new() -> Self {
+        return Profiles{profiles: Vec::new(), games: Vec::new()};
+    }
+
+    // Tab separated lines: a profile's name, rating, deviation and volatility, or a rated game's
+    // date, players, result and both ratings after it.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = match fs::read_to_string(path) {
+            Ok(t) => t,
+            Err(e) => return Err(format!("Could not read {}: {}", path.display(), e)),
+        };
+        let mut profiles = Profiles::new();
+        for (n, line) in text.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let bad = || format!("{} line {}: {}", path.display(), n + 1, line);
+            let fields: Vec<&str> = line.split('\t').collect();
+            let number = |i: usize| fields.get(i).and_then(|f| f.parse::<f64>().ok()).ok_or_else(bad);
+            match (fields[0], fields.len()) {
+                ("profile", 5) => profiles.profiles.push(Profile{
+                    name: fields[1].to_string(),
+                    rating: Rating{rating: number(2)?, deviation: number(3)?, volatility: number(4)?},
+                }),
+                ("game", 7) => profiles.games.push(RatedGame{
+                    date: fields[1].to_string(),
+                    white: fields[2].to_string(),
+                    black: fields[3].to_string(),
+                    result: fields[4].to_string(),
+                    white_rating: number(5)?,
+                    black_rating: number(6)?,
+                }),
+                _ => return Err(bad()),
+            }
+        }
+        return Ok(profiles);
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let mut text = String::from("# jchess profiles\n");
+        for p in &self.profiles {
+            text.push_str(&format!("profile\t{}\t{:.2}\t{:.2}\t{:.6}\n", p.name, p.rating.rating, p.rating.deviation, p.rating.volatility));
+        }
+        for g in &self.games {
+            text.push_str(&format!("game\t{}\t{}\t{}\t{}\t{:.2}\t{:.2}\n", g.date, g.white, g.black, g.result, g.white_rating, g.black_rating));
+        }
+        return match fs::write(path, text) {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Could not write {}: {}", path.display(), e)),
+        };
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        return self.profiles.iter().find(|p| p.name == name);
+    }
+
+    fn rating(&self, side: &Side) -> Rating {
+        return match side {
+            Side::Anchor(_, r) => Rating::fixed(*r),
+            Side::Profile(name) => match self.get(name) {
+                Some(p) => p.rating,
+                None => Rating::new(),
+            },
+        };
+    }
+
+    fn set_rating(&mut self, name: &str, rating: Rating) {
+        match self.profiles.iter_mut().find(|p| p.name == name) {
+            Some(p) => p.rating = rating,
+            None => self.profiles.push(Profile{name: name.to_string(), rating: rating}),
+        }
+    }
+
+    // Rates a finished game. Returns a line for each profile whose rating changed, or nothing when
+    // the game is not rated.
+    pub fn record(&mut self, white: &Box<dyn Player>, black: &Box<dyn Player>, result: &str) -> Vec<String> {
+        let (white, black) = match (Side::of(white), Side::of(black)) {
+            (Some(w), Some(b)) if w.name() != b.name() => (w, b),
+            _ => return Vec::new(),
+        };
+        if let (Side::Anchor(..), Side::Anchor(..)) = (&white, &black) {
+            return Vec::new();
+        }
+        let white_points = match result {
+            "1-0" => 1.0,
+            "0-1" => 0.0,
+            _ => 0.5,
+        };
+        let (white_before, black_before) = (self.rating(&white), self.rating(&black));
+        let mut changes = Vec::new();
+        let mut after = |side: &Side, before: Rating, opponent: Rating, points: f64| -> f64 {
+            return match side {
+                Side::Anchor(_, r) => *r,
+                Side::Profile(name) => {
+                    let rating = before.update(&[(opponent, points)]);
+                    changes.push(format!("{}: {:.0} -> {:.0} ({:+.0})", name, before.rating, rating.rating, rating.rating - before.rating));
+                    self.set_rating(name, rating);
+                    rating.rating
+                },
+            };
+        };
+        let white_rating = after(&white, white_before, black_before, white_points);
+        let black_rating = after(&black, black_before, white_before, 1.0 - white_points);
+        self.games.push(RatedGame{
+            date: today(),
+            white: white.name().to_string(),
+            black: black.name().to_string(),
+            result: result.to_string(),
+            white_rating: white_rating,
+            black_rating: black_rating,
+        });
+        return changes;
+    }
+
+    // Wins, draws and losses.
+    pub fn record_of(&self, name: &str) -> (u32, u32, u32) {
+        let mut record = (0, 0, 0);
+        for (points, _, _) in self.games.iter().filter_map(|g| g.for_player(name)) {
+            if points > 0.75 {
+                record.0 += 1;
+            } else if points < 0.25 {
+                record.2 += 1;
+            } else {
+                record.1 += 1;
+            }
+        }
+        return record;
+    }
+
+    // Each game of the player as (game, rating after it).
+    pub fn history(&self, name: &str) -> Vec<(&RatedGame, f64)> {
+        return self.games.iter().filter_map(|g| g.rating_of(name).map(|r| (g, r))).collect();
+    }
+
+    // Results per opponent, most played first. The opponents' ratings are the ones they had going
+    // into each game.
+    pub fn matchups(&self, name: &str) -> Vec<Matchup> {
+        let mut matchups: Vec<Matchup> = Vec::new();
+        let mut last_rating: Vec<(&str, f64)> = Vec::new();
+        for g in &self.games {
+            let before = |player: &str, last: &Vec<(&str, f64)>| {
+                last.iter().rev().find(|(n, _)| *n == player).map(|(_, r)| *r).unwrap_or(glicko::INITIAL_RATING)
+            };
+            if let Some((points, opponent, opponent_after)) = g.for_player(name) {
+                // An anchored bot's rating never changes, so its rating after is the one before.
+                let opponent_before = if self.get(opponent).is_some() { before(opponent, &last_rating) } else { opponent_after };
+                match matchups.iter_mut().find(|m| m.opponent == opponent) {
+                    Some(m) => {
+                        m.games += 1;
+                        m.points += points;
+                        m.opponent_ratings += opponent_before;
+                    },
+                    None => matchups.push(Matchup{opponent: opponent.to_string(), games: 1, points: points, opponent_ratings: opponent_before}),
+                }
+            }
+            last_rating.push((&g.white, g.white_rating));
+            last_rating.push((&g.black, g.black_rating));
+        }
+        matchups.sort_by_key(|m| std::cmp::Reverse(m.games));
+        return matchups;
+    }
+
+    // The list of profiles, best rated first.
+    pub fn print_summary(&self, message: &str) {
+        let _ = Command::new("clear").status();
+        println!();
+        println!("  Player Profiles");
+        println!();
+        let mut profiles: Vec<&Profile> = self.profiles.iter().collect();
+        profiles.sort_by(|a, b| b.rating.rating.total_cmp(&a.rating.rating));
+        let width = profiles.iter().map(|p| p.name.len()).max().unwrap_or(0).max(4);
+        if profiles.len() == 0 {
+            println!("  No rated games yet. Name the players in Setup to have their games rated.");
+        } else {
+            println!("  {:<width$}  {:>6}  {:>4}  {:>5}  {:>5}", "Name", "Rating", "RD", "Games", "+/=/-", width = width);
+            for p in profiles {
+                let (w, d, l) = self.record_of(&p.name);
+                println!("  {:<width$}  {:>6.0}  {:>4.0}  {:>5}  {}/{}/{}", p.name, p.rating.rating, p.rating.deviation, w + d + l,
+                    w, d, l, width = width);
+            }
+        }
+        println!();
+        println!("  {}", message);
+        print!("  > ");
+        let _ = stdout().flush();
+    }
+
+    // One profile: its record, the last few games with the rating after each, and the results
+    // against each opponent.
+    pub fn print_profile(&self, profile: &Profile) {
+        const RECENT: usize = 10;
+        let _ = Command::new("clear").status();
+        let name = &profile.name;
+        let (w, d, l) = self.record_of(name);
+        println!();
+        println!("  {}", name);
+        println!();
+        println!("  Rating {:.0} +/- {:.0} (volatility {:.4})", profile.rating.rating, 2.0 * profile.rating.deviation,
+            profile.rating.volatility);
+        println!("  Record +{} ={} -{} in {} games", w, d, l, w + d + l);
+
+        let history = self.history(name);
+        println!();
+        println!("  Rating history");
+        let mut previous = glicko::INITIAL_RATING;
+        let skip = history.len().saturating_sub(RECENT);
+        for (i, (g, rating)) in history.iter().enumerate() {
+            if i >= skip {
+                let (points, opponent, _) = g.for_player(name).unwrap();
+                let score = if points > 0.75 { "won" } else if points < 0.25 { "lost" } else { "drew" };
+                println!("  {}  {:<4} {:<24} {:>6.0} ({:+.0})", g.date, score, opponent, rating, rating - previous);
+            }
+            previous = *rating;
+        }
+
+        let matchups = self.matchups(name);
+        let width = matchups.iter().map(|m| m.opponent.len()).max().unwrap_or(0).max(8);
+        println!();
+        println!("  {:<width$}  {:>5}  {:>5}  {:>11}", "Opponent", "Games", "Score", "Performance", width = width);
+        for m in matchups {
+            println!("  {:<width$}  {:>5}  {:>5.1}  {:>11.0}", m.opponent, m.games, m.points, m.performance(), width = width);
+        }
+        println!();
+        print!("  Press enter to go back ");
+        let _ = stdout().flush();
+    }
+}
@@ -0,0 +1,15 @@
+pub mod uci;
+pub mod xboard;
+
+use std::time::Duration;
+
+// The engine protocols, for running jchess bots under chess GUIs and tournament tools.
+
+// How long to think on one move with `left` on the clock: an even share of it over the moves to
+// the next time control (or 30 when there is none), plus most of the increment. A little is always
+// kept back for the time it takes to answer.
+pub fn time_for_move(left: Duration, inc: Duration, moves_to_go: Option<u32>) -> Duration {
+    let share = left / moves_to_go.unwrap_or(30).max(1) + inc * 3 / 4;
+    let margin = Duration::from_millis(50);
+    return share.min(left.saturating_sub(margin)).max(Duration::from_millis(10));
+}
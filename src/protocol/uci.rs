@@ -0,0 +1,203 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::bots::{from_spec, SPEC_HELP};
+use crate::chess::color::Color;
+use crate::chess::coordinate::{play_coordinate, to_coordinate};
+use crate::chess::fen::parse_fen;
+use crate::chess::game::Game;
+use crate::chess::player::{Limits, Player, Thinking};
+use crate::input::args::Args;
+use crate::input::next_line;
+
+use super::time_for_move;
+
+// Runs a jchess bot as a UCI engine over stdin and stdout, for chess GUIs and tournament tools:
+//   jchess uci [--bot SPEC]
+// The bot can also be changed with `setoption name Bot value SPEC`. Searching happens on its own
+// thread so that `stop` and `isready` are answered while it thinks. Pondering is not supported.
+
+const USAGE: &str = "Usage: jchess uci [--bot SPEC]";
+const DEFAULT_BOT: &str = "search";
+
+struct Engine {
+    spec: String,
+    bot: Box<dyn Player>,
+    game: Game,
+    searching: Option<(Arc<AtomicBool>, JoinHandle<()>)>,
+}
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &["bot"], &[], &format!("{}\n{}", USAGE, SPEC_HELP))?;
+    if args.positional.len() > 0 {
+        return Err(format!("{}\n{}", USAGE, SPEC_HELP));
+    }
+    let spec = args.get("bot").unwrap_or(String::from(DEFAULT_BOT));
+    let mut engine = Engine{spec: spec.clone(), bot: from_spec(&spec)?, game: start_position(), searching: None};
+
+    while let Some(line) = next_line() {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.len() == 0 {
+            continue;
+        }
+        match tokens[0] {
+            "uci" => {
+                println!("id name jchess {}", env!("CARGO_PKG_VERSION"));
+                println!("id author Joseph Bursey");
+                println!("option name Bot type string default {}", engine.spec);
+                println!("uciok");
+            },
+            "isready" => println!("readyok"),
+            "debug" | "ponderhit" | "register" => {},
+            "setoption" => {
+                engine.stop();
+                engine.set_option(&tokens[1..]);
+            },
+            "ucinewgame" => {
+                engine.stop();
+                engine.bot.reset();
+                engine.game = start_position();
+            },
+            "position" => {
+                engine.stop();
+                match parse_position(&tokens[1..]) {
+                    Ok(g) => engine.game = g,
+                    Err(e) => println!("info string {}", e),
+                }
+            },
+            "go" => {
+                engine.stop();
+                engine.go(&tokens[1..]);
+            },
+            "stop" => engine.stop(),
+            "quit" => break,
+            _ => println!("info string Unknown command: {}", tokens[0]),
+        }
+    }
+    engine.stop();
+    return Ok(());
+}
+
+impl Engine {
+    // Ends the search, if one is running, once it has given its move. Every command that changes
+    // the position, the bot or the search does this first, so one left running (an infinite one
+    // that was never stopped, say) cannot hang the engine.
+    fn stop(&mut self) {
+        if let Some((stop, handle)) = self.searching.take() {
+            stop.store(true, Ordering::Relaxed);
+            let _ = handle.join();
+        }
+    }
+
+    // setoption name <id> [value <x>]
+    fn set_option(&mut self, tokens: &[&str]) {
+        let value_at = tokens.iter().position(|t| *t == "value").unwrap_or(tokens.len());
+        let name = tokens[1.min(value_at)..value_at].join(" ");
+        let value = tokens[(value_at + 1).min(tokens.len())..].join(" ");
+        if !name.eq_ignore_ascii_case("Bot") {
+            println!("info string Unknown option: {}", name);
+            return;
+        }
+        match from_spec(&value) {
+            Ok(b) => {
+                self.bot = b;
+                self.spec = value;
+            },
+            Err(e) => println!("info string {}", e.replace('\n', " ")),
+        }
+    }
+
+    // Starts thinking on the current position; `bestmove` is printed when done. An infinite search
+    // waits for `stop` before giving its move, as the protocol asks.
+    fn go(&mut self, tokens: &[&str]) {
+        let limits = parse_go(tokens, self.game.current_color());
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let bot = self.bot.clone();
+        let game = self.game.clone();
+        let handle = thread::spawn(move || {
+            let start = Instant::now();
+            let root = game.clone();
+            let report = Box::new(move |t: &Thinking| println!("{}", info_line(t, start.elapsed(), &root)));
+            let result = bot.think(&game, limits, thread_stop.clone(), report);
+            while limits.infinite && !thread_stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(10));
+            }
+            match result {
+                Ok(m) => println!("bestmove {}", to_coordinate(&game, &m)),
+                Err(_) => println!("bestmove 0000"),
+            }
+        });
+        self.searching = Some((stop, handle));
+    }
+}
+
+fn start_position() -> Game {
+    let mut game = Game::new();
+    game.default_board();
+    return game;
+}
+
+// position [startpos | fen <fen>] [moves <move>...]
+fn parse_position(tokens: &[&str]) -> Result<Game, String> {
+    let moves_at = tokens.iter().position(|t| *t == "moves").unwrap_or(tokens.len());
+    let mut game = match tokens.first() {
+        Some(&"startpos") => start_position(),
+        Some(&"fen") => parse_fen(&tokens[1..moves_at].join(" "))?,
+        _ => return Err(String::from("Expected startpos or fen")),
+    };
+    for text in &tokens[(moves_at + 1).min(tokens.len())..] {
+        play_coordinate(&mut game, text)?;
+    }
+    return Ok(game);
+}
+
+// go [depth N] [movetime MS] [wtime MS] [btime MS] [winc MS] [binc MS] [movestogo N] [infinite]
+// Anything else, such as nodes or searchmoves, is skipped.
+fn parse_go(tokens: &[&str], to_move: Color) -> Limits {
+    let mut limits = Limits::new();
+    let mut clock = [None, None]; // time left for white and black
+    let mut inc = [Duration::ZERO, Duration::ZERO];
+    let mut moves_to_go = None;
+    let number = |i: usize| tokens.get(i + 1).and_then(|t| t.parse::<u64>().ok());
+    for (i, token) in tokens.iter().enumerate() {
+        match *token {
+            "depth" => limits.depth = number(i).map(|d| d as u32),
+            "movetime" => limits.movetime = number(i).map(Duration::from_millis),
+            "wtime" => clock[0] = number(i).map(Duration::from_millis),
+            "btime" => clock[1] = number(i).map(Duration::from_millis),
+            "winc" => inc[0] = number(i).map(Duration::from_millis).unwrap_or_default(),
+            "binc" => inc[1] = number(i).map(Duration::from_millis).unwrap_or_default(),
+            "movestogo" => moves_to_go = number(i).map(|n| n as u32),
+            "infinite" => limits.infinite = true,
+            _ => {},
+        }
+    }
+    let side = if to_move == Color::White { 0 } else { 1 };
+    if limits.movetime.is_none() && !limits.infinite {
+        if let Some(left) = clock[side] {
+            limits.movetime = Some(time_for_move(left, inc[side], moves_to_go));
+        }
+    }
+    return limits;
+}
+
+fn info_line(t: &Thinking, elapsed: Duration, root: &Game) -> String {
+    let ms = elapsed.as_millis().max(1);
+    let score = match t.mate {
+        Some(m) => format!("mate {}", m),
+        None => format!("cp {}", t.score),
+    };
+    // Coordinate notation needs the position each move is played from.
+    let mut game = root.clone();
+    let mut pv = Vec::new();
+    for m in &t.pv {
+        pv.push(to_coordinate(&game, m));
+        game.do_move(*m);
+        game.next_turn();
+    }
+    return format!("info depth {} score {} nodes {} nps {} time {} pv {}", t.depth, score, t.nodes,
+        t.nodes as u128 * 1000 / ms, ms, pv.join(" "));
+}
@@ -0,0 +1,346 @@
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::arena::DrawRules;
+use crate::bots::{from_spec, SPEC_HELP};
+use crate::chess::color::Color;
+use crate::chess::coordinate::{resolve_coordinate, to_coordinate};
+use crate::chess::fen::parse_fen;
+use crate::chess::game::Game;
+use crate::chess::player::{Limits, Player, Thinking};
+use crate::chess::r#move::Move;
+use crate::input::args::Args;
+use crate::input::next_line;
+
+use super::time_for_move;
+
+// Runs a jchess bot as an XBoard/WinBoard (CECP) engine over stdin and stdout:
+//   jchess xboard [--bot SPEC]
+// Protocol version 2 with usermove, setboard and ping. The engine thinks on its own thread, and the
+// thread plays its move on the shared game when it is done, unless the search was called off in
+// the meantime (by force, new, undo and the like); `?` makes it move at once instead.
+
+const USAGE: &str = "Usage: jchess xboard [--bot SPEC]";
+const DEFAULT_BOT: &str = "search";
+
+// What the commands and the thinking thread share.
+struct State {
+    game: Game,
+    draws: DrawRules,
+    undo: Vec<(Game, DrawRules)>,
+    engine: Option<Color>, // the side the engine plays, None in force mode
+    over: bool,            // no more thinking until the next new or setboard
+    post: bool,            // thinking output
+    generation: u64,       // bumped to throw away a search that is no longer wanted
+}
+
+impl State {
+    fn play(&mut self, m: Move) {
+        self.undo.push((self.game.clone(), self.draws.clone()));
+        self.game.do_move(m);
+        self.game.next_turn();
+        self.draws.record(&self.game, &m);
+    }
+
+    // Prints the result when the game has just ended.
+    fn announce_end(&mut self) {
+        let result = if !self.game.any_valid_moves() {
+            if !self.game.is_check() {
+                String::from("1/2-1/2 {Stalemate}")
+            } else if self.game.current_color() == Color::White {
+                String::from("0-1 {Black mates}")
+            } else {
+                String::from("1-0 {White mates}")
+            }
+        } else {
+            match self.draws.check(&self.game) {
+                Some(t) => format!("1/2-1/2 {{Draw by {}}}", t.to_string()),
+                None => return,
+            }
+        };
+        println!("{}", result);
+        self.over = true;
+    }
+
+    fn engine_to_move(&self) -> bool {
+        return !self.over && self.engine == Some(self.game.current_color());
+    }
+}
+
+// The time control as `level`, `st` and `sd` set it, and the engine's clock from `time`.
+struct Clock {
+    moves_per_control: u32, // 0 for the whole game
+    base: Option<Duration>,
+    inc: Duration,
+    per_move: Option<Duration>,
+    depth: Option<u32>,
+    left: Option<Duration>,
+}
+
+impl Clock {
+    fn new() -> Self {
+        return Clock{moves_per_control: 0, base: None, inc: Duration::ZERO, per_move: None, depth: None, left: None};
+    }
+
+    fn limits(&self, game: &Game) -> Limits {
+        let mut limits = Limits::new();
+        limits.depth = self.depth;
+        if self.per_move.is_some() {
+            limits.movetime = self.per_move;
+        } else if let Some(left) = self.left.or(self.base) {
+            let to_go = match self.moves_per_control {
+                0 => None,
+                n => Some(n - (game.history().len() / 2) as u32 % n),
+            };
+            limits.movetime = Some(time_for_move(left, self.inc, to_go));
+        }
+        return limits;
+    }
+}
+
+struct Engine {
+    state: Arc<Mutex<State>>,
+    bot: Box<dyn Player>,
+    clock: Clock,
+    searching: Option<(Arc<AtomicBool>, JoinHandle<()>)>,
+}
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let args = Args::parse(args, &["bot"], &[], &format!("{}\n{}", USAGE, SPEC_HELP))?;
+    if args.positional.len() > 0 {
+        return Err(format!("{}\n{}", USAGE, SPEC_HELP));
+    }
+    let bot = from_spec(&args.get("bot").unwrap_or(String::from(DEFAULT_BOT)))?;
+    let game = start_position();
+    let state = State{draws: DrawRules::new(&game), game: game, undo: Vec::new(), engine: Some(Color::Black), over: false, post: false, generation: 0};
+    let mut engine = Engine{state: Arc::new(Mutex::new(state)), bot: bot, clock: Clock::new(), searching: None};
+
+    while let Some(line) = next_line() {
+        let (command, rest) = match line.split_once(' ') {
+            Some((c, r)) => (c, r.trim()),
+            None => (line.as_str(), ""),
+        };
+        match command {
+            "" | "xboard" | "accepted" | "rejected" | "random" | "hard" | "easy" | "computer" | "name" | "rating" | "ics" | "draw" => {},
+            "protover" => {
+                println!("feature done=0");
+                println!("feature myname=\"jchess {}\" ping=1 setboard=1 usermove=1 san=0 colors=0 sigint=0 sigterm=0 reuse=1 analyze=0", env!("CARGO_PKG_VERSION"));
+                println!("feature done=1");
+            },
+            "new" => {
+                engine.cancel();
+                engine.bot.reset();
+                engine.clock.depth = None;
+                engine.clock.left = None;
+                engine.set_position(start_position());
+                engine.state.lock().unwrap().engine = Some(Color::Black);
+            },
+            "setboard" => {
+                engine.cancel();
+                match parse_fen(rest) {
+                    Ok(g) => engine.set_position(g),
+                    Err(e) => println!("Error (bad FEN): {}", e),
+                }
+            },
+            "force" => {
+                engine.cancel();
+                engine.state.lock().unwrap().engine = None;
+            },
+            "go" => {
+                engine.cancel();
+                let mut state = engine.state.lock().unwrap();
+                state.engine = Some(state.game.current_color());
+                drop(state);
+                engine.think();
+            },
+            "playother" => {
+                engine.cancel();
+                let mut state = engine.state.lock().unwrap();
+                state.engine = Some(if state.game.current_color() == Color::White { Color::Black } else { Color::White });
+            },
+            "usermove" => engine.user_move(rest),
+            "?" => engine.move_now(),
+            "undo" => engine.take_back(1),
+            "remove" => engine.take_back(2),
+            "result" => {
+                engine.cancel();
+                engine.state.lock().unwrap().over = true;
+            },
+            "level" => {
+                if let Err(e) = engine.set_level(rest) {
+                    println!("Error (bad level): {}", e);
+                }
+            },
+            "st" => match rest.parse::<f64>() {
+                Ok(s) if s > 0.0 => engine.clock.per_move = Some(Duration::from_secs_f64(s)),
+                _ => println!("Error (bad time): {}", rest),
+            },
+            "sd" => match rest.parse::<u32>() {
+                Ok(d) if d > 0 => engine.clock.depth = Some(d),
+                _ => println!("Error (bad depth): {}", rest),
+            },
+            "time" => match rest.parse::<u64>() {
+                Ok(cs) => engine.clock.left = Some(Duration::from_millis(cs * 10)),
+                _ => println!("Error (bad time): {}", rest),
+            },
+            "otim" => {},
+            "post" => engine.state.lock().unwrap().post = true,
+            "nopost" => engine.state.lock().unwrap().post = false,
+            "ping" => println!("pong {}", rest),
+            "quit" => break,
+            _ => println!("Error (unknown command): {}", command),
+        }
+    }
+    engine.cancel();
+    return Ok(());
+}
+
+impl Engine {
+    // Calls off a search without playing its move.
+    fn cancel(&mut self) {
+        self.state.lock().unwrap().generation += 1;
+        if let Some((stop, handle)) = self.searching.take() {
+            stop.store(true, Ordering::Relaxed);
+            let _ = handle.join();
+        }
+    }
+
+    // Stops a search early; its move is still played.
+    fn move_now(&mut self) {
+        if let Some((stop, handle)) = self.searching.take() {
+            stop.store(true, Ordering::Relaxed);
+            let _ = handle.join();
+        }
+    }
+
+    fn set_position(&mut self, game: Game) {
+        let mut state = self.state.lock().unwrap();
+        state.draws = DrawRules::new(&game);
+        state.game = game;
+        state.undo.clear();
+        state.over = false;
+    }
+
+    fn user_move(&mut self, text: &str) {
+        // A search still running is for a position that is gone now.
+        self.cancel();
+        let mut state = self.state.lock().unwrap();
+        let m = match resolve_coordinate(&state.game, text) {
+            Ok(m) => m,
+            Err(_) => {
+                println!("Illegal move: {}", text);
+                return;
+            },
+        };
+        state.play(m);
+        state.announce_end();
+        let think = state.engine_to_move();
+        drop(state);
+        if think {
+            self.think();
+        }
+    }
+
+    fn take_back(&mut self, plies: usize) {
+        self.cancel();
+        let mut state = self.state.lock().unwrap();
+        for _ in 0..plies {
+            if let Some((game, draws)) = state.undo.pop() {
+                state.game = game;
+                state.draws = draws;
+                state.over = false;
+            }
+        }
+    }
+
+    // level MPS BASE INC, with BASE in minutes or minutes:seconds and INC in seconds.
+    fn set_level(&mut self, text: &str) -> Result<(), String> {
+        let fields: Vec<&str> = text.split_whitespace().collect();
+        if fields.len() != 3 {
+            return Err(text.to_string());
+        }
+        let bad = || text.to_string();
+        let moves = fields[0].parse::<u32>().map_err(|_| bad())?;
+        let base = match fields[1].split_once(':') {
+            Some((m, s)) => m.parse::<u64>().map_err(|_| bad())? * 60 + s.parse::<u64>().map_err(|_| bad())?,
+            None => fields[1].parse::<u64>().map_err(|_| bad())? * 60,
+        };
+        let inc = fields[2].parse::<f64>().map_err(|_| bad())?;
+        self.clock = Clock{
+            moves_per_control: moves,
+            base: Some(Duration::from_secs(base)),
+            inc: Duration::from_secs_f64(inc.max(0.0)),
+            per_move: None,
+            depth: self.clock.depth,
+            left: None,
+        };
+        return Ok(());
+    }
+
+    // Starts thinking for the side to move. The move is played and sent from the search thread.
+    fn think(&mut self) {
+        let state = self.state.lock().unwrap();
+        if !state.engine_to_move() {
+            return;
+        }
+        let game = state.game.clone();
+        let generation = state.generation;
+        let post = state.post;
+        drop(state);
+
+        let limits = self.clock.limits(&game);
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let bot = self.bot.clone();
+        let shared = self.state.clone();
+        let handle = thread::spawn(move || {
+            let start = Instant::now();
+            let root = game.clone();
+            let report = Box::new(move |t: &Thinking| {
+                if post {
+                    println!("{}", thinking_line(t, start.elapsed(), &root));
+                }
+            });
+            let result = bot.think(&game, limits, thread_stop, report);
+            let mut state = shared.lock().unwrap();
+            if state.generation != generation {
+                return;
+            }
+            match result {
+                Ok(m) => {
+                    println!("move {}", to_coordinate(&game, &m));
+                    state.play(m);
+                    state.announce_end();
+                },
+                Err(e) => println!("Error (no move): {}", e),
+            }
+        });
+        self.searching = Some((stop, handle));
+    }
+}
+
+fn start_position() -> Game {
+    let mut game = Game::new();
+    game.default_board();
+    return game;
+}
+
+// ply score time nodes pv, with the time in centiseconds. Mates are scored 100000 and more, counted
+// down by the moves to go.
+fn thinking_line(t: &Thinking, elapsed: Duration, root: &Game) -> String {
+    let score = match t.mate {
+        Some(m) if m > 0 => 100000 + m,
+        Some(m) => -100000 + m,
+        None => t.score,
+    };
+    let mut game = root.clone();
+    let mut pv = Vec::new();
+    for m in &t.pv {
+        pv.push(to_coordinate(&game, m));
+        game.do_move(*m);
+        game.next_turn();
+    }
+    return format!("{} {} {} {} {}", t.depth, score, elapsed.as_millis() / 10, t.nodes, pv.join(" "));
+}
@@ -0,0 +1,24 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+// Local data files (weights, books, archives, ...) live in ~/.jchess so that they do not depend
+// on the directory jchess is run from.
+
+const DATA_DIR: &str = ".jchess";
+
+pub fn data_dir() -> PathBuf {
+    let home = env::var("HOME").unwrap_or(String::from("."));
+    let mut path = PathBuf::from(home);
+    path.push(DATA_DIR);
+    return path;
+}
+
+// Path to a file in the data directory. The directory is created if it does not exist yet.
+pub fn data_file(name: &str) -> PathBuf {
+    let dir = data_dir();
+    let _ = fs::create_dir_all(&dir);
+    let mut path = dir;
+    path.push(name);
+    return path;
+}
@@ -0,0 +1,275 @@
+pub mod syzygy;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::chess::color::Color;
+use crate::chess::game::Game;
+use crate::chess::piece::PieceKind;
+use crate::chess::r#move::Move;
+
+use syzygy::*;
+
+// Endgame tablebases in the Syzygy format. Only the directory is scanned up front; a table is read
+// the first time a position needs it. WDL values are from the side to move's point of view:
+// 2 win, 1 win that would be drawn by the fifty move rule (cursed), 0 draw, -1 blessed loss, -2 loss.
+// DTZ values are plies to the next capture or pawn move with the winning (or longest losing) play.
+
+pub const TABLEBASE_DIR: &str = "syzygy";
+
+struct Entry {
+    material: Material,
+    wdl: OnceLock<Option<Table>>,
+    dtz: OnceLock<Option<Table>>,
+}
+
+pub struct Tablebase {
+    dir: PathBuf,
+    entries: HashMap<String, usize>, // both material keys of each table
+    tables: Vec<Entry>,
+    indexing: Indexing,
+    pub max_pieces: usize,
+}
+
+impl Tablebase {
+    pub fn open(dir: &Path) -> Result<Self, String> {
+        let listing = match fs::read_dir(dir) {
+            Ok(l) => l,
+            Err(e) => return Err(format!("Could not read {}: {}", dir.display(), e)),
+        };
+        let mut tb = Tablebase{dir: dir.to_path_buf(), entries: HashMap::new(), tables: Vec::new(), indexing: Indexing::new(), max_pieces: 0};
+        for file in listing.flatten() {
+            let path = file.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(Kind::Wdl.extension()) {
+                continue;
+            }
+            let name = match path.file_stem().and_then(|s| s.to_str()) {
+                Some(n) => n.to_string(),
+                None => continue,
+            };
+            if let Some((white, black)) = parse_name(&name) {
+                let material = Material::new(&white, &black);
+                tb.max_pieces = tb.max_pieces.max(material.piece_count);
+                tb.entries.insert(material.key.clone(), tb.tables.len());
+                tb.entries.insert(material.key2.clone(), tb.tables.len());
+                tb.tables.push(Entry{material: material, wdl: OnceLock::new(), dtz: OnceLock::new()});
+            }
+        }
+        if tb.tables.len() == 0 {
+            return Err(format!("No tablebases found in {}", dir.display()));
+        }
+        return Ok(tb);
+    }
+
+    pub fn len(&self) -> usize {
+        return self.tables.len();
+    }
+
+    // Whether the tables can say anything about this position. Positions with castling rights
+    // are never in them.
+    pub fn covers(&self, game: &Game) -> bool {
+        let mut count = 0;
+        for f in 0..8 {
+            for r in 0..8 {
+                if game.piece_at(f, r).kind != PieceKind::None {
+                    count += 1;
+                }
+            }
+        }
+        return count <= self.max_pieces && !game.castle_rights().contains(&true);
+    }
+
+    fn table(&self, pos: &Position, kind: Kind) -> Option<(&Table, &Material)> {
+        let entry = &self.tables[*self.entries.get(&pos.key())?];
+        let cell = if kind == Kind::Wdl { &entry.wdl } else { &entry.dtz };
+        let table = cell.get_or_init(|| {
+            let mut path = self.dir.clone();
+            path.push(format!("{}.{}", entry.material.key, kind.extension()));
+            return Table::load(&path, kind, &entry.material, &self.indexing);
+        });
+        return match table {
+            Some(t) => Some((t, &entry.material)),
+            None => None,
+        };
+    }
+
+    fn probe_wdl_table(&self, game: &Game) -> Option<i32> {
+        let pos = position(game);
+        if pos.piece_count() == 2 {
+            return Some(0);
+        }
+        let (table, material) = self.table(&pos, Kind::Wdl)?;
+        return table.probe_wdl(&pos, material, &self.indexing);
+    }
+
+    // The WDL value of the position, or None if it is not in the tables.
+    pub fn probe_wdl(&self, game: &Game) -> Option<i32> {
+        if !self.covers(game) {
+            return None;
+        }
+        return Some(self.search(game, false)?.0);
+    }
+
+    // The tables leave out positions where a capture wins (the generator stores whatever
+    // compresses best there), and a stored draw can also be a loss for the same reason, so the
+    // captures have to be tried as well as the position looked up. With `zeroing` pawn moves are
+    // tried too, which DTZ needs. Also returns whether the best result comes from such a move.
+    fn search(&self, game: &Game, zeroing: bool) -> Option<(i32, bool)> {
+        let moves = game.list_valid_moves_with_underpromotions();
+        let mut best = -2;
+        let mut count = 0;
+        for m in &moves {
+            if !is_capture(game, m) && !(zeroing && m.piece.kind == PieceKind::Pawn) {
+                continue;
+            }
+            count += 1;
+            let value = -self.search(&child(game, m), false)?.0;
+            if value > best {
+                best = value;
+                if value >= 2 {
+                    return Some((value, true));
+                }
+            }
+        }
+
+        // If every move was tried there is nothing to look up, which also covers positions the
+        // tables get wrong such as those with an en passant capture.
+        let searched_all = count > 0 && count == moves.len();
+        let value = if searched_all { best } else { self.probe_wdl_table(game)? };
+        if best >= value {
+            return Some((best, best > 0 || searched_all));
+        }
+        return Some((value, false));
+    }
+
+    // The DTZ value of the position, negative when losing and 0 for draws.
+    pub fn probe_dtz(&self, game: &Game) -> Option<i32> {
+        if !self.covers(game) {
+            return None;
+        }
+        let (wdl, zeroing_best) = self.search(game, true)?;
+        if wdl == 0 {
+            return Some(0);
+        }
+        if zeroing_best {
+            return Some(dtz_before_zeroing(wdl));
+        }
+
+        let pos = position(game);
+        let (table, material) = self.table(&pos, Kind::Dtz)?;
+        if let DtzProbe::Value(dtz) = table.probe_dtz(&pos, material, &self.indexing, wdl)? {
+            return Some((dtz + if wdl == 1 || wdl == -1 { 100 } else { 0 }) * wdl.signum());
+        }
+
+        // The table only has the other side to move, so go one ply further and take the best move.
+        let mut min_dtz = 0xFFFF;
+        for m in game.list_valid_moves_with_underpromotions() {
+            let zeroing = is_capture(game, &m) || m.piece.kind == PieceKind::Pawn;
+            let next = child(game, &m);
+            let mut dtz = if zeroing {
+                -dtz_before_zeroing(self.search(&next, false)?.0)
+            } else {
+                -self.probe_dtz(&next)?
+            };
+            if dtz == 1 && next.is_check() && !next.any_valid_moves() {
+                min_dtz = 1;
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz < min_dtz && dtz.signum() == wdl.signum() {
+                min_dtz = dtz;
+            }
+        }
+        return Some(if min_dtz == 0xFFFF { -1 } else { min_dtz });
+    }
+
+    // The move that keeps the best result and makes the quickest progress towards it: the lowest
+    // DTZ when winning, the highest when losing. None when the position or a reply is not covered.
+    pub fn best_move(&self, game: &Game) -> Option<Move> {
+        if !self.covers(game) {
+            return None;
+        }
+        let mut best: Option<(i32, Move)> = None;
+        for m in game.list_valid_moves_with_underpromotions() {
+            let next = child(game, &m);
+            let mut dtz = if is_capture(game, &m) || m.piece.kind == PieceKind::Pawn {
+                dtz_before_zeroing(-self.probe_wdl(&next)?)
+            } else {
+                let d = -self.probe_dtz(&next)?;
+                d + d.signum()
+            };
+            if dtz == 2 && next.is_check() && !next.any_valid_moves() {
+                dtz = 1;
+            }
+            let rank = if dtz > 0 { 10000 - dtz } else if dtz < 0 { -10000 - dtz } else { 0 };
+            if best.is_none() || rank > best.unwrap().0 {
+                best = Some((rank, m));
+            }
+        }
+        return match best {
+            Some((_, m)) => Some(m),
+            None => None,
+        };
+    }
+}
+
+pub fn wdl_string(wdl: i32) -> String {
+    return match wdl {
+        2 => String::from("Tablebase win"),
+        1 => String::from("Tablebase win (cursed)"),
+        0 => String::from("Tablebase draw"),
+        -1 => String::from("Tablebase loss (blessed)"),
+        _ => String::from("Tablebase loss"),
+    };
+}
+
+// The DTZ of the move before a capture or pawn move with the given result.
+fn dtz_before_zeroing(wdl: i32) -> i32 {
+    return match wdl {
+        2 => 1,
+        1 => 101,
+        -1 => -101,
+        -2 => -1,
+        _ => 0,
+    };
+}
+
+fn position(game: &Game) -> Position {
+    let mut pos = Position{board: [0; 64], black_to_move: game.current_color() == Color::Black};
+    for f in 0..8 {
+        for r in 0..8 {
+            let p = game.piece_at(f, r);
+            let code = match p.kind {
+                PieceKind::None => continue,
+                PieceKind::Pawn => 1,
+                PieceKind::Knight => 2,
+                PieceKind::Bishop => 3,
+                PieceKind::Rook => 4,
+                PieceKind::Queen => 5,
+                PieceKind::King => 6,
+            };
+            pos.board[r * 8 + f] = if p.color == Color::Black { code | 8 } else { code };
+        }
+    }
+    return pos;
+}
+
+fn is_capture(game: &Game, m: &Move) -> bool {
+    if m.en_passant.is_some() {
+        return true;
+    }
+    return match (m.dest.0.index(), m.dest.1.index()) {
+        (Some(f), Some(r)) => game.piece_at(f, r).kind != PieceKind::None,
+        _ => false,
+    };
+}
+
+fn child(game: &Game, m: &Move) -> Game {
+    let mut next = game.clone();
+    next.do_move(*m);
+    next.next_turn();
+    return next;
+}
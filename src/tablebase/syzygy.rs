@@ -0,0 +1,910 @@
+use std::fs;
+use std::path::Path;
+
+// Decoding of Syzygy .rtbw (win/draw/loss) and .rtbz (distance to zeroing) files. The layout and
+// the indexing scheme follow the reference prober; the comments below only note what is needed to
+// follow the code.
+//
+// Squares are numbered a1 = 0, b1 = 1, ..., h8 = 63. Pieces use the codes stored in the files:
+// pawn 1, knight 2, bishop 3, rook 4, queen 5, king 6, and 8 more for black, so that xor 8 swaps
+// the color of a piece and xor 56 mirrors a square vertically.
+
+pub const TB_PIECES: usize = 6;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+
+// Flags stored per table
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+const PIECE_CHARS: [char; 7] = [' ', 'P', 'N', 'B', 'R', 'Q', 'K'];
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Kind {
+    Wdl,
+    Dtz,
+}
+
+impl Kind {
+    pub fn extension(self) -> &'static str {
+        return match self {
+            Kind::Wdl => "rtbw",
+            Kind::Dtz => "rtbz",
+        };
+    }
+}
+
+// A position reduced to what the tables need.
+pub struct Position {
+    pub board: [u8; 64],
+    pub black_to_move: bool,
+}
+
+impl Position {
+    pub fn piece_count(&self) -> usize {
+        return self.board.iter().filter(|p| **p != 0).count();
+    }
+
+    // The material as a table name, white first: "KRPvKR".
+    pub fn key(&self) -> String {
+        return format!("{}v{}", self.side_string(0), self.side_string(8));
+    }
+
+    fn side_string(&self, color: u8) -> String {
+        let mut s = String::new();
+        for kind in (1..7).rev() {
+            for p in self.board.iter() {
+                if *p == kind | color {
+                    s.push(PIECE_CHARS[kind as usize]);
+                }
+            }
+        }
+        return s;
+    }
+}
+
+// Splits a table name like "KRPvKR" into its two sides, checking that it is one.
+pub fn parse_name(name: &str) -> Option<(String, String)> {
+    let (white, black) = name.split_once('v')?;
+    for side in [white, black] {
+        if !side.starts_with('K') || side.matches('K').count() != 1
+            || !side.chars().all(|c| PIECE_CHARS[1..].contains(&c))
+        {
+            return None;
+        }
+    }
+    if white.len() + black.len() > TB_PIECES {
+        return None;
+    }
+    return Some((white.to_string(), black.to_string()));
+}
+
+fn file_of(sq: usize) -> usize {
+    return sq & 7;
+}
+
+fn rank_of(sq: usize) -> usize {
+    return sq >> 3;
+}
+
+// Signed distance of a square from the a1-h8 diagonal: negative below it, positive above.
+fn off_diagonal(sq: usize) -> i32 {
+    return rank_of(sq) as i32 - file_of(sq) as i32;
+}
+
+// The constant tables of the indexing scheme.
+pub struct Indexing {
+    map_pawns: [usize; 64],
+    map_b1h1h7: [usize; 64],
+    map_a1d1d4: [usize; 64],
+    map_kk: [[u64; 64]; 10],
+    binomial: [[u64; 64]; 6],
+    lead_pawn_idx: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+impl Indexing {
+    pub fn new() -> Self {
+        let mut ix = Indexing{
+            map_pawns: [0; 64],
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            binomial: [[0; 64]; 6],
+            lead_pawn_idx: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+        };
+
+        // Squares below the a1-h8 diagonal to 0..27
+        let mut code = 0;
+        for s in 0..64 {
+            if off_diagonal(s) < 0 {
+                ix.map_b1h1h7[s] = code;
+                code += 1;
+            }
+        }
+
+        // The a1-d1-d4 triangle to 0..9, the squares on the diagonal last
+        let mut diagonal = Vec::new();
+        code = 0;
+        for s in 0..28 {
+            if off_diagonal(s) < 0 && file_of(s) <= 3 {
+                ix.map_a1d1d4[s] = code;
+                code += 1;
+            } else if off_diagonal(s) == 0 && file_of(s) <= 3 {
+                diagonal.push(s);
+            }
+        }
+        for s in diagonal {
+            ix.map_a1d1d4[s] = code;
+            code += 1;
+        }
+
+        // The 462 legal placements of two kings with the first in the a1-d1-d4 triangle. With the
+        // first king on the diagonal the second one is kept on or below it.
+        let mut both_on_diagonal = Vec::new();
+        let mut code: u64 = 0;
+        for idx in 0..10 {
+            for s1 in 0..28 {
+                if ix.map_a1d1d4[s1] != idx || (idx == 0 && s1 != 1) {
+                    continue;
+                }
+                for s2 in 0..64 {
+                    let near = (file_of(s1) as i32 - file_of(s2) as i32).abs() <= 1
+                        && (rank_of(s1) as i32 - rank_of(s2) as i32).abs() <= 1;
+                    if near || (off_diagonal(s1) == 0 && off_diagonal(s2) > 0) {
+                        continue;
+                    }
+                    if off_diagonal(s1) == 0 && off_diagonal(s2) == 0 {
+                        both_on_diagonal.push((idx, s2));
+                    } else {
+                        ix.map_kk[idx][s2] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, s2) in both_on_diagonal {
+            ix.map_kk[idx][s2] = code;
+            code += 1;
+        }
+
+        // binomial[k][n]: ways to choose k of n
+        ix.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..6 {
+                if k > n {
+                    break;
+                }
+                ix.binomial[k][n] = (if k > 0 { ix.binomial[k - 1][n - 1] } else { 0 })
+                    + (if k < n { ix.binomial[k][n - 1] } else { 0 });
+            }
+        }
+
+        // map_pawns numbers a2-h7 from the edges inwards, so the pawn with the highest value is
+        // the leading one: nearest the edge, and the lowest of those.
+        let mut available = 47;
+        for lead in 1..6 {
+            for f in 0..4 {
+                let mut idx = 0;
+                for r in 1..7 {
+                    let sq = r * 8 + f;
+                    if lead == 1 {
+                        ix.map_pawns[sq] = available;
+                        ix.map_pawns[sq ^ 7] = available - 1;
+                        available -= 2;
+                    }
+                    ix.lead_pawn_idx[lead][sq] = idx;
+                    idx += ix.binomial[lead - 1][ix.map_pawns[sq]];
+                }
+                ix.lead_pawns_size[lead][f] = idx;
+            }
+        }
+        return ix;
+    }
+}
+
+// The indexing and compression data for one side to move and, with pawns, one leading file.
+#[derive(Clone)]
+struct PairsData {
+    flags: u8,
+    max_sym_len: u8,
+    min_sym_len: u8,
+    num_blocks: usize,
+    block_size: usize,
+    span: usize,
+    lowest_sym: usize,   // offsets into the file
+    btree: usize,
+    block_length: usize,
+    block_length_size: usize,
+    sparse_index: usize,
+    sparse_index_size: usize,
+    data: usize,
+    base64: Vec<u64>,
+    symlen: Vec<u8>,
+    pieces: [u8; TB_PIECES],
+    group_idx: [u64; TB_PIECES + 1],
+    group_len: [usize; TB_PIECES + 1],
+    map_idx: [usize; 4], // win, loss, cursed win, blessed loss (DTZ only)
+}
+
+impl PairsData {
+    fn new() -> Self {
+        return PairsData{
+            flags: 0, max_sym_len: 0, min_sym_len: 0, num_blocks: 0, block_size: 0, span: 0,
+            lowest_sym: 0, btree: 0, block_length: 0, block_length_size: 0, sparse_index: 0,
+            sparse_index_size: 0, data: 0, base64: Vec::new(), symlen: Vec::new(),
+            pieces: [0; TB_PIECES], group_idx: [0; TB_PIECES + 1], group_len: [0; TB_PIECES + 1],
+            map_idx: [0; 4],
+        };
+    }
+}
+
+// What is known about a table from its name alone.
+pub struct Material {
+    pub key: String,  // white has the first side of the name
+    pub key2: String, // black has it
+    pub piece_count: usize,
+    pub has_pawns: bool,
+    pub has_unique_pieces: bool,
+    pub pawn_count: [usize; 2], // leading color, other color
+}
+
+impl Material {
+    pub fn new(white: &str, black: &str) -> Self {
+        let count = |s: &str, c: char| s.chars().filter(|x| *x == c).count();
+        let mut unique = false;
+        for side in [white, black] {
+            for c in ['P', 'N', 'B', 'R', 'Q'] {
+                if count(side, c) == 1 {
+                    unique = true;
+                }
+            }
+        }
+        // The side with fewer pawns leads, for better compression.
+        let (wp, bp) = (count(white, 'P'), count(black, 'P'));
+        let white_leads = bp == 0 || (wp > 0 && bp >= wp);
+        return Material{
+            key: format!("{}v{}", white, black),
+            key2: format!("{}v{}", black, white),
+            piece_count: white.len() + black.len(),
+            has_pawns: wp + bp > 0,
+            has_unique_pieces: unique,
+            pawn_count: if white_leads { [wp, bp] } else { [bp, wp] },
+        };
+    }
+
+    fn symmetric(&self) -> bool {
+        return self.key == self.key2;
+    }
+}
+
+// The result of looking a position up in a DTZ table, which only stores one side to move.
+pub enum DtzProbe {
+    Value(i32),
+    ChangeStm,
+}
+
+enum Lookup {
+    At(usize, u64, usize), // leading file, index, table side
+    OtherSide,             // a DTZ table that does not store this side to move
+}
+
+// A loaded .rtbw or .rtbz file.
+pub struct Table {
+    kind: Kind,
+    bytes: Vec<u8>,
+    items: Vec<Vec<PairsData>>, // [side to move][leading file]
+    map: usize,
+}
+
+impl Table {
+    pub fn load(path: &Path, kind: Kind, mat: &Material, ix: &Indexing) -> Option<Self> {
+        let bytes = fs::read(path).ok()?;
+        let magic = if kind == Kind::Wdl { WDL_MAGIC } else { DTZ_MAGIC };
+        if bytes.len() < 5 || bytes[0..4] != magic {
+            return None;
+        }
+        let mut table = Table{kind: kind, bytes: bytes, items: vec![vec![PairsData::new(); 4]; 2], map: 0};
+        table.setup(mat, ix)?;
+        return Some(table);
+    }
+
+    fn byte(&self, o: usize) -> u8 {
+        return if o < self.bytes.len() { self.bytes[o] } else { 0 };
+    }
+
+    fn u16_le(&self, o: usize) -> u16 {
+        return u16::from_le_bytes([self.byte(o), self.byte(o + 1)]);
+    }
+
+    fn u32_le(&self, o: usize) -> u32 {
+        return u32::from_le_bytes([self.byte(o), self.byte(o + 1), self.byte(o + 2), self.byte(o + 3)]);
+    }
+
+    fn u32_be(&self, o: usize) -> u32 {
+        return u32::from_be_bytes([self.byte(o), self.byte(o + 1), self.byte(o + 2), self.byte(o + 3)]);
+    }
+
+    fn u64_be(&self, o: usize) -> u64 {
+        return (self.u32_be(o) as u64) << 32 | self.u32_be(o + 4) as u64;
+    }
+
+    fn sides(&self, mat: &Material) -> usize {
+        return if self.kind == Kind::Wdl && !mat.symmetric() { 2 } else { 1 };
+    }
+
+    // Reads the header: piece order and groups, the Huffman tables and where each part starts.
+    fn setup(&mut self, mat: &Material, ix: &Indexing) -> Option<()> {
+        let flags = self.byte(4);
+        if (flags & 2 != 0) != mat.has_pawns || (flags & 1 != 0) == mat.symmetric() {
+            return None;
+        }
+        let mut p = 5;
+        let sides = self.sides(mat);
+        let files = if mat.has_pawns { 4 } else { 1 };
+        let pp = mat.has_pawns && mat.pawn_count[1] > 0;
+
+        for f in 0..files {
+            let order = [
+                [self.byte(p) & 0xF, if pp { self.byte(p + 1) & 0xF } else { 0xF }],
+                [self.byte(p) >> 4, if pp { self.byte(p + 1) >> 4 } else { 0xF }],
+            ];
+            p += if pp { 2 } else { 1 };
+            for k in 0..mat.piece_count {
+                for i in 0..sides {
+                    self.items[i][f].pieces[k] = if i == 1 { self.byte(p) >> 4 } else { self.byte(p) & 0xF };
+                }
+                p += 1;
+            }
+            for i in 0..sides {
+                set_groups(&mut self.items[i][f], mat, order[i], f, ix);
+            }
+        }
+        p += p & 1;
+
+        for f in 0..files {
+            for i in 0..sides {
+                p = self.set_sizes(i, f, p)?;
+            }
+        }
+
+        if self.kind == Kind::Dtz {
+            self.map = p;
+            for f in 0..files {
+                let flags = self.items[0][f].flags;
+                if flags & FLAG_MAPPED == 0 {
+                    continue;
+                }
+                for i in 0..4 {
+                    if flags & FLAG_WIDE != 0 {
+                        p += p & 1;
+                        self.items[0][f].map_idx[i] = (p - self.map) / 2 + 1;
+                        p += 2 * self.u16_le(p) as usize + 2;
+                    } else {
+                        self.items[0][f].map_idx[i] = p - self.map + 1;
+                        p += self.byte(p) as usize + 1;
+                    }
+                }
+            }
+            p += p & 1;
+        }
+
+        for f in 0..files {
+            for i in 0..sides {
+                self.items[i][f].sparse_index = p;
+                p += self.items[i][f].sparse_index_size * 6;
+            }
+        }
+        for f in 0..files {
+            for i in 0..sides {
+                self.items[i][f].block_length = p;
+                p += self.items[i][f].block_length_size * 2;
+            }
+        }
+        for f in 0..files {
+            for i in 0..sides {
+                p = (p + 0x3F) & !0x3F;
+                self.items[i][f].data = p;
+                p += self.items[i][f].num_blocks * self.items[i][f].block_size;
+            }
+        }
+        if p > self.bytes.len() {
+            return None;
+        }
+        return Some(());
+    }
+
+    fn set_sizes(&mut self, side: usize, file: usize, mut p: usize) -> Option<usize> {
+        let flags = self.byte(p);
+        p += 1;
+        let mut d = self.items[side][file].clone();
+        d.flags = flags;
+        if flags & FLAG_SINGLE_VALUE != 0 {
+            d.min_sym_len = self.byte(p);
+            self.items[side][file] = d;
+            return Some(p + 1);
+        }
+
+        let groups = d.group_len.iter().position(|l| *l == 0).unwrap_or(TB_PIECES);
+        let tb_size = d.group_idx[groups];
+        d.block_size = 1 << self.byte(p);
+        d.span = 1 << self.byte(p + 1);
+        d.sparse_index_size = tb_size.div_ceil(d.span as u64) as usize;
+        let padding = self.byte(p + 2) as usize;
+        d.num_blocks = self.u32_le(p + 3) as usize;
+        d.block_length_size = d.num_blocks + padding;
+        d.max_sym_len = self.byte(p + 7);
+        d.min_sym_len = self.byte(p + 8);
+        p += 9;
+        if d.max_sym_len < d.min_sym_len || d.min_sym_len == 0 {
+            return None;
+        }
+        d.lowest_sym = p;
+
+        // Canonical Huffman codes: base64[l] is the lowest code of length min + l, left aligned in
+        // 64 bits, so that a code can be found by comparing the buffer against base64 in order.
+        let n = (d.max_sym_len - d.min_sym_len + 1) as usize;
+        d.base64 = vec![0; n];
+        for i in (0..n - 1).rev() {
+            d.base64[i] = d.base64[i + 1].wrapping_add(self.u16_le(p + 2 * i) as u64)
+                .wrapping_sub(self.u16_le(p + 2 * i + 2) as u64) / 2;
+        }
+        for i in 0..n {
+            d.base64[i] = d.base64[i].checked_shl(64 - i as u32 - d.min_sym_len as u32).unwrap_or(0);
+        }
+        p += n * 2;
+
+        // Every symbol is either a value or a pair of two other symbols. symlen is the number of
+        // values a symbol expands to, minus one.
+        let count = self.u16_le(p) as usize;
+        p += 2;
+        d.btree = p;
+        d.symlen = vec![0; count];
+        let mut visited = vec![false; count];
+        for s in 0..count {
+            if !visited[s] {
+                let len = self.set_symlen(&mut d, s, &mut visited);
+                d.symlen[s] = len;
+            }
+        }
+        self.items[side][file] = d;
+        return Some(p + count * 3 + (count & 1));
+    }
+
+    fn set_symlen(&self, d: &mut PairsData, s: usize, visited: &mut Vec<bool>) -> u8 {
+        visited[s] = true;
+        let right = self.btree_right(d, s);
+        if right == 0xFFF {
+            return 0;
+        }
+        let left = self.btree_left(d, s);
+        if left < visited.len() && !visited[left] {
+            d.symlen[left] = self.set_symlen(d, left, visited);
+        }
+        if right < visited.len() && !visited[right] {
+            d.symlen[right] = self.set_symlen(d, right, visited);
+        }
+        let sum = |i: usize| if i < d.symlen.len() { d.symlen[i] } else { 0 };
+        return sum(left).wrapping_add(sum(right)).wrapping_add(1);
+    }
+
+    fn btree_left(&self, d: &PairsData, s: usize) -> usize {
+        let o = d.btree + 3 * s;
+        return ((self.byte(o + 1) as usize & 0xF) << 8) | self.byte(o) as usize;
+    }
+
+    fn btree_right(&self, d: &PairsData, s: usize) -> usize {
+        let o = d.btree + 3 * s;
+        return ((self.byte(o + 2) as usize) << 4) | (self.byte(o + 1) as usize >> 4);
+    }
+
+    // The value stored at an index of the table.
+    fn decompress(&self, d: &PairsData, idx: u64) -> Option<i32> {
+        if d.flags & FLAG_SINGLE_VALUE != 0 {
+            return Some(d.min_sym_len as i32);
+        }
+        let span = d.span as u64;
+        let k = (idx / span) as usize;
+        if k >= d.sparse_index_size {
+            return None;
+        }
+
+        // The sparse index gives the block and offset of the value every `span` values; walk the
+        // block lengths from there to the block holding idx.
+        let mut block = self.u32_le(d.sparse_index + 6 * k) as i64;
+        let mut offset = self.u16_le(d.sparse_index + 6 * k + 4) as i64;
+        offset += (idx % span) as i64 - (span / 2) as i64;
+        let length = |b: i64| self.u16_le(d.block_length + 2 * b as usize) as i64;
+        while offset < 0 {
+            block -= 1;
+            if block < 0 {
+                return None;
+            }
+            offset += length(block) + 1;
+        }
+        while offset > length(block) {
+            offset -= length(block) + 1;
+            block += 1;
+            if block as usize >= d.block_length_size {
+                return None;
+            }
+        }
+
+        // Read symbols from the start of the block until the one covering offset.
+        let mut ptr = d.data + block as usize * d.block_size;
+        let mut buf = self.u64_be(ptr);
+        ptr += 8;
+        let mut buf_size = 64;
+        let min = d.min_sym_len as usize;
+        let mut sym;
+        loop {
+            let mut len = 0;
+            while len + 1 < d.base64.len() && buf < d.base64[len] {
+                len += 1;
+            }
+            sym = (((buf - d.base64[len]) >> (64 - len - min)) as u16)
+                .wrapping_add(self.u16_le(d.lowest_sym + 2 * len)) as usize;
+            if sym >= d.symlen.len() {
+                return None;
+            }
+            if offset < d.symlen[sym] as i64 + 1 {
+                break;
+            }
+            offset -= d.symlen[sym] as i64 + 1;
+            len += min;
+            buf <<= len;
+            buf_size -= len;
+            if buf_size <= 32 {
+                buf_size += 32;
+                buf |= (self.u32_be(ptr) as u64) << (64 - buf_size);
+                ptr += 4;
+            }
+        }
+
+        // Then expand the pairs down to the single value.
+        while d.symlen[sym] != 0 {
+            let left = self.btree_left(d, sym);
+            if left >= d.symlen.len() {
+                return None;
+            }
+            if offset < d.symlen[left] as i64 + 1 {
+                sym = left;
+            } else {
+                offset -= d.symlen[left] as i64 + 1;
+                sym = self.btree_right(d, sym);
+                if sym >= d.symlen.len() {
+                    return None;
+                }
+            }
+        }
+        return Some(self.btree_left(d, sym) as i32);
+    }
+
+    // DTZ values are stored remapped by frequency, and in moves rather than plies unless the
+    // flags say otherwise. Returns plies.
+    fn map_dtz(&self, file: usize, mut value: i32, wdl: i32) -> i32 {
+        let d = &self.items[0][file];
+        let which = match wdl {
+            2 => 0,
+            -2 => 1,
+            1 => 2,
+            _ => 3,
+        };
+        if d.flags & FLAG_MAPPED != 0 {
+            let idx = d.map_idx[which] + value as usize;
+            value = if d.flags & FLAG_WIDE != 0 {
+                self.u16_le(self.map + 2 * idx) as i32
+            } else {
+                self.byte(self.map + idx) as i32
+            };
+        }
+        if (wdl == 2 && d.flags & FLAG_WIN_PLIES == 0) || (wdl == -2 && d.flags & FLAG_LOSS_PLIES == 0)
+            || wdl == 1 || wdl == -1
+        {
+            value *= 2;
+        }
+        return value + 1;
+    }
+
+    pub fn probe_wdl(&self, pos: &Position, mat: &Material, ix: &Indexing) -> Option<i32> {
+        return match self.index(pos, mat, ix)? {
+            Lookup::At(file, idx, side) => Some(self.decompress(&self.items[side][file], idx)? - 2),
+            Lookup::OtherSide => None,
+        };
+    }
+
+    // `wdl` is the position's value, which selects how the stored number is decoded.
+    pub fn probe_dtz(&self, pos: &Position, mat: &Material, ix: &Indexing, wdl: i32) -> Option<DtzProbe> {
+        return match self.index(pos, mat, ix)? {
+            Lookup::At(file, idx, _) => {
+                let value = self.decompress(&self.items[0][file], idx)?;
+                Some(DtzProbe::Value(self.map_dtz(file, value, wdl)))
+            },
+            Lookup::OtherSide => Some(DtzProbe::ChangeStm),
+        };
+    }
+
+    // Maps the position to where its value is stored.
+    fn index(&self, pos: &Position, mat: &Material, ix: &Indexing) -> Option<Lookup> {
+        let key = pos.key();
+        // Tables are stored with the stronger side as white, and symmetric ones with white to move
+        // only, so the position may have to be seen with colors swapped and the board mirrored.
+        let flip = (mat.symmetric() && pos.black_to_move) || key != mat.key;
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = (flip ^ pos.black_to_move) as usize;
+
+        let mut squares = [0usize; TB_PIECES];
+        let mut pieces = [0u8; TB_PIECES];
+        let mut size = 0;
+        let mut lead_count = 0;
+        let mut lead_color = 0;
+        let mut file = 0;
+
+        // With pawns there is one table per file of the leading pawn, which is the one with the
+        // highest map_pawns value.
+        if mat.has_pawns {
+            let pc = self.items[0][0].pieces[0] ^ flip_color;
+            lead_color = pc;
+            for s in 0..64 {
+                if pos.board[s] == pc {
+                    if size >= TB_PIECES {
+                        return None;
+                    }
+                    squares[size] = s ^ flip_squares;
+                    size += 1;
+                }
+            }
+            lead_count = size;
+            if lead_count == 0 {
+                return None;
+            }
+            let mut best = 0;
+            for i in 1..lead_count {
+                if ix.map_pawns[squares[i]] > ix.map_pawns[squares[best]] {
+                    best = i;
+                }
+            }
+            squares.swap(0, best);
+            file = file_of(squares[0]);
+            if file > 3 {
+                file = 7 - file;
+            }
+        }
+
+        if self.kind == Kind::Dtz {
+            let flags = self.items[0][file].flags;
+            if (flags & FLAG_STM) as usize != stm && !(mat.symmetric() && !mat.has_pawns) {
+                return Some(Lookup::OtherSide);
+            }
+        }
+
+        for s in 0..64 {
+            if pos.board[s] != 0 && !(mat.has_pawns && pos.board[s] == lead_color) {
+                if size >= TB_PIECES {
+                    return None;
+                }
+                squares[size] = s ^ flip_squares;
+                pieces[size] = pos.board[s] ^ flip_color;
+                size += 1;
+            }
+        }
+        if size != mat.piece_count {
+            return None;
+        }
+
+        let side = stm % self.sides(mat);
+        let d = &self.items[side][file];
+
+        // Put the pieces in the order the table stores them.
+        for i in lead_count..size - 1 {
+            for j in i + 1..size {
+                if d.pieces[i] == pieces[j] {
+                    pieces.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+
+        // Mirror so that the leading piece is on files a-d.
+        if file_of(squares[0]) > 3 {
+            for s in squares.iter_mut().take(size) {
+                *s ^= 7;
+            }
+        }
+
+        let mut idx: u64;
+        if mat.has_pawns {
+            idx = ix.lead_pawn_idx[lead_count][squares[0]];
+            squares[1..lead_count].sort_by_key(|s| ix.map_pawns[*s]);
+            for i in 1..lead_count {
+                idx += ix.binomial[i][ix.map_pawns[squares[i]]];
+            }
+        } else {
+            // Without pawns, also mirror to ranks 1-4 and then below the a1-h8 diagonal.
+            if rank_of(squares[0]) > 3 {
+                for s in squares.iter_mut().take(size) {
+                    *s ^= 56;
+                }
+            }
+            for i in 0..d.group_len[0] {
+                let off = off_diagonal(squares[i]);
+                if off == 0 {
+                    continue;
+                }
+                if off > 0 {
+                    for s in squares.iter_mut().take(size).skip(i) {
+                        *s = ((*s >> 3) | (*s << 3)) & 63;
+                    }
+                }
+                break;
+            }
+
+            if mat.has_unique_pieces {
+                // The first three pieces are encoded together.
+                let adjust1 = (squares[1] > squares[0]) as usize;
+                let adjust2 = (squares[2] > squares[0]) as usize + (squares[2] > squares[1]) as usize;
+                idx = if off_diagonal(squares[0]) != 0 {
+                    ((ix.map_a1d1d4[squares[0]] * 63 + squares[1] - adjust1) * 62 + squares[2] - adjust2) as u64
+                } else if off_diagonal(squares[1]) != 0 {
+                    ((6 * 63 + rank_of(squares[0]) * 28 + ix.map_b1h1h7[squares[1]]) * 62 + squares[2] - adjust2) as u64
+                } else if off_diagonal(squares[2]) != 0 {
+                    (6 * 63 * 62 + 4 * 28 * 62 + rank_of(squares[0]) * 7 * 28
+                        + (rank_of(squares[1]) - adjust1) * 28 + ix.map_b1h1h7[squares[2]]) as u64
+                } else {
+                    (6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + rank_of(squares[0]) * 7 * 6
+                        + (rank_of(squares[1]) - adjust1) * 6 + (rank_of(squares[2]) - adjust2)) as u64
+                };
+            } else {
+                // Only the kings are encoded together.
+                idx = ix.map_kk[ix.map_a1d1d4[squares[0]]][squares[1]];
+            }
+        }
+
+        // The remaining groups: each one's squares, skipping the squares taken by earlier groups,
+        // as a combination.
+        idx *= d.group_idx[0];
+        let mut start = d.group_len[0];
+        let mut remaining_pawns = mat.has_pawns && mat.pawn_count[1] > 0;
+        let mut next = 1;
+        while next <= TB_PIECES && d.group_len[next] != 0 {
+            let len = d.group_len[next];
+            squares[start..start + len].sort();
+            let mut n: u64 = 0;
+            for i in 0..len {
+                let sq = squares[start + i];
+                let adjust = squares[..start].iter().filter(|s| sq > **s).count();
+                let below = if remaining_pawns { 8 } else { 0 };
+                n += ix.binomial[i + 1][(sq - adjust).checked_sub(below)?];
+            }
+            remaining_pawns = false;
+            idx += n * d.group_idx[next];
+            start += len;
+            next += 1;
+        }
+        return Some(Lookup::At(file, idx, side));
+    }
+}
+
+// Splits the pieces into the groups that are encoded together and works out the multiplier of each.
+fn set_groups(d: &mut PairsData, mat: &Material, order: [u8; 2], file: usize, ix: &Indexing) {
+    let mut n = 0;
+    let mut first_len: i32 = if mat.has_pawns { 0 } else if mat.has_unique_pieces { 3 } else { 2 };
+    d.group_len[0] = 1;
+    for i in 1..mat.piece_count {
+        first_len -= 1;
+        if first_len > 0 || d.pieces[i] == d.pieces[i - 1] {
+            d.group_len[n] += 1;
+        } else {
+            n += 1;
+            d.group_len[n] = 1;
+        }
+    }
+    n += 1;
+    d.group_len[n] = 0;
+
+    // The groups are combined in the order the table gives: the leading group at order[0], the
+    // other side's pawns (if both sides have pawns) at order[1], then the rest.
+    let pp = mat.has_pawns && mat.pawn_count[1] > 0;
+    let mut next = if pp { 2 } else { 1 };
+    let mut free = 64 - d.group_len[0] - if pp { d.group_len[1] } else { 0 };
+    let mut idx: u64 = 1;
+    let mut k = 0;
+    while next < n || k == order[0] as usize || k == order[1] as usize {
+        if k == order[0] as usize {
+            d.group_idx[0] = idx;
+            idx *= if mat.has_pawns {
+                ix.lead_pawns_size[d.group_len[0]][file]
+            } else if mat.has_unique_pieces {
+                31332
+            } else {
+                462
+            };
+        } else if k == order[1] as usize {
+            d.group_idx[1] = idx;
+            idx *= ix.binomial[d.group_len[1]][48 - d.group_len[0]];
+        } else {
+            d.group_idx[next] = idx;
+            idx *= ix.binomial[d.group_len[next]][free];
+            free -= d.group_len[next];
+            next += 1;
+        }
+        k += 1;
+    }
+    d.group_idx[n] = idx;
+}
+
+// These need the 3-man tables, which are not part of the repository, so they are ignored by
+// default. Point JCHESS_SYZYGY at a directory holding at least KQvK, KRvK and KPvK (.rtbw and
+// .rtbz) and run `cargo test -- --ignored`.
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::path::PathBuf;
+
+    use crate::chess::coordinate::to_coordinate;
+    use crate::chess::fen::parse_fen;
+    use crate::tablebase::Tablebase;
+
+    fn tablebase() -> Tablebase {
+        let dir = PathBuf::from(env::var("JCHESS_SYZYGY").expect("JCHESS_SYZYGY is not set"));
+        return Tablebase::open(&dir).unwrap();
+    }
+
+    // WDL and DTZ of each position, with the winning move where there is a mate in one.
+    fn check(tb: &Tablebase, cases: &[(&str, i32, i32, Option<&str>)]) {
+        for (fen, wdl, dtz, best) in cases {
+            let game = parse_fen(fen).unwrap();
+            assert_eq!(tb.probe_wdl(&game), Some(*wdl), "WDL of {}", fen);
+            assert_eq!(tb.probe_dtz(&game), Some(*dtz), "DTZ of {}", fen);
+            if let Some(best) = best {
+                let m = tb.best_move(&game).unwrap();
+                assert_eq!(to_coordinate(&game, &m), *best, "best move in {}", fen);
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "needs JCHESS_SYZYGY"]
+    fn kqvk() {
+        let tb = tablebase();
+        check(&tb, &[
+            ("7k/8/6K1/8/8/8/8/1Q6 w - - 0 1", 2, 1, Some("b1b8")),
+            // Kg8 is the only move, and Qb8 mates.
+            ("7k/8/6K1/8/8/8/8/1Q6 b - - 0 1", -2, -2, None),
+            // The queen is hanging and cannot be defended.
+            ("8/8/8/8/8/8/1k6/Q6K b - - 0 1", 0, 0, Some("b2a1")),
+        ]);
+    }
+
+    #[test]
+    #[ignore = "needs JCHESS_SYZYGY"]
+    fn krvk() {
+        let tb = tablebase();
+        check(&tb, &[
+            ("7k/8/6K1/8/8/8/8/R7 w - - 0 1", 2, 1, Some("a1a8")),
+            ("7k/8/6K1/8/8/8/8/R7 b - - 0 1", -2, -2, None),
+        ]);
+    }
+
+    #[test]
+    #[ignore = "needs JCHESS_SYZYGY"]
+    fn kpvk() {
+        let tb = tablebase();
+        check(&tb, &[
+            // Outside the king's square: the pawn move that starts the run is the zeroing move.
+            ("8/8/8/8/8/k7/7P/7K w - - 0 1", 2, 1, None),
+            ("8/8/8/8/8/k7/7P/7K b - - 0 1", -2, -2, None),
+            // A rook pawn with the defending king in the corner.
+            ("k7/8/8/8/8/8/P7/K7 w - - 0 1", 0, 0, None),
+            ("k7/8/8/8/8/8/P7/K7 b - - 0 1", 0, 0, None),
+        ]);
+    }
+}